@@ -1,46 +1,100 @@
-
-#![feature(test)]
-
+#[macro_use]
+extern crate bencher;
 extern crate ravro;
 extern crate rand;
 
-use ravro::datafile::{DataWriter, Codecs};
-
-use std::fs::OpenOptions;
-use ravro::types::Schema;
-use ravro::schema::AvroSchema;
+use bencher::Bencher;
+use ravro::{AvroReader, AvroSchema, AvroWriter, Codec, Type};
+use ravro::complex::{Record, Field};
+use std::collections::HashMap;
+use std::io::Cursor;
 
-use std::collections::BTreeMap;
+use rand::{thread_rng, Rng};
 
-use ravro::complex::{RecordSchema, Field};
+pub fn gen_rand_str() -> String {
+	thread_rng().gen_ascii_chars().take(20).collect()
+}
 
-extern crate test;
-use test::Bencher;
+fn nested_record() -> Record {
+	let name_field = Field::new("name", Type::Str(gen_rand_str()));
 
-use rand::StdRng;
-use rand::Rng;
+	let mut map = HashMap::new();
+	map.insert("Adfwf".to_owned(), Type::Float(234.455));
 
-pub fn gen_rand_str() -> String {
-	let mut std_rng = StdRng::new().unwrap();
-	let ascii_iter = std_rng.gen_ascii_chars();
-	ascii_iter.take(20).collect()
+	let map_field = Field::new("foo", Type::Map(map));
+	let inner_rec = Record::new("id_rec", None, vec![Field::new("id", Type::Long(3i64))]);
+	Record::new("dashboard_stats", None, vec![name_field, map_field, Field::new("inner_rec", Type::Record(inner_rec))])
 }
 
-#[bench]
 fn bench_write_nested_record(b: &mut Bencher) {
-	let schema_file = "tests/schemas/nested_schema.avsc";
-	let rec_schema = AvroSchema::from_file(schema_file).unwrap();
-	let datafile_name = "tests/encoded/nested_encoded.avro";
-	let writer = OpenOptions::new().write(true).create(true).open(datafile_name).unwrap();
-	let mut data_writer = DataWriter::new(rec_schema, writer, Codecs::Snappy).unwrap();
-	let name_field = Field::new("name", None, Schema::Str(gen_rand_str()));
-
-	let mut map = BTreeMap::new();
-	map.insert("Adfwf".to_owned(), Schema::Float(234.455));
-
-	let map_field = Field::new("foo", None, Schema::Map(map));
-	let inner_rec = RecordSchema::new("id_rec", None, vec![Field::new("id", None, Schema::Long(3i64))]);
-	let outer_rec = RecordSchema::new("dashboard_stats", None, vec![name_field, map_field,  Field::new("inner_rec", None, Schema::Record(inner_rec))]);
-	let mut writer_bench = |rec| {data_writer.write(rec)};
-    b.iter(|| writer_bench(outer_rec.clone()));
-}
\ No newline at end of file
+	let mut data_writer = AvroWriter::from_schema("tests/schemas/nested_schema.avsc").unwrap();
+	data_writer.set_codec(Codec::Snappy);
+	let mut data_writer = data_writer.build().unwrap();
+	let outer_rec = nested_record();
+	b.iter(|| {
+		data_writer.write(outer_rec.clone()).unwrap();
+		// Committed blocks are dropped instead of piling up in memory
+		data_writer.swap_buffer()
+	});
+}
+
+fn bench_write_nested_record_by_ref(b: &mut Bencher) {
+	let mut data_writer = AvroWriter::from_schema("tests/schemas/nested_schema.avsc").unwrap();
+	data_writer.set_codec(Codec::Snappy);
+	let mut data_writer = data_writer.build().unwrap();
+	let outer_rec = Type::Record(nested_record());
+	b.iter(|| {
+		data_writer.write_type(&outer_rec).unwrap();
+		data_writer.swap_buffer()
+	});
+}
+
+fn bench_read_nested_record(b: &mut Bencher) {
+	let mut data_writer = AvroWriter::from_schema("tests/schemas/nested_schema.avsc").unwrap();
+	data_writer.set_codec(Codec::Snappy);
+	let mut data_writer = data_writer.build().unwrap();
+	for _ in 0..1000 {
+		data_writer.write(nested_record()).unwrap();
+	}
+	let datafile = data_writer.take_datafile().unwrap();
+	b.iter(|| {
+		let reader = AvroReader::new(Cursor::new(&datafile)).unwrap();
+		reader.map(|r| r.unwrap()).count()
+	});
+}
+
+fn journal_datafile() -> Vec<u8> {
+	let schema = AvroSchema::from_file("tests/schemas/journal.avsc").unwrap();
+	let fields = schema.record_field_pairs().unwrap();
+	let mut data_writer = AvroWriter::from_schema("tests/schemas/journal.avsc").unwrap().build().unwrap();
+	for _ in 0..1000 {
+		let record = fields.iter().map(|&(ref name, _)| Field::new(name, Type::Str(gen_rand_str()))).collect();
+		data_writer.write(Record::new("dashboard_stats", None, record)).unwrap();
+	}
+	data_writer.take_datafile().unwrap()
+}
+
+fn bench_read_journal(b: &mut Bencher) {
+	let datafile = journal_datafile();
+	b.iter(|| {
+		let reader = AvroReader::new(Cursor::new(&datafile)).unwrap();
+		reader.map(|r| r.unwrap()).count()
+	});
+}
+
+fn bench_read_projected_journal(b: &mut Bencher) {
+	let datafile = journal_datafile();
+	let reader_schema = AvroSchema::from_str(r#"{"type": "record", "name": "dashboard_stats", "fields": [
+		{"name": "__REALTIME_TIMESTAMP", "type": "string"},
+		{"name": "_HOSTNAME", "type": "string"},
+		{"name": "MESSAGE", "type": "string"}
+	]}"#).unwrap();
+	b.iter(|| {
+		let mut reader = AvroReader::new(Cursor::new(&datafile)).unwrap();
+		reader.set_reader_schema(&reader_schema).unwrap();
+		reader.map(|r| r.unwrap()).count()
+	});
+}
+
+benchmark_group!(benches, bench_write_nested_record, bench_write_nested_record_by_ref, bench_read_nested_record, bench_read_journal, bench_read_projected_journal);
+benchmark_main!(benches);
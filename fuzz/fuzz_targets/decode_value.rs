@@ -0,0 +1,29 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+#[macro_use]
+extern crate lazy_static;
+extern crate ravro;
+
+use ravro::{AvroSchema, Type, TypeRef};
+use ravro::schema::CompiledSchema;
+use std::io::Cursor;
+
+lazy_static! {
+	static ref SCHEMAS: Vec<CompiledSchema> = [
+		include_str!("../../tests/schemas/nested_schema.avsc"),
+		include_str!("../../tests/schemas/union_schema.avsc"),
+		include_str!("../../tests/schemas/mapmap_schema.avsc"),
+		include_str!("../../tests/schemas/array_schema.avsc"),
+		include_str!("../../tests/schemas/enum_schema.avsc")
+	].iter().map(|schema| AvroSchema::from_str(schema).unwrap().compile().unwrap()).collect();
+}
+
+// The first byte picks the schema the rest of the input is decoded with
+fuzz_target!(|data: &[u8]| {
+	if let Some((&pick, data)) = data.split_first() {
+		let schema = &SCHEMAS[pick as usize % SCHEMAS.len()];
+		let _ = Type::decode_with(schema, &mut Cursor::new(data));
+		let _ = TypeRef::decode(schema, &mut &data[..]);
+	}
+});
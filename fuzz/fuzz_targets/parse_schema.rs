@@ -0,0 +1,16 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate ravro;
+
+use ravro::AvroSchema;
+use std::str;
+
+fuzz_target!(|data: &[u8]| {
+	if let Ok(declaration) = str::from_utf8(data) {
+		if let Ok(schema) = AvroSchema::from_str(declaration) {
+			let _ = schema.compile();
+			let _ = schema.record_field_pairs();
+		}
+	}
+});
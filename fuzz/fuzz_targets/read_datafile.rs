@@ -0,0 +1,26 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate ravro;
+
+use ravro::{AvroReader, SliceReader};
+use std::io::Cursor;
+
+fuzz_target!(|data: &[u8]| {
+	if let Ok(reader) = AvroReader::new(Cursor::new(data)) {
+		for _ in reader {}
+	}
+	if let Ok(reader) = AvroReader::new(Cursor::new(data)) {
+		for _ in reader.recover() {}
+	}
+	if let Ok(reader) = SliceReader::new(data) {
+		if let Ok(records) = reader.records() {
+			for _ in records {}
+		}
+		for block in reader.blocks().flatten() {
+			if let Ok(block_data) = block.decompress() {
+				for _ in reader.block_records(&block_data, block.count) {}
+			}
+		}
+	}
+});
@@ -1,3 +0,0 @@
-#! /usr/bin/env sh
-
-wget 'http://redrockdigimark.com/apachemirror/avro/avro-1.8.2/java/avro-tools-1.8.2.jar'
\ No newline at end of file
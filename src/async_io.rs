@@ -0,0 +1,321 @@
+//! This module declares an `AsyncAvroReader` and an `AsyncAvroWriter`, which read and write avro
+//! data files over tokio's `AsyncRead` and `AsyncWrite`. The reader is a `Stream` of records and
+//! the writer a `Sink` of them. They are enabled with the `async` feature.
+
+use std::io::{self, Cursor, ErrorKind, Read};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use futures_sink::Sink;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use codec::{Decoder, DecoderLimits};
+use reader::check_block_data;
+use errors::AvroErr;
+use schema::CompiledSchema;
+use types::Type;
+use writer::{check_block_size, split_block, AvroWriter, Codec, Header, WriterBuilder};
+
+/// No of bytes asked for on each read from the underlying reader
+const READ_SIZE: usize = 8 * 1024;
+
+/// `AsyncAvroReader` reads an avro data file from an `AsyncRead` and streams the records in it.
+/// Blocks are decoded once they are read in full, the same way as `AvroReader` does.
+#[derive(Debug)]
+pub struct AsyncAvroReader<R> {
+	/// The underlying data file
+	reader: R,
+	/// Data read from the data file which was not decoded yet
+	buffer: Vec<u8>,
+	/// Byte offset in the data file of the start of `buffer`
+	buffer_offset: u64,
+	/// Set once the underlying reader is exhausted
+	eof: bool,
+	/// The header of the data file, once it has been read
+	header: Option<Header>,
+	/// The codec the data blocks are compressed with
+	codec: Codec,
+	/// The writer's schema, compiled for decoding records
+	schema: Option<CompiledSchema>,
+	/// Decompressed contents of the block being read
+	block: Cursor<Vec<u8>>,
+	/// No of records left to be read in the current block
+	block_remaining: i64,
+	/// No of items of arrays encoded in no bytes decoded out of the current block
+	block_zero_width_items: u64,
+	/// No of blocks read so far
+	blocks_read: u64,
+	/// Byte offset in the data file of the current block
+	block_offset: u64,
+	/// Limits on the blocks and records decoded
+	limits: DecoderLimits,
+	/// Set once the end of the data file is reached or an error was encountered
+	finished: bool
+}
+
+impl<R: AsyncRead + Unpin> AsyncAvroReader<R> {
+	/// Creates a reader over the given data file. The header is read when the first record is polled for.
+	pub fn new(reader: R) -> Self {
+		AsyncAvroReader {
+			reader,
+			buffer: vec![],
+			buffer_offset: 0,
+			eof: false,
+			header: None,
+			codec: Codec::Null,
+			schema: None,
+			block: Cursor::new(vec![]),
+			block_remaining: 0,
+			block_zero_width_items: 0,
+			blocks_read: 0,
+			block_offset: 0,
+			limits: DecoderLimits::default(),
+			finished: false
+		}
+	}
+
+	/// Retrieves a reference to the header of the data file, once it has been read
+	pub fn header(&self) -> Option<&Header> {
+		self.header.as_ref()
+	}
+
+	/// Sets the limits on the blocks and records read, the same way as `AvroReader::set_limits` does.
+	/// The header and blocks are buffered whole, so the block size limit also bounds the memory used for that.
+	pub fn set_limits(&mut self, limits: DecoderLimits) {
+		self.limits = limits;
+	}
+
+	/// Gives back the underlying reader
+	pub fn into_inner(self) -> R {
+		self.reader
+	}
+
+	/// Reads more of the data file into the buffer, noting when the end of it is reached
+	fn poll_fill(&mut self, cx: &mut Context) -> Poll<Result<(), AvroErr>> {
+		let len = self.buffer.len();
+		self.buffer.resize(len + READ_SIZE, 0);
+		let (poll, filled) = {
+			let mut read_buf = ReadBuf::new(&mut self.buffer[len..]);
+			let poll = Pin::new(&mut self.reader).poll_read(cx, &mut read_buf);
+			(poll, read_buf.filled().len())
+		};
+		self.buffer.truncate(len + filled);
+		match poll {
+			Poll::Ready(Ok(())) => {
+				self.eof = filled == 0;
+				Poll::Ready(Ok(()))
+			}
+			Poll::Ready(Err(e)) => Poll::Ready(Err(e.into())),
+			Poll::Pending => Poll::Pending
+		}
+	}
+
+	/// Decodes the header or the next block out of the buffered data. Gives back false
+	/// if more of the data file has to be read first.
+	fn advance(&mut self) -> Result<bool, AvroErr> {
+		let consumed = match self.header {
+			None => {
+				let mut buf = EndTracker { buf: &self.buffer[..], hit_end: false };
+				match Header::decode_with_limits(&mut buf, &self.limits) {
+					Ok(header) => {
+						self.codec = header.get_codec()?;
+						self.schema = Some(header.schema.compile()?);
+						self.header = Some(header);
+						self.buffer.len() - buf.buf.len()
+					}
+					// The header may go on past the data read so far
+					Err(_) if buf.hit_end && !self.eof => {
+						check_block_size(self.buffer.len() as u64, self.limits.max_block_size)?;
+						return Ok(false);
+					}
+					Err(e) => return Err(e)
+				}
+			}
+			Some(ref header) => {
+				if self.buffer.is_empty() && self.eof {
+					self.finished = true;
+					return Ok(true);
+				}
+				match split_block(&self.buffer, header.sync_marker.as_bytes(), self.buffer_offset, &self.limits) {
+					Ok((count, block_data, len)) => {
+						let index = Some(self.blocks_read);
+						let schema = self.schema.as_ref().ok_or_else(|| AvroErr::decode("Blocks found before the header"))?;
+						let data = self.codec.decompress_limited(block_data, self.limits.max_block_size)
+							.and_then(|data| check_block_data(schema, count, data))
+							.map_err(|e| e.in_block(index, self.buffer_offset))?;
+						self.block = Cursor::new(data);
+						self.block_remaining = count;
+						self.block_zero_width_items = 0;
+						self.block_offset = self.buffer_offset;
+						self.blocks_read += 1;
+						len
+					}
+					Err(AvroErr::TruncatedBlock(_)) if !self.eof => return Ok(false),
+					Err(e) => return Err(e)
+				}
+			}
+		};
+		self.buffer.drain(..consumed);
+		self.buffer_offset += consumed as u64;
+		Ok(true)
+	}
+}
+
+/// Reads out of a byte slice, noting whether a read ran into the end of it
+struct EndTracker<'a> {
+	buf: &'a [u8],
+	hit_end: bool
+}
+
+impl<'a> Read for EndTracker<'a> {
+	fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+		if self.buf.len() < out.len() {
+			self.hit_end = true;
+		}
+		self.buf.read(out)
+	}
+}
+
+impl<R: AsyncRead + Unpin> Stream for AsyncAvroReader<R> {
+	type Item = Result<Type, AvroErr>;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+		let this = self.get_mut();
+		loop {
+			if this.finished {
+				return Poll::Ready(None);
+			}
+			if this.block_remaining > 0 {
+				this.block_remaining -= 1;
+				let (block, zero_width_items) = (&mut this.block, &mut this.block_zero_width_items);
+				let (index, offset, limits) = (Some(this.blocks_read - 1), this.block_offset, this.limits);
+				let record = this.schema.as_ref()
+					.ok_or_else(|| AvroErr::decode("Records found before the header"))
+					.and_then(|schema| Type::decode_limited(schema, schema.root(), &limits, 0, zero_width_items, block))
+					.map_err(|e| e.in_block(index, offset));
+				this.finished = record.is_err();
+				return Poll::Ready(Some(record));
+			}
+			let advanced = match this.advance() {
+				Ok(true) => continue,
+				Ok(false) => this.poll_fill(cx),
+				Err(e) => Poll::Ready(Err(e))
+			};
+			match advanced {
+				Poll::Ready(Ok(())) => (),
+				Poll::Ready(Err(e)) => {
+					this.finished = true;
+					return Poll::Ready(Some(Err(e)));
+				}
+				Poll::Pending => return Poll::Pending
+			}
+		}
+	}
+}
+
+/// `AsyncAvroWriter` writes an avro data file to an `AsyncWrite`. Records are encoded and
+/// compressed into blocks by an `AvroWriter`, and committed blocks are written out as the
+/// sink is polled. Flushing the sink commits the current block, so records should be passed
+/// to it with `SinkExt::feed` or `SinkExt::send_all` rather than `SinkExt::send`, which
+/// flushes after every record.
+pub struct AsyncAvroWriter<W> {
+	/// The underlying data file
+	writer: W,
+	/// Encodes records into blocks
+	inner: AvroWriter,
+	/// Encoded data waiting to be written out
+	pending: Vec<u8>,
+	/// No of bytes of `pending` written out so far
+	written: usize
+}
+
+impl<W: AsyncWrite + Unpin> AsyncAvroWriter<W> {
+	/// Creates a writer which writes the data file set up by the given builder to `writer`
+	pub fn new(builder: WriterBuilder, writer: W) -> Result<Self, AvroErr> {
+		Ok(AsyncAvroWriter {
+			writer,
+			inner: builder.build()?,
+			pending: vec![],
+			written: 0
+		})
+	}
+
+	/// Writes a value to the current block, the same way as `AvroWriter::write` does. Blocks
+	/// committed by it are written out the next time the sink is polled.
+	pub fn write<T: Into<Type>>(&mut self, value: T) -> Result<(), AvroErr> {
+		self.inner.write(value)
+	}
+
+	/// Retrieves a reference to the underlying writer
+	pub fn get_ref(&self) -> &W {
+		&self.writer
+	}
+
+	/// Gives back the underlying writer. Data which was not flushed is lost.
+	pub fn into_inner(self) -> W {
+		self.writer
+	}
+
+	/// Writes out the committed blocks, without waiting for the worker threads compressing them.
+	/// Gives back once at most `max_compressing` blocks are left being compressed.
+	fn poll_write_pending(&mut self, cx: &mut Context, max_compressing: u64) -> Poll<Result<(), AvroErr>> {
+		let mut registered = false;
+		loop {
+			if self.written == self.pending.len() {
+				let (pending, compressing) = self.inner.take_compressed()?;
+				self.pending = pending;
+				self.written = 0;
+				if self.pending.is_empty() {
+					if compressing <= max_compressing {
+						return Poll::Ready(Ok(()));
+					}
+					if registered {
+						return Poll::Pending;
+					}
+					// Blocks compressed before the waker was registered do not wake the task,
+					// so they are taken once more after registering it
+					self.inner.wake_on_compressed(cx.waker());
+					registered = true;
+					continue;
+				}
+			}
+			match Pin::new(&mut self.writer).poll_write(cx, &self.pending[self.written..]) {
+				Poll::Ready(Ok(0)) => return Poll::Ready(Err(io::Error::new(ErrorKind::WriteZero, "failed to write the data file").into())),
+				Poll::Ready(Ok(written)) => self.written += written,
+				Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
+				Poll::Pending => return Poll::Pending
+			}
+		}
+	}
+}
+
+impl<W: AsyncWrite + Unpin, T: Into<Type>> Sink<T> for AsyncAvroWriter<W> {
+	type Error = AvroErr;
+
+	fn poll_ready(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), AvroErr>> {
+		let this = self.get_mut();
+		let max_compressing = 2 * this.inner.workers() as u64;
+		this.poll_write_pending(cx, max_compressing)
+	}
+
+	fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), AvroErr> {
+		self.get_mut().inner.write(item)
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), AvroErr>> {
+		let this = self.get_mut();
+		this.inner.commit_pending()?;
+		match this.poll_write_pending(cx, 0) {
+			Poll::Ready(Ok(())) => Pin::new(&mut this.writer).poll_flush(cx).map_err(AvroErr::from),
+			other => other
+		}
+	}
+
+	fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), AvroErr>> {
+		match Sink::<T>::poll_flush(self.as_mut(), cx) {
+			Poll::Ready(Ok(())) => Pin::new(&mut self.get_mut().writer).poll_shutdown(cx).map_err(AvroErr::from),
+			other => other
+		}
+	}
+}
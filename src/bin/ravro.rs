@@ -0,0 +1,378 @@
+//! `ravro` is a command line tool for inspecting and writing avro data files, built on the reader
+//! and writer of this crate. Its commands follow the ones of avro-tools.
+
+extern crate clap;
+#[macro_use]
+extern crate failure;
+extern crate rand;
+extern crate ravro;
+extern crate serde_json;
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::process;
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use failure::Error;
+use rand::{thread_rng, Rng};
+use serde_json::Value;
+
+use ravro::check::check;
+use ravro::json::{from_json, to_json, to_json_pretty};
+use ravro::random::RandomGenerator;
+use ravro::writer::Header;
+use ravro::{AvroReader, AvroWriter, Codec, Type};
+
+/// No of records written by `fromjson` and `random` between writes of the data file to its output
+const FLUSH_RECORDS: u64 = 4096;
+
+fn input_arg() -> Arg<'static, 'static> {
+	Arg::with_name("input").help("The data file to read, or - for stdin").default_value("-")
+}
+
+fn output_arg() -> Arg<'static, 'static> {
+	Arg::with_name("output").short("o").long("output").takes_value(true).help("The data file to write, instead of stdout")
+}
+
+fn codec_arg() -> Arg<'static, 'static> {
+	Arg::with_name("codec").long("codec").takes_value(true).possible_values(&["null", "deflate", "snappy"])
+		.help("The codec to compress blocks with")
+}
+
+fn main() {
+	let matches = App::new("ravro")
+		.version(env!("CARGO_PKG_VERSION"))
+		.about("Inspects and writes avro data files")
+		.setting(AppSettings::SubcommandRequiredElseHelp)
+		.subcommand(SubCommand::with_name("tojson")
+			.about("Prints the records of a data file as JSON, one record per line")
+			.arg(Arg::with_name("pretty").long("pretty").help("Prints each record indented over several lines"))
+			.arg(input_arg()))
+		.subcommand(SubCommand::with_name("fromjson")
+			.about("Writes JSON records, separated by whitespace, to a data file")
+			.arg(Arg::with_name("schema").long("schema").takes_value(true).required(true).help("The schema file to write records with"))
+			.arg(codec_arg().default_value("null"))
+			.arg(output_arg())
+			.arg(Arg::with_name("input").help("The JSON records to read, or - for stdin").default_value("-")))
+		.subcommand(SubCommand::with_name("getschema")
+			.about("Prints the schema of a data file")
+			.arg(input_arg()))
+		.subcommand(SubCommand::with_name("getmeta")
+			.about("Prints the metadata of a data file, one tab separated key and value per line")
+			.arg(Arg::with_name("key").long("key").takes_value(true).help("Prints only the value of the given key"))
+			.arg(input_arg()))
+		.subcommand(SubCommand::with_name("count")
+			.about("Prints the no of records in a data file, without decoding them")
+			.arg(input_arg()))
+		.subcommand(SubCommand::with_name("concat")
+			.about("Joins data files with the same schema and metadata into one, copying their blocks without decoding them")
+			.arg(codec_arg().help("The codec to compress blocks with, instead of the one of the first data file"))
+			.arg(output_arg())
+			.arg(Arg::with_name("input").help("The data files to join, or - for stdin").required(true).multiple(true)))
+		.subcommand(SubCommand::with_name("recodec")
+			.about("Compresses the blocks of a data file with another codec, without decoding its records")
+			.arg(codec_arg().required(true))
+			.arg(output_arg())
+			.arg(input_arg()))
+		.subcommand(SubCommand::with_name("random")
+			.about("Writes random records of a schema to a data file")
+			.arg(Arg::with_name("schema").long("schema").takes_value(true).required(true).help("The schema file to write records with"))
+			.arg(Arg::with_name("count").long("count").takes_value(true).required(true).help("The no of records to write"))
+			.arg(Arg::with_name("seed").long("seed").takes_value(true).help("The seed of the records, to write the same records again"))
+			.arg(Arg::with_name("max-string-len").long("max-string-len").takes_value(true)
+				.help("The maximum length of strings and bytes, 16 by default"))
+			.arg(Arg::with_name("max-items").long("max-items").takes_value(true).help("The maximum no of items of arrays and maps, 8 by default"))
+			.arg(codec_arg().default_value("null"))
+			.arg(output_arg()))
+		.subcommand(SubCommand::with_name("check")
+			.about("Checks the integrity of a data file, decoding all of its records, and prints a report of its blocks")
+			.arg(input_arg()))
+		.get_matches();
+
+	let result = match matches.subcommand() {
+		("tojson", Some(args)) => tojson(args),
+		("fromjson", Some(args)) => fromjson(args),
+		("getschema", Some(args)) => getschema(args),
+		("getmeta", Some(args)) => getmeta(args),
+		("count", Some(args)) => count(args),
+		("concat", Some(args)) => concat(args),
+		("recodec", Some(args)) => recodec(args),
+		("random", Some(args)) => random(args),
+		("check", Some(args)) => check_datafile(args),
+		_ => unreachable!("A subcommand is required")
+	};
+	if let Err(e) = result {
+		if is_broken_pipe(&e) {
+			return;
+		}
+		eprintln!("ravro: {}", e);
+		process::exit(1);
+	}
+}
+
+/// Whether the error is from writing to a pipe closed by its reader, as in `ravro tojson | head`
+fn is_broken_pipe(e: &Error) -> bool {
+	let io_error = match e.downcast_ref::<ravro::errors::AvroErr>() {
+		Some(ravro::errors::AvroErr::Io(e)) => Some(e),
+		_ => e.downcast_ref::<io::Error>()
+	};
+	io_error.map_or(false, |e| e.kind() == io::ErrorKind::BrokenPipe)
+}
+
+/// Opens the named file, or stdin for `-`
+fn open_input(path: &str) -> Result<Box<dyn Read>, Error> {
+	if path == "-" {
+		return Ok(Box::new(io::stdin()));
+	}
+	let file = File::open(path).map_err(|e| format_err!("Failed to open {}: {}", path, e))?;
+	Ok(Box::new(BufReader::new(file)))
+}
+
+/// Opens the data file named by the `input` argument
+fn open_reader(args: &ArgMatches) -> Result<AvroReader<Box<dyn Read>>, Error> {
+	let input = args.value_of("input").unwrap_or("-");
+	Ok(AvroReader::new(open_input(input)?)?)
+}
+
+/// Creates the data file named by the `output` argument, or writes to stdout without one
+fn open_output(args: &ArgMatches) -> Result<Box<dyn Write>, Error> {
+	match args.value_of("output") {
+		Some(path) => {
+			let file = File::create(path).map_err(|e| format_err!("Failed to create {}: {}", path, e))?;
+			Ok(Box::new(BufWriter::new(file)))
+		}
+		None => Ok(Box::new(BufWriter::new(io::stdout())))
+	}
+}
+
+/// Refuses to write the output over one of the inputs, which creating it would truncate before it is read
+fn check_output(args: &ArgMatches, inputs: &[&str]) -> Result<(), Error> {
+	let output = match args.value_of("output") {
+		Some(output) => output,
+		None => return Ok(())
+	};
+	// An output which does not exist yet is none of the inputs
+	if let Ok(output_path) = fs::canonicalize(output) {
+		if inputs.iter().any(|input| fs::canonicalize(input).ok().as_ref() == Some(&output_path)) {
+			bail!("Output {} is also an input", output);
+		}
+	}
+	Ok(())
+}
+
+/// Appends the blocks of a data file to the writer, writing them out as they are appended
+fn copy_blocks<R: Read>(reader: AvroReader<R>, writer: &mut AvroWriter, out: &mut dyn Write) -> Result<(), Error> {
+	for block in reader.iter_block() {
+		writer.append_block(&block?)?;
+		out.write_all(&writer.try_swap_buffer()?)?;
+	}
+	Ok(())
+}
+
+/// Gives the metadata of the header of a data file
+fn metadata(header: &Header) -> BTreeMap<&str, &[u8]> {
+	let mut metadata = BTreeMap::new();
+	if let Type::Map(ref map) = header.metadata {
+		for (key, value) in map {
+			if let Type::Bytes(ref value) = *value {
+				metadata.insert(key.as_str(), value.as_slice());
+			}
+		}
+	}
+	metadata
+}
+
+/// Gives the metadata of the header of a data file other than the `avro.` keys, which the writer sets
+fn user_metadata(header: &Header) -> BTreeMap<String, Vec<u8>> {
+	metadata(header).into_iter()
+		.filter(|&(key, _)| !key.starts_with("avro."))
+		.map(|(key, value)| (key.to_string(), value.to_vec()))
+		.collect()
+}
+
+fn tojson(args: &ArgMatches) -> Result<(), Error> {
+	let reader = open_reader(args)?;
+	let schema = reader.get_schema().compile()?;
+	let pretty = args.is_present("pretty");
+	let stdout = io::stdout();
+	let mut out = BufWriter::new(stdout.lock());
+	for record in reader {
+		let record = record?;
+		let json = if pretty { to_json_pretty(&schema, &record)? } else { to_json(&schema, &record)? };
+		writeln!(out, "{}", json)?;
+	}
+	out.flush()?;
+	Ok(())
+}
+
+fn fromjson(args: &ArgMatches) -> Result<(), Error> {
+	let mut builder = AvroWriter::from_schema(args.value_of("schema").unwrap_or_default())?;
+	builder.set_codec(args.value_of("codec").unwrap_or("null").parse::<Codec>()?);
+	let mut writer = builder.build()?;
+	let schema = writer.get_schema().compile()?;
+	let input = open_input(args.value_of("input").unwrap_or("-"))?;
+	let mut out = open_output(args)?;
+	let mut written = 0;
+	for json in serde_json::Deserializer::from_reader(input).into_iter::<Value>() {
+		let json = json.map_err(|e| format_err!("Invalid JSON after {} records: {}", written, e))?;
+		let value = from_json(&schema, &json).map_err(|e| format_err!("Invalid record {}: {}", written, e))?;
+		writer.write(value)?;
+		written += 1;
+		if written % FLUSH_RECORDS == 0 {
+			out.write_all(&writer.try_swap_buffer()?)?;
+		}
+	}
+	if written % FLUSH_RECORDS != 0 {
+		writer.commit_block()?;
+	}
+	out.write_all(&writer.try_swap_buffer()?)?;
+	out.flush()?;
+	Ok(())
+}
+
+fn getschema(args: &ArgMatches) -> Result<(), Error> {
+	let reader = open_reader(args)?;
+	// The schema is printed as it is stored in the header
+	let schema = metadata(reader.header()).get("avro.schema").map(|schema| String::from_utf8_lossy(schema).into_owned())
+		.ok_or_else(|| format_err!("Data file has no schema"))?;
+	println!("{}", schema);
+	Ok(())
+}
+
+fn getmeta(args: &ArgMatches) -> Result<(), Error> {
+	let reader = open_reader(args)?;
+	let metadata = metadata(reader.header());
+	match args.value_of("key") {
+		Some(key) => {
+			let value = metadata.get(key).ok_or_else(|| format_err!("No metadata for key {}", key))?;
+			println!("{}", String::from_utf8_lossy(value));
+		}
+		None => {
+			for (key, value) in metadata {
+				println!("{}\t{}", key, String::from_utf8_lossy(value));
+			}
+		}
+	}
+	Ok(())
+}
+
+fn count(args: &ArgMatches) -> Result<(), Error> {
+	let reader = open_reader(args)?;
+	let mut count = 0;
+	for block in reader.iter_block() {
+		count += block?.count;
+	}
+	println!("{}", count);
+	Ok(())
+}
+
+fn concat(args: &ArgMatches) -> Result<(), Error> {
+	let inputs: Vec<&str> = args.values_of("input").map(|inputs| inputs.collect()).unwrap_or_default();
+	check_output(args, &inputs)?;
+	let first = AvroReader::new(open_input(inputs[0])?)?;
+	let schema = first.get_schema().compile()?;
+	let metadata = user_metadata(first.header());
+	let mut builder = AvroWriter::with_schema(first.get_schema().clone());
+	builder.set_codec(match args.value_of("codec") {
+		Some(codec) => codec.parse()?,
+		None => first.get_codec()
+	});
+	for (key, value) in &metadata {
+		builder.set_metadata(key.as_str(), value.clone())?;
+	}
+	let mut writer = builder.build()?;
+	let mut out = open_output(args)?;
+	copy_blocks(first, &mut writer, &mut out)?;
+	for input in &inputs[1..] {
+		let reader = AvroReader::new(open_input(input)?)?;
+		if reader.get_schema().compile()? != schema {
+			bail!("Schema of {} differs from the schema of {}", input, inputs[0]);
+		}
+		if user_metadata(reader.header()) != metadata {
+			bail!("Metadata of {} differs from the metadata of {}", input, inputs[0]);
+		}
+		copy_blocks(reader, &mut writer, &mut out)?;
+	}
+	// Writes the header, when no blocks were copied
+	out.write_all(&writer.try_swap_buffer()?)?;
+	out.flush()?;
+	Ok(())
+}
+
+fn recodec(args: &ArgMatches) -> Result<(), Error> {
+	check_output(args, &[args.value_of("input").unwrap_or("-")])?;
+	let reader = open_reader(args)?;
+	let mut builder = AvroWriter::with_schema(reader.get_schema().clone());
+	builder.set_codec(args.value_of("codec").unwrap_or("null").parse()?);
+	for (key, value) in user_metadata(reader.header()) {
+		builder.set_metadata(key, value)?;
+	}
+	let mut writer = builder.build()?;
+	let mut out = open_output(args)?;
+	copy_blocks(reader, &mut writer, &mut out)?;
+	out.write_all(&writer.try_swap_buffer()?)?;
+	out.flush()?;
+	Ok(())
+}
+
+/// Parses the value of a numeric argument
+fn number_arg(args: &ArgMatches, name: &str) -> Result<Option<u64>, Error> {
+	match args.value_of(name) {
+		Some(value) => Ok(Some(value.parse().map_err(|_| format_err!("Invalid {}: {}", name, value))?)),
+		None => Ok(None)
+	}
+}
+
+fn random(args: &ArgMatches) -> Result<(), Error> {
+	let mut builder = AvroWriter::from_schema(args.value_of("schema").unwrap_or_default())?;
+	builder.set_codec(args.value_of("codec").unwrap_or("null").parse::<Codec>()?);
+	let mut writer = builder.build()?;
+	let seed = number_arg(args, "seed")?.unwrap_or_else(|| thread_rng().gen());
+	let mut generator = RandomGenerator::new(writer.get_schema().compile()?, seed)?;
+	if let Some(len) = number_arg(args, "max-string-len")? {
+		generator.set_max_string_len(len as usize);
+	}
+	if let Some(items) = number_arg(args, "max-items")? {
+		generator.set_max_items(items as usize);
+	}
+	let count = number_arg(args, "count")?.unwrap_or_default();
+	let mut out = open_output(args)?;
+	for written in 1..=count {
+		writer.write(generator.generate())?;
+		if written % FLUSH_RECORDS == 0 {
+			out.write_all(&writer.try_swap_buffer()?)?;
+		}
+	}
+	if count % FLUSH_RECORDS != 0 {
+		writer.commit_block()?;
+	}
+	out.write_all(&writer.try_swap_buffer()?)?;
+	out.flush()?;
+	Ok(())
+}
+
+fn check_datafile(args: &ArgMatches) -> Result<(), Error> {
+	let input = args.value_of("input").unwrap_or("-");
+	let report = check(open_input(input)?);
+	let stdout = io::stdout();
+	let mut out = stdout.lock();
+	for block in &report.blocks {
+		write!(out, "block {} at offset {}: {} records in {} bytes: ", block.index, block.offset, block.count, block.size)?;
+		match block.error {
+			Some(ref e) if block.decoded > 0 => writeln!(out, "failed after {} records: {}", block.decoded, e)?,
+			Some(ref e) => writeln!(out, "failed: {}", e)?,
+			None => writeln!(out, "ok")?
+		}
+	}
+	writeln!(out, "{} blocks with {} records checked", report.blocks.len(), report.records())?;
+	out.flush()?;
+	if let Some(e) = report.fatal {
+		bail!("{} is corrupt: {}", input, e);
+	}
+	let failed = report.failed_blocks().len();
+	if failed > 0 {
+		bail!("{} is corrupt: {} of {} blocks failed the check", input, failed, report.blocks.len());
+	}
+	Ok(())
+}
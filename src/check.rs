@@ -0,0 +1,119 @@
+//! This module checks the integrity of avro data files: the magic bytes, metadata and schema of the
+//! header, the count, size and sync marker of every block, the decompression of blocks, along with
+//! the checksums of snappy blocks, and the decoding of every record against the schema.
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use codec::DecoderLimits;
+use errors::AvroErr;
+use reader::{check_block_data, AvroReader, Block};
+use schema::CompiledSchema;
+use types::TypeRef;
+use writer::Codec;
+
+/// The outcome of checking a data block
+#[derive(Debug)]
+pub struct BlockCheck {
+	/// No of the block in the data file, counting from zero
+	pub index: u64,
+	/// Byte offset of the block in the data file
+	pub offset: u64,
+	/// No of records in the block
+	pub count: i64,
+	/// Size in bytes of the block data as stored in the data file
+	pub size: usize,
+	/// No of records decoded, up to the first one that could not be
+	pub decoded: i64,
+	/// What is wrong with the block, if anything
+	pub error: Option<AvroErr>
+}
+
+/// The outcome of checking a data file
+#[derive(Debug, Default)]
+pub struct CheckReport {
+	/// Codec of the data file, once its header was read
+	pub codec: Option<Codec>,
+	/// The blocks checked, in the order they are stored in the data file
+	pub blocks: Vec<BlockCheck>,
+	/// The error the check stopped at, which leaves the rest of the data file unreadable: a
+	/// broken header, or a block whose end could not be found
+	pub fatal: Option<AvroErr>
+}
+
+impl CheckReport {
+	/// Whether the data file passed the check
+	pub fn is_ok(&self) -> bool {
+		self.fatal.is_none() && self.blocks.iter().all(|block| block.error.is_none())
+	}
+
+	/// Returns the no of records in the blocks checked
+	pub fn records(&self) -> u64 {
+		self.blocks.iter().map(|block| block.count as u64).sum()
+	}
+
+	/// Returns the blocks that failed the check
+	pub fn failed_blocks(&self) -> Vec<&BlockCheck> {
+		self.blocks.iter().filter(|block| block.error.is_some()).collect()
+	}
+}
+
+/// Checks the data file at the given path
+pub fn check_file<P: AsRef<Path>>(path: P) -> CheckReport {
+	match File::open(path) {
+		Ok(file) => check(BufReader::new(file)),
+		Err(e) => CheckReport { fatal: Some(AvroErr::Io(e)), ..CheckReport::default() }
+	}
+}
+
+/// Checks the data file read from the given stream, which must be positioned at its start. Blocks
+/// that fail to decompress or decode are reported and the check carries on with the next block.
+pub fn check<R: Read>(reader: R) -> CheckReport {
+	let mut report = CheckReport::default();
+	let reader = match AvroReader::new(reader) {
+		Ok(reader) => reader,
+		Err(e) => {
+			report.fatal = Some(e);
+			return report;
+		}
+	};
+	report.codec = Some(reader.get_codec());
+	let schema = match reader.get_schema().compile() {
+		Ok(schema) => schema,
+		Err(e) => {
+			report.fatal = Some(e);
+			return report;
+		}
+	};
+	let limits = DecoderLimits::default();
+	for (index, block) in reader.iter_block().enumerate() {
+		let index = index as u64;
+		match block {
+			Ok(block) => {
+				let mut decoded = 0;
+				let error = check_block(&schema, &limits, &block, &mut decoded).err().map(|e| e.in_block(Some(index), block.offset));
+				report.blocks.push(BlockCheck { index, offset: block.offset, count: block.count, size: block.data.len(), decoded, error });
+			}
+			Err(e) => report.fatal = Some(e)
+		}
+	}
+	report
+}
+
+/// Decompresses a block and decodes its records, which must take up all of its data, counting the
+/// records decoded
+fn check_block(schema: &CompiledSchema, limits: &DecoderLimits, block: &Block, decoded: &mut i64) -> Result<(), AvroErr> {
+	let data = block.codec.decompress_limited(&block.data, limits.max_block_size)?;
+	let data = check_block_data(schema, block.count, data)?;
+	let mut buf = &data[..];
+	let mut zero_width_items = 0;
+	while *decoded < block.count {
+		TypeRef::decode_limited(schema, schema.root(), limits, 0, &mut zero_width_items, &mut buf)?;
+		*decoded += 1;
+	}
+	if !buf.is_empty() {
+		return Err(AvroErr::decode(format!("{} bytes are left after the {} records of the block", buf.len(), block.count)));
+	}
+	Ok(())
+}
@@ -16,4 +16,51 @@ pub trait Decoder {
 	type Out;
     /// Allows decoding a type out of a given Reader
 	fn decode<R: Read>(reader: &mut R) -> Result<Self::Out, AvroErr>;
+    /// Allows decoding a type out of a given Reader, failing with `AvroErr::LimitExceeded` on data
+    /// exceeding the given limits. Types without lengths or counts decode the same as with `decode`.
+	fn decode_with_limits<R: Read>(reader: &mut R, _limits: &DecoderLimits) -> Result<Self::Out, AvroErr> {
+		Self::decode(reader)
+	}
+}
+
+/// Limits on the data decoded out of an avro data file, guarding against corrupt or malicious
+/// length prefixes and counts. Data exceeding them fails with `AvroErr::LimitExceeded`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecoderLimits {
+	/// Max length in bytes of bytes, string and fixed values
+	pub max_length: u64,
+	/// Max no of items of an array or entries of a map
+	pub max_items: u64,
+	/// Max nesting depth of records, arrays, maps and unions
+	pub max_depth: usize,
+	/// Max size in bytes of a data block, before and after decompression
+	pub max_block_size: u64,
+	/// Max no of records in a data block
+	pub max_block_records: u64,
+	/// Max no of items of arrays whose items are encoded in no bytes, such as nulls, within a
+	/// value or a data block. As such items cost nothing in the data, they are held to a much
+	/// smaller budget than `max_items`.
+	pub max_zero_width_items: u64
+}
+
+impl Default for DecoderLimits {
+	fn default() -> Self {
+		DecoderLimits {
+			max_length: 64 * 1024 * 1024,
+			max_items: 16 * 1024 * 1024,
+			max_depth: 64,
+			max_block_size: 256 * 1024 * 1024,
+			max_block_records: 1024 * 1024,
+			max_zero_width_items: 64 * 1024
+		}
+	}
+}
+
+/// Options for encoding values
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct EncoderOptions {
+	/// Max no of items in each block of an array or map. When set, blocks are written with a
+	/// negative item count followed by their size in bytes, so that readers can skip them.
+	/// When unset, the default, each array or map is written as a single block without its size.
+	pub block_items: Option<usize>
 }
@@ -0,0 +1,227 @@
+//! This module implements the sort order of the avro spec, comparing values either in their binary
+//! encoding, without decoding them, or as `Type` values. Record fields are compared in the order
+//! they are declared, as their `order` attribute says. Values holding maps can not be compared,
+//! unless the maps are in record fields which are ignored.
+
+use std::cmp::Ordering;
+
+use codec::{Decoder, DecoderLimits};
+use errors::AvroErr;
+use schema::{CompiledSchema, Order, SchemaNode};
+use types::{decode_block_header, decode_len, describe_value, schema_mismatch, Type};
+
+/// Compares two values encoded with the given schema
+pub fn compare(schema: &CompiledSchema, a: &[u8], b: &[u8]) -> Result<Ordering, AvroErr> {
+	let (mut a, mut b) = (a, b);
+	compare_encoded(schema, schema.root(), &DecoderLimits::default(), 0, &mut a, &mut b)
+}
+
+/// Compares two values encoded with the schema node at the given index
+pub fn compare_node(schema: &CompiledSchema, node: usize, a: &[u8], b: &[u8]) -> Result<Ordering, AvroErr> {
+	let (mut a, mut b) = (a, b);
+	compare_encoded(schema, node, &DecoderLimits::default(), 0, &mut a, &mut b)
+}
+
+/// Compares two values of the given schema, in the same order as their encodings compare
+pub fn compare_values(schema: &CompiledSchema, a: &Type, b: &Type) -> Result<Ordering, AvroErr> {
+	compare_value_nodes(schema, schema.root(), &DecoderLimits::default(), 0, a, b)
+}
+
+/// Compares floats numerically, with -0.0 ordered before 0.0 and NaN after all other values
+fn compare_floats(a: f64, b: f64) -> Ordering {
+	fn bits(f: f64) -> i64 {
+		if f.is_nan() { 0x7ff8_0000_0000_0000 } else { f.to_bits() as i64 }
+	}
+	match a.partial_cmp(&b) {
+		Some(Ordering::Equal) | None => bits(a).cmp(&bits(b)),
+		Some(order) => order
+	}
+}
+
+/// Takes the given no of bytes off the front of the buffer
+fn take<'a>(buf: &mut &'a [u8], len: u64) -> Result<&'a [u8], AvroErr> {
+	if (buf.len() as u64) < len {
+		return Err(AvroErr::decode("Unexpected end of data"));
+	}
+	let (taken, rest) = buf.split_at(len as usize);
+	*buf = rest;
+	Ok(taken)
+}
+
+/// Walks the blocks of an encoded array, item by item
+#[derive(Default)]
+struct Items {
+	/// No of items left in the current block
+	remaining: usize,
+	/// No of items walked over so far
+	read: usize
+}
+
+impl Items {
+	/// Moves to the next item, returning false once the array ends
+	fn next(&mut self, buf: &mut &[u8], limits: &DecoderLimits) -> Result<bool, AvroErr> {
+		while self.remaining == 0 {
+			let (count, _) = decode_block_header(buf, self.read, limits)?;
+			if count == 0 {
+				return Ok(false);
+			}
+			self.remaining = count;
+		}
+		self.remaining -= 1;
+		self.read += 1;
+		Ok(true)
+	}
+}
+
+/// Compares two values encoded with the schema node at the given index, nested `depth` values deep,
+/// advancing the buffers past them as far as they were compared
+fn compare_encoded(schema: &CompiledSchema, node: usize, limits: &DecoderLimits, depth: usize, a: &mut &[u8], b: &mut &[u8]) -> Result<Ordering, AvroErr> {
+	if depth > limits.max_depth {
+		return Err(AvroErr::limit("max_depth", depth as u64, limits.max_depth as u64));
+	}
+	let order = match *schema.node(node) {
+		SchemaNode::Null => Ordering::Equal,
+		SchemaNode::Boolean => bool::decode(a)?.cmp(&bool::decode(b)?),
+		// Enums are ordered by the position of their symbols in the schema
+		SchemaNode::Int | SchemaNode::Long | SchemaNode::Enum { .. } => i64::decode(a)?.cmp(&i64::decode(b)?),
+		SchemaNode::Float => compare_floats(f64::from(f32::decode(a)?), f64::from(f32::decode(b)?)),
+		SchemaNode::Double => compare_floats(f64::decode(a)?, f64::decode(b)?),
+		// Strings compare by their UTF-8 bytes, which orders them by code point
+		SchemaNode::Bytes | SchemaNode::String => {
+			let a_len = decode_len(a, limits.max_length)?;
+			let b_len = decode_len(b, limits.max_length)?;
+			take(a, a_len)?.cmp(take(b, b_len)?)
+		}
+		SchemaNode::Fixed { size, .. } => take(a, size as u64)?.cmp(take(b, size as u64)?),
+		SchemaNode::Record { ref fields, .. } => {
+			for field in fields {
+				let order = match field.order {
+					Order::Ignore => {
+						Type::skip_limited(schema, field.node, limits, depth + 1, a)
+							.and_then(|_| Type::skip_limited(schema, field.node, limits, depth + 1, b))
+							.map_err(|e| e.within(&format!(".{}", field.name)))?;
+						continue;
+					}
+					Order::Ascending => compare_encoded(schema, field.node, limits, depth + 1, a, b),
+					Order::Descending => compare_encoded(schema, field.node, limits, depth + 1, a, b).map(Ordering::reverse)
+				};
+				match order.map_err(|e| e.within(&format!(".{}", field.name)))? {
+					Ordering::Equal => {}
+					order => return Ok(order)
+				}
+			}
+			Ordering::Equal
+		}
+		SchemaNode::Array(items) => {
+			let (mut a_items, mut b_items) = (Items::default(), Items::default());
+			loop {
+				match (a_items.next(a, limits)?, b_items.next(b, limits)?) {
+					(false, false) => break Ordering::Equal,
+					(false, true) => break Ordering::Less,
+					(true, false) => break Ordering::Greater,
+					(true, true) => {
+						let order = compare_encoded(schema, items, limits, depth + 1, a, b)
+							.map_err(|e| e.within(&format!("[{}]", a_items.read - 1)))?;
+						if order != Ordering::Equal {
+							break order;
+						}
+					}
+				}
+			}
+		}
+		SchemaNode::Map(_) => return Err(AvroErr::schema("Maps can not be compared")),
+		SchemaNode::Union(ref branches) => {
+			let (a_idx, b_idx) = (i64::decode(a)?, i64::decode(b)?);
+			if a_idx != b_idx {
+				a_idx.cmp(&b_idx)
+			} else {
+				let branch = if a_idx < 0 { None } else { branches.get(a_idx as usize) };
+				let branch = branch.ok_or_else(|| AvroErr::decode(format!("Union branch {} does not exist", a_idx)))?;
+				compare_encoded(schema, *branch, limits, depth + 1, a, b).map_err(|e| e.within(&format!("[{}]", a_idx)))?
+			}
+		}
+	};
+	Ok(order)
+}
+
+/// Compares two values of the schema node at the given index, nested `depth` values deep
+fn compare_value_nodes(schema: &CompiledSchema, node: usize, limits: &DecoderLimits, depth: usize, a: &Type, b: &Type) -> Result<Ordering, AvroErr> {
+	if depth > limits.max_depth {
+		return Err(AvroErr::limit("max_depth", depth as u64, limits.max_depth as u64));
+	}
+	let schema_node = schema.node(node);
+	let order = match (schema_node, a, b) {
+		(SchemaNode::Null, Type::Null, Type::Null) => Ordering::Equal,
+		(SchemaNode::Boolean, Type::Bool(a), Type::Bool(b)) => a.cmp(b),
+		(SchemaNode::Int, _, _) | (SchemaNode::Long, _, _) => match (long_value(a), long_value(b)) {
+			(Some(a), Some(b)) => a.cmp(&b),
+			_ => return Err(mismatch(schema_node, a, b))
+		},
+		(SchemaNode::Float, Type::Float(a), Type::Float(b)) => compare_floats(f64::from(*a), f64::from(*b)),
+		(SchemaNode::Double, Type::Double(a), Type::Double(b)) => compare_floats(*a, *b),
+		(SchemaNode::Bytes, Type::Bytes(a), Type::Bytes(b)) => a.cmp(b),
+		(SchemaNode::String, Type::Str(a), Type::Str(b)) => a.as_bytes().cmp(b.as_bytes()),
+		(SchemaNode::Fixed { .. }, Type::Fixed(a), Type::Fixed(b)) => a.cmp(b),
+		(SchemaNode::Enum { symbols, .. }, Type::Enum(a_val), Type::Enum(b_val)) => {
+			let position = |value: &Option<&str>| value.and_then(|val| symbols.iter().position(|s| s == val));
+			match (position(&a_val.get_value()), position(&b_val.get_value())) {
+				(Some(a), Some(b)) => a.cmp(&b),
+				_ => return Err(mismatch(schema_node, a, b))
+			}
+		}
+		(SchemaNode::Record { fields, .. }, Type::Record(a_rec), Type::Record(b_rec))
+			if fields.len() == a_rec.fields.len() && fields.len() == b_rec.fields.len() => {
+			for (field, (a_field, b_field)) in fields.iter().zip(a_rec.fields.iter().zip(&b_rec.fields)) {
+				if field.name != a_field.name || field.name != b_field.name {
+					let found = format!("fields {} and {}", a_field.name, b_field.name);
+					return Err(AvroErr::validation(format!("field {}", field.name), found));
+				}
+				let order = match field.order {
+					Order::Ignore => continue,
+					Order::Ascending => compare_value_nodes(schema, field.node, limits, depth + 1, &a_field.ty, &b_field.ty),
+					Order::Descending => compare_value_nodes(schema, field.node, limits, depth + 1, &a_field.ty, &b_field.ty).map(Ordering::reverse)
+				};
+				match order.map_err(|e| e.within(&format!(".{}", field.name)))? {
+					Ordering::Equal => {}
+					order => return Ok(order)
+				}
+			}
+			Ordering::Equal
+		}
+		(SchemaNode::Array(items), Type::Array(a_items), Type::Array(b_items)) => {
+			for (i, (a_item, b_item)) in a_items.iter().zip(b_items).enumerate() {
+				let order = compare_value_nodes(schema, *items, limits, depth + 1, a_item, b_item)
+					.map_err(|e| e.within(&format!("[{}]", i)))?;
+				if order != Ordering::Equal {
+					return Ok(order);
+				}
+			}
+			a_items.len().cmp(&b_items.len())
+		}
+		(SchemaNode::Map(_), _, _) => return Err(AvroErr::schema("Maps can not be compared")),
+		(SchemaNode::Union(branches), Type::Union(a_idx, a_val), Type::Union(b_idx, b_val)) if *a_idx < branches.len() && *b_idx < branches.len() => {
+			if a_idx != b_idx {
+				a_idx.cmp(b_idx)
+			} else {
+				compare_value_nodes(schema, branches[*a_idx], limits, depth + 1, a_val, b_val)
+					.map_err(|e| e.within(&format!("[{}]", a_idx)))?
+			}
+		}
+		_ => return Err(mismatch(schema_node, a, b))
+	};
+	Ok(order)
+}
+
+/// The value of an int or long, which compare alike
+fn long_value(value: &Type) -> Option<i64> {
+	match *value {
+		Type::Int(i) => Some(i64::from(i)),
+		Type::Long(l) => Some(l),
+		_ => None
+	}
+}
+
+/// Error for values which do not conform to the schema node they are compared with
+fn mismatch(node: &SchemaNode, a: &Type, b: &Type) -> AvroErr {
+	schema_mismatch(format!("{} and {}", describe_value(a), describe_value(b)), node)
+}
@@ -30,20 +30,35 @@ impl Enum {
 		}
 	}
 
+	/// Creates a new enum schema from a list of owned symbols
+	pub fn from_symbols(name: &str, symbols: Vec<String>) -> Self {
+		Enum {
+			name: name.to_string(),
+			symbols,
+			current_val: None
+		}
+	}
+
 	/// sets the active enum variant
 	pub fn set_value(&mut self, val: &str) {
 		self.current_val = Some(val.to_string());
 	}
+
+	/// Retrieves the active enum variant
+	pub fn get_value(&self) -> Option<&str> {
+		self.current_val.as_deref()
+	}
 }
 
 impl Encoder for Enum {
 	fn encode<W: Write>(&self, writer: &mut W) -> Result<usize, AvroErr> {
 		if let Some(ref current_val) = self.current_val {
-			let idx = self.symbols.iter().position(|it| it == current_val).unwrap();
+			let idx = self.symbols.iter().position(|it| it == current_val)
+				.ok_or_else(|| AvroErr::encode(format!("{} is not a symbol of enum {}", current_val, self.name)))?;
 			let int: Type = (idx as i64).into();
 			int.encode(writer)
 		} else {
-			Err(AvroErr::EncodeErr(format!("Expect enum value, found: {:?}", self.current_val)))
+			Err(AvroErr::encode(format!("Enum {} has no value set", self.name)))
 		}
 	}
 }
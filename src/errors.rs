@@ -1,67 +1,234 @@
 
-use std::io::Error as StdError;
+use std::fmt;
+use std::io::{self, ErrorKind};
 
-// TODO expand this module to have more descriptive and detailed errors
+use serde_json;
 
-use failure::Error;
+/// Path to a part of a schema or of a value, such as `$.inner_rec.id`, `$.items[2]` or `$.foo["key"]`.
+/// `$` stands for the top level schema or value.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SchemaPath(String);
+
+impl SchemaPath {
+    /// Prepends a step, such as `.field` or `[2]`, to the path
+    pub(crate) fn prepend(&mut self, step: &str) {
+        self.0.insert_str(0, step);
+    }
+
+    /// Retrieves the steps of the path following `$`
+    pub fn steps(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for SchemaPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "${}", self.0)
+    }
+}
+
+/// The place in a data file an error was encountered at, as far as it is known
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Position {
+    /// No of the block in the data file, counting from zero
+    pub block: Option<u64>,
+    /// Byte offset of the block in the data file
+    pub offset: Option<u64>
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(block) = self.block {
+            write!(f, " in block {}", block)?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " at offset {}", offset)?;
+        }
+        Ok(())
+    }
+}
 
 /// The error enum wraps all kinds of errors during serialization/deserialization
 #[derive(Debug, Fail)]
 pub enum AvroErr {
-    /// Avro file read errors
-    #[fail(display = "Failed to write avro data")]
-    AvroWriteErr,
-    /// A unexpected schema was encountered
-    #[fail(display = "An unexpected schema was encountered")]
-    UnexpectedSchema,
-    /// Avro file read errors
-    #[fail(display = "Failed to read avro data")]
-    AvroReadErr,
-    /// Error encountered while encoding avro data file
-    #[fail(display = "Error encountered while encoding avro data file: {}", _0)]
-    EncodeErr(String),
-    /// Error encountered while decoding avro data file
-    #[fail(display = "Error encountered while decoding avro data file: {}", _0)]
-    DecodeErr(String),
-    /// A named complex type does not confirm to a valid full name as defined in spec
-    #[fail(display = "A named complex type does not confirm to a valid full name as defined in spec")]
-    InvalidFullname,
-    /// Variant which corresponds to Avro file read/write errors.
-    #[fail(display = "Avro I/O error")]
-    AvroIOErr,
-    /// An unexpected data was parsed.
-    #[fail(display = "An unexpected data was parsed")]
-    UnexpectedData,
-    /// An unexpected codec was detected.
-    #[fail(display = "An unexpected codec was detected")]
-    UnexpectedCodec
+    /// The schema declaration is not a valid avro schema
+    #[fail(display = "Invalid schema at {}: {}", path, reason)]
+    SchemaParse {
+        /// Path to the invalid part of the schema declaration
+        path: SchemaPath,
+        /// What is wrong with it
+        reason: String
+    },
+    /// The schema declaration is not valid json
+    #[fail(display = "Schema declaration is not valid json: {}", _0)]
+    SchemaJson(#[cause] serde_json::Error),
+    /// A value does not conform to the schema it is written with
+    #[fail(display = "Expected {} at {}, found {}", expected, path, found)]
+    Validation {
+        /// Path to the offending part of the value
+        path: SchemaPath,
+        /// What the schema declares
+        expected: String,
+        /// What the value holds
+        found: String
+    },
+    /// A value could not be encoded
+    #[fail(display = "Failed to encode {}: {}", path, reason)]
+    Encode {
+        /// Path to the part of the value which could not be encoded
+        path: SchemaPath,
+        /// Why it could not be encoded
+        reason: String
+    },
+    /// Data could not be decoded, as it is corrupt or not avro data
+    #[fail(display = "Failed to decode {}{}: {}", path, position, reason)]
+    Decode {
+        /// Path to the part of the value being decoded, within the schema it is decoded with
+        path: SchemaPath,
+        /// The block being decoded
+        position: Position,
+        /// What is wrong with the data
+        reason: String
+    },
+    /// A block could not be decompressed, or the codec is not supported
+    #[fail(display = "Codec {} failed{}: {}", codec, position, reason)]
+    Codec {
+        /// Name of the codec
+        codec: String,
+        /// The block being decompressed
+        position: Position,
+        /// Why the codec failed
+        reason: String
+    },
+    /// Decoded data exceeds one of the `DecoderLimits`
+    #[fail(display = "Failed to decode {}{}: {} of {} exceeds the limit of {}", path, position, limit, value, max)]
+    LimitExceeded {
+        /// Path to the part of the value being decoded, within the schema it is decoded with
+        path: SchemaPath,
+        /// The block being decoded
+        position: Position,
+        /// Name of the exceeded limit, such as `max_length`
+        limit: &'static str,
+        /// The value found in the data
+        value: u64,
+        /// The limit it exceeds
+        max: u64
+    },
+    /// The data file ends in the middle of the block starting at the given offset
+    #[fail(display = "Data file is truncated in the block at offset {}", _0)]
+    TruncatedBlock(u64),
+    /// The sync marker following the block at the given offset differs from the one in the header
+    #[fail(display = "Sync marker of the block at offset {} does not match the header", _0)]
+    SyncMarkerMismatch(u64),
+    /// `AvroWriter::flush` was called on a writer which was not opened with `WriterBuilder::append_to`
+    #[fail(display = "Writer was not opened for appending to a data file")]
+    NotAppending,
+    /// Reading or writing the underlying data failed
+    #[fail(display = "I/O error: {}", _0)]
+    Io(#[cause] io::Error)
 }
 
-/// The error enum wraps all kinds of errors during parsing of schema_declaration
-#[derive(Debug, Fail)]
-pub enum SchemaParseErr {
-    /// Not a valid schema declaration
-    #[fail(display = "Not a valid schema declaration")]
-    InvalidSchema,
-    /// Schema file not found
-    #[fail(display = "Could not find schema file")]
-    NotFound
-}
+impl AvroErr {
+    /// Creates a `SchemaParse` error about the top level schema
+    pub(crate) fn schema<S: Into<String>>(reason: S) -> Self {
+        AvroErr::SchemaParse { path: SchemaPath::default(), reason: reason.into() }
+    }
+
+    /// Creates a `Validation` error about the top level value
+    pub(crate) fn validation<E: Into<String>, F: Into<String>>(expected: E, found: F) -> Self {
+        AvroErr::Validation { path: SchemaPath::default(), expected: expected.into(), found: found.into() }
+    }
+
+    /// Creates an `Encode` error about the top level value
+    pub(crate) fn encode<S: Into<String>>(reason: S) -> Self {
+        AvroErr::Encode { path: SchemaPath::default(), reason: reason.into() }
+    }
+
+    /// Creates a `Decode` error about the top level value
+    pub(crate) fn decode<S: Into<String>>(reason: S) -> Self {
+        AvroErr::Decode { path: SchemaPath::default(), position: Position::default(), reason: reason.into() }
+    }
+
+    /// Creates a `Codec` error
+    pub(crate) fn codec<C: fmt::Display, S: Into<String>>(codec: C, reason: S) -> Self {
+        AvroErr::Codec { codec: codec.to_string(), position: Position::default(), reason: reason.into() }
+    }
+
+    /// Creates a `LimitExceeded` error about the top level value
+    pub(crate) fn limit(limit: &'static str, value: u64, max: u64) -> Self {
+        AvroErr::LimitExceeded { path: SchemaPath::default(), position: Position::default(), limit, value, max }
+    }
+
+    /// Converts an error reading encoded data, where running out of data means it is corrupt
+    pub(crate) fn read(err: io::Error) -> Self {
+        if err.kind() == ErrorKind::UnexpectedEof {
+            AvroErr::decode("Unexpected end of data")
+        } else {
+            AvroErr::Io(err)
+        }
+    }
+
+    /// Prepends a step to the path of the error, as it is passed up to the enclosing schema or value
+    pub(crate) fn within(mut self, step: &str) -> Self {
+        match self {
+            AvroErr::SchemaParse { ref mut path, .. } |
+            AvroErr::Validation { ref mut path, .. } |
+            AvroErr::Encode { ref mut path, .. } |
+            AvroErr::Decode { ref mut path, .. } |
+            AvroErr::LimitExceeded { ref mut path, .. } => path.prepend(step),
+            _ => ()
+        }
+        self
+    }
+
+    /// Fills in the block a decoding error was encountered in, unless it is known already
+    pub(crate) fn in_block(mut self, block: Option<u64>, offset: u64) -> Self {
+        match self {
+            AvroErr::Decode { ref mut position, .. } |
+            AvroErr::LimitExceeded { ref mut position, .. } |
+            AvroErr::Codec { ref mut position, .. } => {
+                position.block = position.block.or(block);
+                position.offset = position.offset.or(Some(offset));
+            }
+            _ => ()
+        }
+        self
+    }
+
+    /// Retrieves the path to the part of the schema or value the error is about, if any
+    pub fn path(&self) -> Option<&SchemaPath> {
+        match *self {
+            AvroErr::SchemaParse { ref path, .. } |
+            AvroErr::Validation { ref path, .. } |
+            AvroErr::Encode { ref path, .. } |
+            AvroErr::Decode { ref path, .. } |
+            AvroErr::LimitExceeded { ref path, .. } => Some(path),
+            _ => None
+        }
+    }
 
-impl From<StdError> for AvroErr {
-    fn from(_val: StdError) -> Self {
-        AvroErr::AvroIOErr
+    /// Retrieves the position in the data file the error was encountered at, if known
+    pub fn position(&self) -> Option<Position> {
+        match *self {
+            AvroErr::Decode { position, .. } |
+            AvroErr::LimitExceeded { position, .. } |
+            AvroErr::Codec { position, .. } => Some(position),
+            AvroErr::TruncatedBlock(offset) | AvroErr::SyncMarkerMismatch(offset) => {
+                Some(Position { block: None, offset: Some(offset) })
+            }
+            _ => None
+        }
     }
 }
 
-impl From<Error> for AvroErr {
-    fn from(_val: Error) -> Self {
-        AvroErr::AvroIOErr
+impl From<io::Error> for AvroErr {
+    fn from(err: io::Error) -> Self {
+        AvroErr::Io(err)
     }
 }
 
-impl From<String> for AvroErr {
-    fn from(_val: String) -> Self {
-        AvroErr::UnexpectedSchema
+impl From<serde_json::Error> for AvroErr {
+    fn from(err: serde_json::Error) -> Self {
+        AvroErr::SchemaJson(err)
     }
 }
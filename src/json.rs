@@ -0,0 +1,274 @@
+//! This module converts values to and from the JSON encoding of the avro spec, in the same form as
+//! the `tojson` and `fromjson` commands of avro-tools. Bytes and fixed values are strings of the
+//! code points of their bytes, and union values other than null are objects keyed by the name of
+//! their branch. Floats are formatted the way Java formats them.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use serde_json::Value;
+
+use complex::{Enum, Field, Record};
+use errors::AvroErr;
+use schema::{CompiledSchema, SchemaNode};
+use types::{describe_value, schema_mismatch, Type};
+
+/// Formats a value of the given schema as a single line of JSON
+pub fn to_json(schema: &CompiledSchema, value: &Type) -> Result<String, AvroErr> {
+	let mut out = String::new();
+	JsonFormatter { schema, out: &mut out, pretty: false }.format(schema.root(), value, 0)?;
+	Ok(out)
+}
+
+/// Formats a value of the given schema as indented JSON, spread over several lines
+pub fn to_json_pretty(schema: &CompiledSchema, value: &Type) -> Result<String, AvroErr> {
+	let mut out = String::new();
+	JsonFormatter { schema, out: &mut out, pretty: true }.format(schema.root(), value, 0)?;
+	Ok(out)
+}
+
+/// Builds a value of the given schema from its JSON encoding
+pub fn from_json(schema: &CompiledSchema, json: &Value) -> Result<Type, AvroErr> {
+	value_of(schema, schema.root(), json)
+}
+
+/// Formats a float the way Java's `Double.toString` does: in decimal notation with at least one
+/// fractional digit between 10^-3 and 10^7, and in scientific notation outside of that range
+fn java_float<F: fmt::Display + fmt::LowerExp>(value: F, magnitude: f64) -> String {
+	if magnitude.is_nan() {
+		return "\"NaN\"".to_string();
+	}
+	if magnitude.is_infinite() {
+		return if magnitude > 0.0 { "\"Infinity\"" } else { "\"-Infinity\"" }.to_string();
+	}
+	let magnitude = magnitude.abs();
+	if magnitude == 0.0 || (1e-3..1e7).contains(&magnitude) {
+		let decimal = value.to_string();
+		return if decimal.contains('.') { decimal } else { decimal + ".0" };
+	}
+	let scientific = format!("{:e}", value);
+	let (mantissa, exponent) = scientific.split_at(scientific.find('e').unwrap_or(scientific.len()));
+	let fraction = if mantissa.contains('.') { "" } else { ".0" };
+	format!("{}{}E{}", mantissa, fraction, exponent.trim_start_matches('e'))
+}
+
+/// Maps bytes to the string of the code points of the same values
+fn byte_string(bytes: &[u8]) -> String {
+	bytes.iter().map(|b| char::from(*b)).collect()
+}
+
+/// Name of the key holding the value of a union branch
+fn branch_name(schema: &CompiledSchema, node: usize) -> String {
+	match schema.fullname(node) {
+		Some(name) => name.to_string(),
+		None => match *schema.node(node) {
+			SchemaNode::Array(_) => "array".to_string(),
+			SchemaNode::Map(_) => "map".to_string(),
+			ref other => other.to_string()
+		}
+	}
+}
+
+/// Writes JSON text, laid out as Jackson's default pretty printer does when pretty printing:
+/// objects spread over indented lines and arrays on a single line
+struct JsonFormatter<'a> {
+	schema: &'a CompiledSchema,
+	out: &'a mut String,
+	pretty: bool
+}
+
+impl<'a> JsonFormatter<'a> {
+	fn string(&mut self, s: &str) {
+		self.out.push_str(&Value::from(s).to_string());
+	}
+
+	/// Writes the entries of an object, nested `level` objects deep. Errors are given the path
+	/// step of the entry they occur in, which `step` makes out of its key.
+	fn object<'e, I, F>(&mut self, entries: I, level: usize, step: F) -> Result<(), AvroErr>
+		where I: IntoIterator<Item = (&'e str, usize, &'e Type)>, F: Fn(&str) -> String {
+		self.out.push('{');
+		let mut empty = true;
+		for (key, node, value) in entries {
+			if !empty {
+				self.out.push(',');
+			}
+			if self.pretty {
+				self.out.push('\n');
+				self.out.push_str(&"  ".repeat(level + 1));
+			}
+			self.string(key);
+			self.out.push_str(if self.pretty { " : " } else { ":" });
+			self.format(node, value, level + 1).map_err(|e| e.within(&step(key)))?;
+			empty = false;
+		}
+		if self.pretty {
+			if empty {
+				self.out.push(' ');
+			} else {
+				self.out.push('\n');
+				self.out.push_str(&"  ".repeat(level));
+			}
+		}
+		self.out.push('}');
+		Ok(())
+	}
+
+	fn format(&mut self, node: usize, value: &Type, level: usize) -> Result<(), AvroErr> {
+		let schema = self.schema;
+		let schema_node = schema.node(node);
+		match (schema_node, value) {
+			(SchemaNode::Null, Type::Null) => self.out.push_str("null"),
+			(SchemaNode::Boolean, Type::Bool(b)) => self.out.push_str(if *b { "true" } else { "false" }),
+			(SchemaNode::Int, Type::Int(i)) => self.out.push_str(&i.to_string()),
+			(SchemaNode::Long, Type::Long(l)) => self.out.push_str(&l.to_string()),
+			(SchemaNode::Long, Type::Int(i)) => self.out.push_str(&i.to_string()),
+			(SchemaNode::Float, Type::Float(f)) => self.out.push_str(&java_float(*f, f64::from(*f))),
+			(SchemaNode::Double, Type::Double(d)) => self.out.push_str(&java_float(*d, *d)),
+			(SchemaNode::Bytes, Type::Bytes(bytes)) => self.string(&byte_string(bytes)),
+			(SchemaNode::Fixed { size, .. }, Type::Fixed(bytes)) if bytes.len() == *size => self.string(&byte_string(bytes)),
+			(SchemaNode::String, Type::Str(s)) => self.string(s),
+			(SchemaNode::Enum { symbols, .. }, Type::Enum(val)) => match val.get_value() {
+				Some(symbol) if symbols.iter().any(|s| s == symbol) => self.string(symbol),
+				_ => return Err(schema_mismatch(describe_value(value), schema_node))
+			},
+			(SchemaNode::Record { fields, .. }, Type::Record(rec)) if fields.len() == rec.fields.len() => {
+				for (field, value) in fields.iter().zip(&rec.fields) {
+					if field.name != value.name {
+						return Err(AvroErr::validation(format!("field {}", field.name), format!("field {}", value.name)));
+					}
+				}
+				self.object(fields.iter().zip(&rec.fields).map(|(field, value)| (field.name.as_str(), field.node, &value.ty)), level, |key| format!(".{}", key))?;
+			}
+			(SchemaNode::Array(items), Type::Array(values)) => {
+				self.out.push('[');
+				for (i, value) in values.iter().enumerate() {
+					if i > 0 {
+						self.out.push(',');
+					}
+					if self.pretty {
+						self.out.push(' ');
+					}
+					self.format(*items, value, level).map_err(|e| e.within(&format!("[{}]", i)))?;
+				}
+				if self.pretty {
+					self.out.push(' ');
+				}
+				self.out.push(']');
+			}
+			(SchemaNode::Map(values), Type::Map(map)) => {
+				// Keys are sorted, so that equal maps give the same JSON
+				let mut entries: Vec<_> = map.iter().map(|(key, value)| (key.as_str(), *values, value)).collect();
+				entries.sort_by_key(|entry| entry.0);
+				self.object(entries, level, |key| format!("[{:?}]", key))?;
+			}
+			(SchemaNode::Union(branches), Type::Union(idx, value)) if *idx < branches.len() => {
+				let branch = branches[*idx];
+				if let SchemaNode::Null = *schema.node(branch) {
+					return self.format(branch, value, level);
+				}
+				let name = branch_name(schema, branch);
+				self.object(vec![(name.as_str(), branch, &**value)], level, |_| format!("[{}]", idx))?;
+			}
+			_ => return Err(schema_mismatch(describe_value(value), schema_node))
+		}
+		Ok(())
+	}
+}
+
+/// Describes a JSON value in schema mismatch errors
+fn describe_json(json: &Value) -> String {
+	match *json {
+		Value::Null => "json null".to_string(),
+		Value::Bool(b) => format!("json {}", b),
+		Value::Number(ref n) => format!("json number {}", n),
+		Value::String(_) => "json string".to_string(),
+		Value::Array(ref items) => format!("json array of {} items", items.len()),
+		Value::Object(ref obj) => format!("json object with {} entries", obj.len())
+	}
+}
+
+/// Maps a string of code points below 256 back to the bytes of the same values
+fn string_bytes(schema_node: &SchemaNode, json: &Value, s: &str) -> Result<Vec<u8>, AvroErr> {
+	s.chars().map(|c| if (c as u32) < 256 { Ok(c as u8) } else { Err(schema_mismatch(describe_json(json), schema_node)) }).collect()
+}
+
+fn value_of(schema: &CompiledSchema, node: usize, json: &Value) -> Result<Type, AvroErr> {
+	let schema_node = schema.node(node);
+	let mismatch = || schema_mismatch(describe_json(json), schema_node);
+	let value = match (schema_node, json) {
+		(SchemaNode::Null, Value::Null) => Type::Null,
+		(SchemaNode::Boolean, Value::Bool(b)) => Type::Bool(*b),
+		(SchemaNode::Int, Value::Number(n)) => match n.as_i64() {
+			Some(i) if i64::from(i as i32) == i => Type::Int(i as i32),
+			_ => return Err(mismatch())
+		},
+		(SchemaNode::Long, Value::Number(n)) => Type::Long(n.as_i64().ok_or_else(mismatch)?),
+		(SchemaNode::Float, _) => Type::Float(float_of(json).ok_or_else(mismatch)? as f32),
+		(SchemaNode::Double, _) => Type::Double(float_of(json).ok_or_else(mismatch)?),
+		(SchemaNode::Bytes, Value::String(s)) => Type::Bytes(string_bytes(schema_node, json, s)?),
+		(SchemaNode::Fixed { size, .. }, Value::String(s)) => {
+			let bytes = string_bytes(schema_node, json, s)?;
+			if bytes.len() != *size {
+				return Err(schema_mismatch(format!("{} bytes", bytes.len()), schema_node));
+			}
+			Type::Fixed(bytes)
+		}
+		(SchemaNode::String, Value::String(s)) => Type::Str(s.clone()),
+		(SchemaNode::Enum { name, symbols }, Value::String(s)) if symbols.contains(s) => {
+			let mut enum_val = Enum::from_symbols(name, symbols.clone());
+			enum_val.set_value(s);
+			Type::Enum(enum_val)
+		}
+		(SchemaNode::Record { name, fields }, Value::Object(obj)) => {
+			let mut values = Vec::with_capacity(fields.len());
+			for field in fields {
+				let json = obj.get(&field.name)
+					.ok_or_else(|| AvroErr::validation(format!("field {}", field.name), "an object without it"))?;
+				let value = value_of(schema, field.node, json).map_err(|e| e.within(&format!(".{}", field.name)))?;
+				values.push(Field::new(&field.name, value));
+			}
+			Type::Record(Record::new(name, None, values))
+		}
+		(SchemaNode::Array(items), Value::Array(jsons)) => {
+			let mut values = Vec::with_capacity(jsons.len());
+			for (i, json) in jsons.iter().enumerate() {
+				values.push(value_of(schema, *items, json).map_err(|e| e.within(&format!("[{}]", i)))?);
+			}
+			Type::Array(values)
+		}
+		(SchemaNode::Map(values), Value::Object(obj)) => {
+			let mut map = HashMap::with_capacity(obj.len());
+			for (key, json) in obj {
+				map.insert(key.clone(), value_of(schema, *values, json).map_err(|e| e.within(&format!("[{:?}]", key)))?);
+			}
+			Type::Map(map)
+		}
+		(SchemaNode::Union(branches), Value::Null) => {
+			let idx = branches.iter().position(|b| *schema.node(*b) == SchemaNode::Null).ok_or_else(mismatch)?;
+			Type::Union(idx, Box::new(Type::Null))
+		}
+		(SchemaNode::Union(branches), Value::Object(obj)) if obj.len() == 1 => {
+			let (key, json) = obj.iter().next().ok_or_else(mismatch)?;
+			let idx = branches.iter().position(|b| branch_name(schema, *b) == *key)
+				.ok_or_else(|| schema_mismatch(format!("branch {}", key), schema_node))?;
+			let value = value_of(schema, branches[idx], json).map_err(|e| e.within(&format!("[{}]", idx)))?;
+			Type::Union(idx, Box::new(value))
+		}
+		_ => return Err(mismatch())
+	};
+	Ok(value)
+}
+
+/// Reads a float from a JSON number, or from the strings Java gives for NaN and the infinities
+fn float_of(json: &Value) -> Option<f64> {
+	match *json {
+		Value::Number(ref n) => n.as_f64(),
+		Value::String(ref s) => match s.as_str() {
+			"NaN" => Some(f64::NAN),
+			"Infinity" => Some(f64::INFINITY),
+			"-Infinity" => Some(f64::NEG_INFINITY),
+			_ => None
+		},
+		_ => None
+	}
+}
@@ -11,19 +11,28 @@ extern crate rand;
 extern crate snap;
 #[macro_use]
 extern crate log;
-extern crate crc;
+extern crate crc32fast;
 extern crate byteorder;
 #[macro_use]
 extern crate lazy_static;
 #[macro_use]
 extern crate failure;
 extern crate flate2;
+#[cfg(feature = "memmap")]
+extern crate memmap;
+#[cfg(feature = "async")]
+extern crate tokio;
+#[cfg(feature = "async")]
+extern crate futures_core;
+#[cfg(feature = "async")]
+extern crate futures_sink;
 
 pub mod schema;
 #[macro_use]
 mod types;
-pub use types::Type;
+pub use types::{Type, TypeRef};
 pub mod codec;
+pub use codec::{DecoderLimits, EncoderOptions};
 pub mod complex;
 pub mod writer;
 pub use schema::AvroSchema;
@@ -31,4 +40,16 @@ pub use schema::AvroSchema;
 pub mod errors;
 /// Allows reading from avro data file
 pub mod reader;
-pub use writer::{AvroWriter, Codec};
\ No newline at end of file
+pub use writer::{AvroWriter, Codec};
+pub use reader::{AvroReader, ParallelReader};
+pub mod slice_reader;
+pub mod projection;
+pub mod compare;
+pub mod sort;
+pub mod json;
+pub mod random;
+pub mod check;
+mod pool;
+#[cfg(feature = "async")]
+pub mod async_io;
+pub use slice_reader::SliceReader;
\ No newline at end of file
@@ -0,0 +1,121 @@
+//! This module declares an `OrderedPool` which runs jobs on a fixed set of worker threads
+//! and hands back their results in the order the jobs were submitted. It is used to compress
+//! and decompress data blocks in parallel while keeping the order of blocks in a data file.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::task::Waker;
+use std::thread::{self, JoinHandle};
+
+pub(crate) struct OrderedPool<J, O> {
+	/// Sends jobs to the workers, dropped to make the workers exit
+	jobs: Option<Sender<(u64, J)>>,
+	/// Results of the jobs, tagged with their sequence number
+	results: Receiver<(u64, O)>,
+	workers: Vec<JoinHandle<()>>,
+	/// No of jobs submitted so far
+	submitted: u64,
+	/// Sequence number of the next result to hand back
+	next: u64,
+	/// Results which arrived before the ones of earlier jobs
+	finished: BTreeMap<u64, O>,
+	/// Task woken by the next worker finishing a job, only registered by the async writer
+	#[cfg_attr(not(feature = "async"), allow(dead_code))]
+	waker: Arc<Mutex<Option<Waker>>>
+}
+
+impl<J: Send + 'static, O: Send + 'static> OrderedPool<J, O> {
+	/// Starts `workers` threads, each running `work` on the jobs it receives
+	pub fn new<F>(workers: usize, work: F) -> Self where F: Fn(J) -> O + Send + Sync + 'static {
+		let (job_tx, job_rx) = channel::<(u64, J)>();
+		let (result_tx, result_rx) = channel();
+		let job_rx = Arc::new(Mutex::new(job_rx));
+		let work = Arc::new(work);
+		let waker = Arc::new(Mutex::new(None::<Waker>));
+		let workers = (0..workers.max(1)).map(|_| {
+			let job_rx = job_rx.clone();
+			let result_tx = result_tx.clone();
+			let work = work.clone();
+			let waker = waker.clone();
+			thread::spawn(move || loop {
+				let job = match job_rx.lock() {
+					Ok(job_rx) => job_rx.recv(),
+					Err(_) => return
+				};
+				match job {
+					Ok((seq, job)) => if result_tx.send((seq, work(job))).is_err() {
+						return;
+					},
+					Err(_) => return
+				}
+				let waiting = waker.lock().ok().and_then(|mut waker| waker.take());
+				if let Some(waiting) = waiting {
+					waiting.wake();
+				}
+			})
+		}).collect();
+		OrderedPool {
+			jobs: Some(job_tx),
+			results: result_rx,
+			workers,
+			submitted: 0,
+			next: 0,
+			finished: BTreeMap::new(),
+			waker
+		}
+	}
+
+	/// Wakes the given task once the next job is done. Results which arrived before this
+	/// do not wake it, so they should be checked for again after registering.
+	#[cfg(feature = "async")]
+	pub fn wake_on_result(&self, waker: &Waker) {
+		if let Ok(mut registered) = self.waker.lock() {
+			*registered = Some(waker.clone());
+		}
+	}
+
+	/// Queues a job to be run by the next idle worker
+	pub fn submit(&mut self, job: J) {
+		if let Some(ref jobs) = self.jobs {
+			// The workers only exit once the sender is dropped
+			let _ = jobs.send((self.submitted, job));
+			self.submitted += 1;
+		}
+	}
+
+	/// No of jobs whose results were not handed back yet
+	pub fn pending(&self) -> u64 {
+		self.submitted - self.next
+	}
+
+	/// Hands back the result of the oldest pending job. If it is not done yet, this waits
+	/// for it when `wait` is set, and returns `None` otherwise.
+	pub fn next_result(&mut self, wait: bool) -> Option<O> {
+		loop {
+			if let Some(result) = self.finished.remove(&self.next) {
+				self.next += 1;
+				return Some(result);
+			}
+			if self.pending() == 0 {
+				return None;
+			}
+			let received = if wait { self.results.recv().ok() } else { self.results.try_recv().ok() };
+			match received {
+				Some((seq, result)) => {
+					self.finished.insert(seq, result);
+				}
+				None => return None
+			}
+		}
+	}
+}
+
+impl<J, O> Drop for OrderedPool<J, O> {
+	fn drop(&mut self) {
+		self.jobs = None;
+		for worker in self.workers.drain(..) {
+			let _ = worker.join();
+		}
+	}
+}
@@ -0,0 +1,330 @@
+//! This module declares a `Projection`, which decodes only the parts of records that a reader's
+//! schema asks for. Fields left out of the reader's schema are skipped over in the encoded data
+//! instead of being decoded and dropped.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+
+use codec::{Decoder, DecoderLimits};
+use complex::{Field, Record};
+use errors::AvroErr;
+use schema::{CompiledSchema, SchemaNode};
+use types::{count_zero_width_items, decode_block_count, decode_string, Type, RESERVE_LIMIT};
+
+/// How values of a node of the writer's schema are decoded
+#[derive(Debug, Clone, PartialEq)]
+enum ProjectedNode {
+	/// The value is decoded whole, with the node of the writer's schema at the given index
+	Whole(usize),
+	/// A record of which only some fields are decoded
+	Record {
+		/// Name of the record
+		name: String,
+		/// Fields of the record in the writer's schema
+		fields: Vec<ProjectedField>
+	},
+	/// An array, with the projection of its items
+	Array(usize),
+	/// A map, with the projection of its values
+	Map(usize),
+	/// A union, with the projections of its branches
+	Union(Vec<usize>)
+}
+
+/// A field of a projected record
+#[derive(Debug, Clone, PartialEq)]
+struct ProjectedField {
+	/// Name of the field
+	name: String,
+	/// Index of the node of the field's type in the writer's schema
+	node: usize,
+	/// Index of the projection of the field, or `None` if the field is skipped
+	projection: Option<usize>,
+	/// Position of the field in the decoded record, which follows the order of the reader's
+	/// schema. Skipped fields have no position of their own and are left at zero.
+	position: usize
+}
+
+/// Decodes data written with a writer's schema into the values of a reader's schema, which
+/// leaves out some of the fields of the writer's records. Decoded records hold the fields
+/// of the reader's schema, in the order they are declared in the reader's schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Projection {
+	schema: CompiledSchema,
+	nodes: Vec<ProjectedNode>,
+	root: usize
+}
+
+impl Projection {
+	/// Resolves the reader's schema against the writer's. Records of the reader's schema may leave
+	/// out fields of the writer's records, while all other types must be the same in both schemas.
+	pub fn new(writer: &CompiledSchema, reader: &CompiledSchema) -> Result<Self, AvroErr> {
+		let mut resolver = Resolver { writer, reader, nodes: vec![], resolved: HashMap::new() };
+		let root = resolver.resolve(writer.root(), reader.root())?;
+		Ok(Projection { schema: writer.clone(), nodes: resolver.nodes, root })
+	}
+
+	/// Selects the fields at the given paths of the records of the writer's schema. A path names a
+	/// field of the top level record, or of a record nested in it with its enclosing fields separated
+	/// by dots, such as `inner_rec.id`. Paths reach through arrays, maps and unions into the records
+	/// they hold. Fields which are not selected are skipped, as are records with none of their fields selected.
+	pub fn from_paths(writer: &CompiledSchema, paths: &[&str]) -> Result<Self, AvroErr> {
+		let mut selection = Selection::Fields(BTreeMap::new());
+		for path in paths {
+			if path.split('.').any(|step| step.is_empty()) {
+				return Err(AvroErr::schema(format!("Invalid field path {:?}", path)));
+			}
+			selection.add(path.split('.'));
+		}
+		let mut nodes = vec![];
+		let root = select(writer, writer.root(), &selection, &mut nodes)?;
+		Ok(Projection { schema: writer.clone(), nodes, root })
+	}
+
+	/// Retrieves a reference to the writer's schema, which the decoded data is encoded with
+	pub fn writer_schema(&self) -> &CompiledSchema {
+		&self.schema
+	}
+
+	/// Decodes a value out of the reader, failing with `AvroErr::LimitExceeded` on data exceeding the given limits
+	pub fn decode<R: Read>(&self, limits: &DecoderLimits, reader: &mut R) -> Result<Type, AvroErr> {
+		self.decode_node(self.root, limits, 0, &mut 0, reader)
+	}
+
+	/// Decodes a value, adding the items of arrays encoded in no bytes to `zero_width_items`,
+	/// which spans the values of a data block
+	pub(crate) fn decode_counted<R: Read>(&self, limits: &DecoderLimits, zero_width_items: &mut u64, reader: &mut R) -> Result<Type, AvroErr> {
+		self.decode_node(self.root, limits, 0, zero_width_items, reader)
+	}
+
+	/// Decodes a value with the projection at the given index, nested `depth` values deep
+	fn decode_node<R: Read>(&self, node: usize, limits: &DecoderLimits, depth: usize, zero_width_items: &mut u64, reader: &mut R) -> Result<Type, AvroErr> {
+		if depth > limits.max_depth {
+			return Err(AvroErr::limit("max_depth", depth as u64, limits.max_depth as u64));
+		}
+		match self.nodes[node] {
+			ProjectedNode::Whole(node) => Type::decode_limited(&self.schema, node, limits, depth, zero_width_items, reader),
+			ProjectedNode::Record { ref name, ref fields } => {
+				let mut values = vec![];
+				for field in fields {
+					let value = match field.projection {
+						Some(projection) => self.decode_node(projection, limits, depth + 1, zero_width_items, reader).map(Some),
+						None => Type::skip_limited(&self.schema, field.node, limits, depth + 1, reader).map(|_| None)
+					};
+					if let Some(value) = value.map_err(|e| e.within(&format!(".{}", field.name)))? {
+						values.push((field.position, Field::new(&field.name, value)));
+					}
+				}
+				// Fields are decoded in the order of the writer's schema
+				values.sort_by_key(|&(position, _)| position);
+				Ok(Type::Record(Record::new(name, None, values.into_iter().map(|(_, field)| field).collect())))
+			}
+			ProjectedNode::Array(items) => {
+				let mut arr = vec![];
+				loop {
+					let count = decode_block_count(reader, arr.len(), limits)?;
+					if count == 0 {
+						return Ok(Type::Array(arr));
+					}
+					count_zero_width_items(self.min_size(items), count, limits, zero_width_items)?;
+					arr.reserve(count.min(RESERVE_LIMIT));
+					for _ in 0..count {
+						let item = self.decode_node(items, limits, depth + 1, zero_width_items, reader).map_err(|e| e.within(&format!("[{}]", arr.len())))?;
+						arr.push(item);
+					}
+				}
+			}
+			ProjectedNode::Map(values) => {
+				let mut map = HashMap::new();
+				loop {
+					let count = decode_block_count(reader, map.len(), limits)?;
+					if count == 0 {
+						return Ok(Type::Map(map));
+					}
+					map.reserve(count.min(RESERVE_LIMIT));
+					for _ in 0..count {
+						let key = decode_string(reader, limits)?;
+						let value = self.decode_node(values, limits, depth + 1, zero_width_items, reader).map_err(|e| e.within(&format!("[{:?}]", key)))?;
+						map.insert(key, value);
+					}
+				}
+			}
+			ProjectedNode::Union(ref branches) => {
+				let idx = i64::decode(reader)?;
+				let branch = if idx < 0 { None } else { branches.get(idx as usize) };
+				let branch = branch.ok_or_else(|| AvroErr::decode(format!("Union branch {} does not exist", idx)))?;
+				let value = self.decode_node(*branch, limits, depth + 1, zero_width_items, reader).map_err(|e| e.within(&format!("[{}]", idx)))?;
+				Ok(Type::Union(idx as usize, Box::new(value)))
+			}
+		}
+	}
+
+	/// Returns the fewest bytes the writer encoded the values of the projection at the given index in
+	fn min_size(&self, node: usize) -> u64 {
+		match self.nodes[node] {
+			ProjectedNode::Whole(node) => self.schema.min_size(node),
+			// Skipped fields take up their bytes all the same
+			ProjectedNode::Record { ref fields, .. } => fields.iter().fold(0u64, |size, field| size.saturating_add(self.schema.min_size(field.node))),
+			ProjectedNode::Array(_) | ProjectedNode::Map(_) | ProjectedNode::Union(_) => 1
+		}
+	}
+}
+
+/// Builds the nodes of a `Projection`, walking the writer's and reader's schemas side by side
+struct Resolver<'a> {
+	writer: &'a CompiledSchema,
+	reader: &'a CompiledSchema,
+	nodes: Vec<ProjectedNode>,
+	/// Projections of the pairs of writer's and reader's nodes resolved so far, which keeps
+	/// recursive types from being resolved forever
+	resolved: HashMap<(usize, usize), usize>
+}
+
+impl<'a> Resolver<'a> {
+	/// Resolves the node of the reader's schema against the node of the writer's schema,
+	/// returning the index of their projection
+	fn resolve(&mut self, writer: usize, reader: usize) -> Result<usize, AvroErr> {
+		if let Some(&idx) = self.resolved.get(&(writer, reader)) {
+			return Ok(idx);
+		}
+		// The node is filled in once resolved, so that recursive references can point to it
+		let idx = self.nodes.len();
+		self.nodes.push(ProjectedNode::Whole(writer));
+		self.resolved.insert((writer, reader), idx);
+		let (writer_schema, reader_schema) = (self.writer, self.reader);
+		let node = match (writer_schema.node(writer), reader_schema.node(reader)) {
+			(SchemaNode::Record { name, fields }, SchemaNode::Record { name: reader_name, fields: reader_fields }) => {
+				if short_name(name) != short_name(reader_name) {
+					return Err(AvroErr::schema(format!("Expected record {} as in the writer's schema, found record {}", name, reader_name)));
+				}
+				if let Some(missing) = reader_fields.iter().find(|rf| fields.iter().all(|f| f.name != rf.name)) {
+					let reason = format!("Field {} is not in the writer's schema", missing.name);
+					return Err(AvroErr::schema(reason).within(&format!(".{}", missing.name)));
+				}
+				let mut projected = Vec::with_capacity(fields.len());
+				for field in fields {
+					let position = reader_fields.iter().position(|rf| rf.name == field.name);
+					let projection = match position {
+						Some(position) => Some(self.resolve(field.node, reader_fields[position].node).map_err(|e| e.within(&format!(".{}", field.name)))?),
+						None => None
+					};
+					projected.push(ProjectedField { name: field.name.clone(), node: field.node, projection, position: position.unwrap_or(0) });
+				}
+				ProjectedNode::Record { name: reader_name.clone(), fields: projected }
+			}
+			(SchemaNode::Array(items), SchemaNode::Array(reader_items)) => {
+				ProjectedNode::Array(self.resolve(*items, *reader_items).map_err(|e| e.within("[*]"))?)
+			}
+			(SchemaNode::Map(values), SchemaNode::Map(reader_values)) => {
+				ProjectedNode::Map(self.resolve(*values, *reader_values).map_err(|e| e.within("[*]"))?)
+			}
+			(SchemaNode::Union(branches), SchemaNode::Union(reader_branches)) if branches.len() == reader_branches.len() => {
+				let mut projected = Vec::with_capacity(branches.len());
+				for (i, (&branch, &reader_branch)) in branches.iter().zip(reader_branches).enumerate() {
+					projected.push(self.resolve(branch, reader_branch).map_err(|e| e.within(&format!("[{}]", i)))?);
+				}
+				ProjectedNode::Union(projected)
+			}
+			// Nodes of the remaining types hold no indices, so they can be compared across schemas
+			(node, reader_node) if node == reader_node => ProjectedNode::Whole(writer),
+			(node, reader_node) => {
+				return Err(AvroErr::schema(format!("Expected {} as in the writer's schema, found {}", node, reader_node)));
+			}
+		};
+		self.nodes[idx] = node;
+		Ok(idx)
+	}
+}
+
+/// The fields selected out of a value by field paths
+#[derive(Debug)]
+enum Selection {
+	/// The whole value is selected
+	Whole,
+	/// The fields with the given names are selected out of the records the value holds
+	Fields(BTreeMap<String, Selection>)
+}
+
+impl Selection {
+	/// Adds the field at the path made of the given steps to the selection
+	fn add<'a, I: Iterator<Item = &'a str>>(&mut self, mut steps: I) {
+		if let Selection::Fields(ref mut fields) = *self {
+			match steps.next() {
+				Some(step) => fields.entry(step.to_string()).or_insert_with(|| Selection::Fields(BTreeMap::new())).add(steps),
+				None => *self = Selection::Whole
+			}
+		}
+	}
+}
+
+/// Pushes the nodes projecting the selection out of the node of the writer's schema at the
+/// given index, returning the index of the projection
+fn select(schema: &CompiledSchema, node: usize, selection: &Selection, nodes: &mut Vec<ProjectedNode>) -> Result<usize, AvroErr> {
+	let fields = match *selection {
+		Selection::Whole => {
+			nodes.push(ProjectedNode::Whole(node));
+			return Ok(nodes.len() - 1);
+		}
+		Selection::Fields(ref fields) => fields
+	};
+	let projected = match *schema.node(node) {
+		SchemaNode::Record { ref name, fields: ref record_fields } => {
+			if let Some(missing) = fields.keys().find(|name| record_fields.iter().all(|f| f.name != **name)) {
+				let reason = format!("Field {} is not in the writer's schema", missing);
+				return Err(AvroErr::schema(reason).within(&format!(".{}", missing)));
+			}
+			let mut projected = Vec::with_capacity(record_fields.len());
+			for field in record_fields {
+				let projection = match fields.get(&field.name) {
+					Some(selection) => Some(select(schema, field.node, selection, nodes).map_err(|e| e.within(&format!(".{}", field.name)))?),
+					None => None
+				};
+				// Paths give no order of their own, so fields keep the order of the writer's schema
+				let position = projected.len();
+				projected.push(ProjectedField { name: field.name.clone(), node: field.node, projection, position });
+			}
+			ProjectedNode::Record { name: name.clone(), fields: projected }
+		}
+		SchemaNode::Array(items) => ProjectedNode::Array(select(schema, items, selection, nodes).map_err(|e| e.within("[*]"))?),
+		SchemaNode::Map(values) => ProjectedNode::Map(select(schema, values, selection, nodes).map_err(|e| e.within("[*]"))?),
+		SchemaNode::Union(ref branches) => {
+			let mut projected = Vec::with_capacity(branches.len());
+			for (i, &branch) in branches.iter().enumerate() {
+				// Branches holding no records, such as null, are decoded whole
+				let selection = if holds_records(schema, branch) { selection } else { &Selection::Whole };
+				projected.push(select(schema, branch, selection, nodes).map_err(|e| e.within(&format!("[{}]", i)))?);
+			}
+			ProjectedNode::Union(projected)
+		}
+		ref other => {
+			let names: Vec<&str> = fields.keys().map(|name| name.as_str()).collect();
+			return Err(AvroErr::schema(format!("Expected a record to select fields {} of, found {}", names.join(", "), other)));
+		}
+	};
+	nodes.push(projected);
+	Ok(nodes.len() - 1)
+}
+
+/// Tells whether values of the node can hold records, however deep within arrays, maps and unions
+fn holds_records(schema: &CompiledSchema, node: usize) -> bool {
+	let mut seen = vec![false; schema.nodes().len()];
+	let mut pending = vec![node];
+	while let Some(node) = pending.pop() {
+		if seen[node] {
+			continue;
+		}
+		seen[node] = true;
+		match *schema.node(node) {
+			SchemaNode::Record { .. } => return true,
+			SchemaNode::Array(inner) | SchemaNode::Map(inner) => pending.push(inner),
+			SchemaNode::Union(ref branches) => pending.extend(branches),
+			_ => ()
+		}
+	}
+	false
+}
+
+/// Strips the namespace off the full name of a named type
+fn short_name(name: &str) -> &str {
+	name.rsplit('.').next().unwrap_or(name)
+}
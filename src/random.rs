@@ -0,0 +1,320 @@
+//! This module declares a `RandomGenerator`, which generates random values of any schema, for
+//! writing test data files and for property tests. Values follow the logical types of the schema,
+//! so that dates, timestamps, uuids and decimals are plausible ones. Generators created with the
+//! same seed generate the same values.
+
+use std::collections::HashMap;
+
+use rand::{Isaac64Rng, Rng, SeedableRng};
+
+use complex::{Enum, Field, Record};
+use errors::AvroErr;
+use schema::{CompiledSchema, LogicalType, SchemaNode};
+use types::Type;
+
+/// Days since the unix epoch of 2000-01-01 and 2030-01-01, the range of generated dates
+const DATES: (i32, i32) = (10_957, 21_915);
+
+/// Milliseconds since the unix epoch of 2000-01-01 and 2030-01-01, the range of generated timestamps
+const TIMESTAMPS_MILLIS: (i64, i64) = (946_684_800_000, 1_893_456_000_000);
+
+/// Milliseconds in a day
+const DAY_MILLIS: i64 = 86_400_000;
+
+/// Generates random values of a schema
+#[derive(Debug)]
+pub struct RandomGenerator {
+	schema: CompiledSchema,
+	rng: Isaac64Rng,
+	max_string_len: usize,
+	max_items: usize,
+	max_depth: usize,
+	/// Whether each node is a record that can hold values of its own type
+	recursive: Vec<bool>,
+	/// Recursion depth of the shallowest value of each node, or None for nodes without finite values
+	min_depths: Vec<Option<usize>>
+}
+
+impl RandomGenerator {
+	/// Creates a generator of values of the schema, seeded with the given seed. Fails for schemas
+	/// without finite values, like records with a field of their own type.
+	pub fn new(schema: CompiledSchema, seed: u64) -> Result<Self, AvroErr> {
+		let recursive = recursive_records(&schema);
+		let min_depths = min_depths(&schema, &recursive);
+		if min_depths[schema.root()].is_none() {
+			return Err(AvroErr::schema("Schema has no finite values"));
+		}
+		Ok(RandomGenerator {
+			schema,
+			rng: Isaac64Rng::from_seed(&[seed][..]),
+			max_string_len: 16,
+			max_items: 8,
+			max_depth: 8,
+			recursive,
+			min_depths
+		})
+	}
+
+	/// Sets the maximum length of generated strings, bytes and map keys. Defaults to 16.
+	pub fn set_max_string_len(&mut self, len: usize) {
+		self.max_string_len = len;
+	}
+
+	/// Sets the maximum no of items of generated arrays and maps. Arrays and maps within values of
+	/// recursive records hold at most half as many items at each level of recursion. Defaults to 8.
+	pub fn set_max_items(&mut self, items: usize) {
+		self.max_items = items;
+	}
+
+	/// Sets the no of times that values of recursive records may nest within each other, where the
+	/// schema lets them end. Defaults to 8.
+	pub fn set_max_depth(&mut self, depth: usize) {
+		self.max_depth = depth;
+	}
+
+	/// Returns the schema of the generated values
+	pub fn schema(&self) -> &CompiledSchema {
+		&self.schema
+	}
+
+	/// Generates a value of the schema
+	pub fn generate(&mut self) -> Type {
+		let mut values = Values {
+			schema: &self.schema,
+			rng: &mut self.rng,
+			max_string_len: self.max_string_len,
+			max_items: self.max_items,
+			max_depth: self.max_depth,
+			recursive: &self.recursive,
+			min_depths: &self.min_depths
+		};
+		values.value(self.schema.root(), 0)
+	}
+}
+
+/// Generates the values of a `RandomGenerator`, borrowing its schema apart from its rng
+struct Values<'a> {
+	schema: &'a CompiledSchema,
+	rng: &'a mut Isaac64Rng,
+	max_string_len: usize,
+	max_items: usize,
+	max_depth: usize,
+	recursive: &'a [bool],
+	min_depths: &'a [Option<usize>]
+}
+
+impl<'a> Values<'a> {
+	fn value(&mut self, node: usize, depth: usize) -> Type {
+		let schema = self.schema;
+		let logical_type = schema.logical_type(node);
+		match *schema.node(node) {
+			SchemaNode::Null => Type::Null,
+			SchemaNode::Boolean => Type::Bool(self.rng.gen()),
+			SchemaNode::Int => Type::Int(match logical_type {
+				Some(LogicalType::Date) => self.rng.gen_range(DATES.0, DATES.1),
+				Some(LogicalType::TimeMillis) => self.rng.gen_range(0, DAY_MILLIS as i32),
+				_ => self.rng.gen()
+			}),
+			SchemaNode::Long => Type::Long(match logical_type {
+				Some(LogicalType::TimeMicros) => self.rng.gen_range(0, DAY_MILLIS * 1000),
+				Some(LogicalType::TimestampMillis) | Some(LogicalType::LocalTimestampMillis) =>
+					self.rng.gen_range(TIMESTAMPS_MILLIS.0, TIMESTAMPS_MILLIS.1),
+				Some(LogicalType::TimestampMicros) | Some(LogicalType::LocalTimestampMicros) =>
+					self.rng.gen_range(TIMESTAMPS_MILLIS.0 * 1000, TIMESTAMPS_MILLIS.1 * 1000),
+				_ => self.rng.gen()
+			}),
+			SchemaNode::Float => Type::Float(self.rng.gen_range(-1e6, 1e6)),
+			SchemaNode::Double => Type::Double(self.rng.gen_range(-1e9, 1e9)),
+			SchemaNode::Bytes => Type::Bytes(match logical_type {
+				Some(LogicalType::Decimal { precision, .. }) => self.decimal(precision, None),
+				_ => {
+					let len = self.rng.gen_range(0, self.max_string_len + 1);
+					self.bytes(len)
+				}
+			}),
+			SchemaNode::String => Type::Str(match logical_type {
+				Some(LogicalType::Uuid) => self.uuid(),
+				_ => {
+					let len = self.rng.gen_range(0, self.max_string_len + 1);
+					self.string(len)
+				}
+			}),
+			SchemaNode::Record { ref name, ref fields } => {
+				let depth = if self.recursive[node] { depth + 1 } else { depth };
+				let values = fields.iter().map(|field| Field::new(&field.name, self.value(field.node, depth))).collect();
+				Type::Record(Record::new(name, None, values))
+			}
+			SchemaNode::Enum { ref name, ref symbols } => {
+				let mut value = Enum::from_symbols(name, symbols.clone());
+				value.set_value(&symbols[self.rng.gen_range(0, symbols.len())]);
+				Type::Enum(value)
+			}
+			SchemaNode::Array(items) => {
+				let len = self.collection_len(depth);
+				Type::Array((0..len).map(|_| self.value(items, depth)).collect())
+			}
+			SchemaNode::Map(values) => {
+				let len = self.collection_len(depth);
+				let mut map = HashMap::with_capacity(len);
+				for _ in 0..len {
+					let key_len = self.rng.gen_range(1, self.max_string_len.max(1) + 1);
+					let key = self.string(key_len);
+					let value = self.value(values, depth);
+					map.insert(key, value);
+				}
+				Type::Map(map)
+			}
+			SchemaNode::Union(ref branches) => {
+				let branch = self.branch(branches, depth);
+				Type::Union(branch, Box::new(self.value(branches[branch], depth)))
+			}
+			SchemaNode::Fixed { size, .. } => Type::Fixed(match logical_type {
+				Some(LogicalType::Decimal { precision, .. }) => self.decimal(precision, Some(size)),
+				Some(LogicalType::Duration) => self.duration(),
+				_ => self.bytes(size)
+			})
+		}
+	}
+
+	/// Picks a branch of the union with the given branches whose values can end within the
+	/// maximum depth, or the branch with the shallowest values when none of them can
+	fn branch(&mut self, branches: &[usize], depth: usize) -> usize {
+		let (min_depths, max_depth) = (self.min_depths, self.max_depth);
+		let within: Vec<usize> = (0..branches.len())
+			.filter(|&i| min_depths[branches[i]].is_some_and(|min| depth + min <= max_depth))
+			.collect();
+		match self.rng.choose(&within) {
+			Some(&branch) => branch,
+			None => (0..branches.len()).filter(|&i| min_depths[branches[i]].is_some())
+				.min_by_key(|&i| min_depths[branches[i]]).unwrap_or(0)
+		}
+	}
+
+	/// Picks the no of items of an array or map, which are empty at the maximum depth
+	fn collection_len(&mut self, depth: usize) -> usize {
+		if depth >= self.max_depth {
+			return 0;
+		}
+		// Collections of the outermost recursive record keep their full size
+		let max_items = self.max_items.checked_shr(depth.saturating_sub(1) as u32).unwrap_or(0);
+		self.rng.gen_range(0, max_items + 1)
+	}
+
+	fn bytes(&mut self, len: usize) -> Vec<u8> {
+		let mut bytes = vec![0; len];
+		self.rng.fill_bytes(&mut bytes);
+		bytes
+	}
+
+	/// Generates an alphanumeric string of the given length
+	fn string(&mut self, len: usize) -> String {
+		self.rng.gen_ascii_chars().take(len).collect()
+	}
+
+	/// Generates a version 4 uuid in its canonical form
+	fn uuid(&mut self) -> String {
+		let mut bytes = self.bytes(16);
+		bytes[6] = bytes[6] & 0x0f | 0x40;
+		bytes[8] = bytes[8] & 0x3f | 0x80;
+		let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+		format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+	}
+
+	/// Generates the unscaled value of a decimal with at most the given no of digits, in big endian
+	/// two's complement. Bytes decimals take the fewest bytes, and fixed ones are sign extended to their size.
+	fn decimal(&mut self, precision: usize, size: Option<usize>) -> Vec<u8> {
+		let bound = 10i64.pow(precision.min(18) as u32);
+		let unscaled = self.rng.gen_range(-bound + 1, bound);
+		let bytes = unscaled.to_be_bytes();
+		match size {
+			Some(size) if size >= bytes.len() => {
+				let sign = if unscaled < 0 { 0xff } else { 0 };
+				let mut extended = vec![sign; size - bytes.len()];
+				extended.extend_from_slice(&bytes);
+				extended
+			}
+			// The precision of the decimal keeps its value within the size of the fixed
+			Some(size) => bytes[bytes.len() - size..].to_vec(),
+			None => {
+				let mut start = 0;
+				while start < bytes.len() - 1 && (bytes[start] == 0 && bytes[start + 1] < 0x80 || bytes[start] == 0xff && bytes[start + 1] >= 0x80) {
+					start += 1;
+				}
+				bytes[start..].to_vec()
+			}
+		}
+	}
+
+	/// Generates a duration of months, days and milliseconds, each a little endian u32
+	fn duration(&mut self) -> Vec<u8> {
+		let parts = [self.rng.gen_range(0u32, 1200), self.rng.gen_range(0u32, 31), self.rng.gen_range(0u32, DAY_MILLIS as u32)];
+		parts.iter().flat_map(|part| part.to_le_bytes().to_vec()).collect()
+	}
+}
+
+impl Iterator for RandomGenerator {
+	type Item = Type;
+
+	fn next(&mut self) -> Option<Type> {
+		Some(self.generate())
+	}
+}
+
+/// Gives the nodes that values of a node can directly hold
+fn children(node: &SchemaNode) -> Vec<usize> {
+	match *node {
+		SchemaNode::Record { ref fields, .. } => fields.iter().map(|field| field.node).collect(),
+		SchemaNode::Array(items) => vec![items],
+		SchemaNode::Map(values) => vec![values],
+		SchemaNode::Union(ref branches) => branches.clone(),
+		_ => vec![]
+	}
+}
+
+/// Finds the records whose values can hold values of the same record
+fn recursive_records(schema: &CompiledSchema) -> Vec<bool> {
+	let nodes = schema.nodes();
+	(0..nodes.len()).map(|record| {
+		if let SchemaNode::Record { .. } = nodes[record] {
+			let mut seen = vec![false; nodes.len()];
+			let mut pending = children(&nodes[record]);
+			while let Some(node) = pending.pop() {
+				if node == record {
+					return true;
+				}
+				if !seen[node] {
+					seen[node] = true;
+					pending.extend(children(&nodes[node]));
+				}
+			}
+		}
+		false
+	}).collect()
+}
+
+/// Finds the recursion depth of the shallowest value of each node. Nodes referring to each other
+/// are revisited until their depths settle, and the nodes left without one have no finite values.
+fn min_depths(schema: &CompiledSchema, recursive: &[bool]) -> Vec<Option<usize>> {
+	let nodes = schema.nodes();
+	let mut depths: Vec<Option<usize>> = vec![None; nodes.len()];
+	let mut changed = true;
+	while changed {
+		changed = false;
+		for (idx, node) in nodes.iter().enumerate() {
+			let depth = match *node {
+				SchemaNode::Record { ref fields, .. } => fields.iter()
+					.try_fold(0, |deepest, field| depths[field.node].map(|depth| deepest.max(depth)))
+					.map(|deepest| if recursive[idx] { deepest + 1 } else { deepest }),
+				SchemaNode::Union(ref branches) => branches.iter().filter_map(|&branch| depths[branch]).min(),
+				SchemaNode::Enum { ref symbols, .. } if symbols.is_empty() => None,
+				// Arrays and maps can be empty
+				_ => Some(0)
+			};
+			if depth != depths[idx] {
+				depths[idx] = depth;
+				changed = true;
+			}
+		}
+	}
+	depths
+}
@@ -1,136 +1,623 @@
+//! This module declares an `AvroReader` which reads back data from an avro data file,
+//! decoding the records with the schema stored in the data file header.
 
-// use std::io::Read;
-// use std::path::Path;
-// use std::fs::OpenOptions;
-// use writer::{Header, SyncMarker, Codec, decompress_snappy};
-// use codec::Decoder;
-// use types::Schema;
-// use std::fs::File;
-// use std::marker::PhantomData;
-// use types::FromAvro;
-// use errors::AvroErr;
-// use std::io::Cursor;
-
-// use byteorder::{BigEndian, ReadBytesExt};
-
-// /// Allows reading from an avro data file
-// #[derive(Debug)]
-// pub struct AvroReader {
-//     header: Header,
-//     buffer: File,
-//     block_count: u64
-// }
-
-// #[derive(Debug)]
-// /// The iterator implementation for iterating over blocks of avro data file
-// /// Design: A BlockReader represents a data block in avro and it implements the Iterator trait. So it
-// /// is provide convenient api for users to just write a for loop to read data blocks from an avro file.
-// /// It abstracts away the details of reading both uncompressed and compresssed writes.
-// /// TODO write more on it.
-// pub struct BlockReader<T> {
-//     sync: SyncMarker,
-//     stream: File,
-//     schema: FromAvro,
-//     block_count: u64,
-//     parsed_data: PhantomData<T>,
-//     codec: Codec,
-//     decompressed_data: Option<Cursor<Vec<u8>>>,
-// }
-
-// impl<T: From<Schema>> BlockReader<T> {
-//     fn decode_block(&mut self) -> Option<T> {
-//         self.schema.clone().decode(&mut self.stream).ok().map(|d| {
-//             self.block_count -= 1;
-//             d.into()
-//         })
-//     }
-
-//     fn decode_compressed_block(&mut self) -> Option<T> {
-//         if self.decompressed_data.is_some() {
-//             let mut data = self.decompressed_data.take().unwrap();
-//             let decoded = self.schema.clone().decode(&mut data).ok().map(|d| {
-//                 self.block_count -= 1;
-//                 d.into()
-//             });
-//             self.decompressed_data = Some(data);
-//             decoded
-//         } else {
-//             let compressed_data_len_plus_cksum = FromAvro::Long.decode(&mut self.stream).unwrap().long_ref();
-//             let compressed_data_len = compressed_data_len_plus_cksum - 4;
-            
-//             let mut compressed_buf = vec![0u8; compressed_data_len as usize];
-//             let _ = self.stream.read_exact(&mut compressed_buf);
-//             let decompressed_data = decompress_snappy(compressed_buf.as_slice());
-//             let mut cksum_buf = vec![0u8; 4];
-//             let _ = self.stream.read_exact(&mut cksum_buf);
-//             let cksum = cksum_buf.as_slice().read_u32::<BigEndian>().unwrap();
-//             let mut cursored_data = Cursor::new(decompressed_data);
-//             let decoded = self.schema.clone().decode(&mut cursored_data).ok().map(|d| {
-//                 self.block_count -= 1;
-//                 d.into()
-//             });
-//             self.decompressed_data = Some(cursored_data);
-//             decoded
-//         }
-        
-//     }
-// }
-
-// impl<T: From<Schema>> Iterator for BlockReader<T> {
-//     type Item = T;
-//     fn next(&mut self) -> Option<Self::Item> {
-//         if self.block_count == 0 {
-//             let end_marker = SyncMarker::new().decode(&mut self.stream).unwrap();
-//             if !(end_marker == self.sync) {
-//                 error!("Possible data corruption! Sync markers do not match");
-//             }
-//             return None;
-//         } else {
-//             match self.codec {
-//                 Codec::Null => self.decode_block(),
-//                 Codec::Snappy => self.decode_compressed_block(),
-//                 Codec::Deflate => unimplemented!()
-//             }
-//         }
-//     }
-// }
-
-// impl AvroReader {
-//     /// Create a avro reader from an existing data file
-//     pub fn from_path<R: AsRef<Path>>(path: R) -> Result<AvroReader, AvroErr> {
-//         let mut reader = OpenOptions::new().read(true).open(path)?;
-//         let header = Header::new().decode(&mut reader)?;
-//         let block_count = FromAvro::Long.decode(&mut reader)?;
-//         // TODO buf_len is unused as of now
-//         // let buf_len = FromAvro::Long.decode(&mut reader)?;
-//         let avro_reader = AvroReader {
-//             header: header,
-//             buffer: reader,
-//             block_count: block_count.long_ref() as u64
-//         };
-//         Ok(avro_reader)
-//     }
-
-//     /// Returns an interator for data blocks in an avro data file
-//     pub fn iter_block<T>(self) -> BlockReader<T> {
-//         let mut reader = self.buffer;
-//         let schema = self.header.get_schema().unwrap();
-//         let codec = self.header.get_codec().unwrap();
-//         if let Codec::Null = codec {
-//             // Read the serialized buffer size if no codec, as in case of codec present
-//             // we do it in our iter calls
-//             // TODO this is unused. use it
-//             let ser_data_sz = FromAvro::Long.decode(&mut reader);
-//         }
-//         let Header {magic, metadata, sync_marker} = self.header;
-//         BlockReader {
-//             stream: reader,
-//             sync: sync_marker,
-//             schema: schema,
-//             parsed_data: PhantomData,
-//             block_count: self.block_count,
-//             codec: codec,
-//             decompressed_data: None
-//         }
-//     }
-// }
+use std::io::{self, Read, Seek, SeekFrom, BufReader, Cursor};
+use std::fs::File;
+use std::path::Path;
+use std::vec;
+
+use codec::{Decoder, DecoderLimits};
+use errors::AvroErr;
+use pool::OrderedPool;
+use schema::{AvroSchema, CompiledSchema};
+use projection::Projection;
+use types::Type;
+use writer::{check_block_records, check_block_size, Codec, Header, SYNC_MARKER_SIZE};
+
+/// Wraps a reader and keeps track of the number of bytes read from it, so that
+/// `AvroReader` knows its byte offset in the data file.
+#[derive(Debug)]
+struct PositionTracker<R> {
+	inner: R,
+	position: u64
+}
+
+impl<R: Read> Read for PositionTracker<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let read = self.inner.read(buf)?;
+		self.position += read as u64;
+		Ok(read)
+	}
+}
+
+/// Reads a long from the start of a block, telling apart the data file ending in the middle of it
+fn read_block_long<R: Read>(reader: &mut R, block_offset: u64) -> Result<i64, AvroErr> {
+	let mut encoded = Vec::with_capacity(10);
+	let mut byte = [0u8; 1];
+	loop {
+		if reader.read(&mut byte)? == 0 {
+			return Err(AvroErr::TruncatedBlock(block_offset));
+		}
+		encoded.push(byte[0]);
+		if byte[0] & 0b1000_0000 == 0 || encoded.len() == 10 {
+			break;
+		}
+	}
+	i64::decode(&mut encoded.as_slice())
+}
+
+/// A data block of an avro data file, as it is stored in the file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+	/// No of records in the block
+	pub count: i64,
+	/// The serialized records of the block, compressed with the codec of the data file
+	pub data: Vec<u8>,
+	/// The codec the block data is compressed with
+	pub codec: Codec,
+	/// Byte offset of the block in the data file
+	pub offset: u64
+}
+
+impl Block {
+	/// Returns the size in bytes of the block data as stored in the data file
+	pub fn compressed_size(&self) -> usize {
+		self.data.len()
+	}
+
+	/// Decompresses the block data, giving back the serialized records of the block
+	pub fn decompress(&self) -> Result<Vec<u8>, AvroErr> {
+		self.codec.decompress(&self.data)
+	}
+}
+
+/// An iterator over the data blocks of an avro data file, which gives access to the blocks
+/// without decoding the records in them. Created with `AvroReader::iter_block`.
+#[derive(Debug)]
+pub struct BlockReader<R> {
+	reader: AvroReader<R>
+}
+
+impl<R> BlockReader<R> {
+	/// Retrieves a reference to the header of the data file
+	pub fn header(&self) -> &Header {
+		&self.reader.header
+	}
+}
+
+impl<R: Read> Iterator for BlockReader<R> {
+	type Item = Result<Block, AvroErr>;
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.reader.finished {
+			return None;
+		}
+		match self.reader.read_raw_block() {
+			Ok(Some(block)) => Some(Ok(block)),
+			Ok(None) => {
+				self.reader.finished = true;
+				None
+			}
+			Err(e) => {
+				self.reader.finished = true;
+				Some(Err(e))
+			}
+		}
+	}
+}
+
+/// `AvroReader` reads an avro data file block by block and iterates over the
+/// records contained in it.
+#[derive(Debug)]
+pub struct AvroReader<R> {
+	/// The header of the data file being read
+	header: Header,
+	/// The codec the data blocks are compressed with
+	codec: Codec,
+	/// The writer's schema, compiled for decoding records
+	schema: CompiledSchema,
+	/// The underlying data file
+	stream: PositionTracker<R>,
+	/// Byte offset in the data file where the data blocks start
+	data_start: u64,
+	/// Byte offset of the start of the block being read
+	block_start: u64,
+	/// No of the next block to be read, counting from zero. Unknown after seeking into the data file.
+	next_block: Option<u64>,
+	/// No of the block being read
+	block_index: Option<u64>,
+	/// Decompressed contents of the block being read
+	block: Cursor<Vec<u8>>,
+	/// No of records left to be read in the current block
+	block_remaining: i64,
+	/// No of items of arrays encoded in no bytes decoded out of the current block
+	block_zero_width_items: u64,
+	/// The incomplete block at the end of a truncated data file
+	truncated_block: Option<Block>,
+	/// Whether the complete records of a truncated block should be read
+	read_partial_block: bool,
+	/// Offset of the truncated block, while its complete records are being read
+	partial_block: Option<u64>,
+	/// Limits on the blocks and records decoded
+	limits: DecoderLimits,
+	/// Projection of the writer's schema onto the reader's schema, if one was set
+	projection: Option<Projection>,
+	/// Set once the end of the data file is reached or an error was encountered
+	finished: bool
+}
+
+impl AvroReader<BufReader<File>> {
+	/// Create an avro reader from an existing data file
+	pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, AvroErr> {
+		let datafile = File::open(path)?;
+		AvroReader::new(BufReader::new(datafile))
+	}
+}
+
+impl<R: Read> AvroReader<R> {
+	/// Creates an avro reader over the given stream, which must be positioned at the start of
+	/// an avro data file. The data file header is read right away.
+	pub fn new(reader: R) -> Result<Self, AvroErr> {
+		let mut stream = PositionTracker { inner: reader, position: 0 };
+		let header = Header::decode(&mut stream)?;
+		let codec = header.get_codec()?;
+		let schema = header.schema.compile()?;
+		let data_start = stream.position;
+		Ok(AvroReader {
+			header,
+			codec,
+			schema,
+			stream,
+			data_start,
+			block_start: data_start,
+			next_block: Some(0),
+			block_index: None,
+			block: Cursor::new(vec![]),
+			block_remaining: 0,
+			block_zero_width_items: 0,
+			truncated_block: None,
+			read_partial_block: false,
+			partial_block: None,
+			limits: DecoderLimits::default(),
+			projection: None,
+			finished: false
+		})
+	}
+
+	/// Retrieves a reference to the header of the data file
+	pub fn header(&self) -> &Header {
+		&self.header
+	}
+
+	/// Retrieves a reference to the schema the data file was written with
+	pub fn get_schema(&self) -> &AvroSchema {
+		&self.header.schema
+	}
+
+	/// Retrieves the codec the data file was written with
+	pub fn get_codec(&self) -> Codec {
+		self.codec
+	}
+
+	/// Returns the current byte offset of the reader in the data file. Blocks are read
+	/// whole, so this is the offset right past the last block that was read.
+	pub fn tell(&self) -> u64 {
+		self.stream.position
+	}
+
+	/// Returns the byte offset of the synchronization point at which the block currently being
+	/// read starts. Passing it to `seek_to_sync` resumes reading at the start of that block.
+	pub fn previous_sync(&self) -> u64 {
+		self.block_start
+	}
+
+	/// When enabled, and the data file is not compressed, the complete records at the start of
+	/// a truncated final block are read before reporting the truncation with an
+	/// `AvroErr::TruncatedBlock` error. Disabled by default.
+	pub fn set_read_partial_block(&mut self, enabled: bool) {
+		self.read_partial_block = enabled;
+	}
+
+	/// Sets the limits on the blocks and records read, which guard against corrupt or malicious data files.
+	/// Data exceeding them fails with `AvroErr::LimitExceeded`. `DecoderLimits::default()` applies otherwise.
+	pub fn set_limits(&mut self, limits: DecoderLimits) {
+		self.limits = limits;
+	}
+
+	/// Decodes records with the given reader's schema, which may leave out fields of the records of
+	/// the writer's schema. The fields left out are skipped over without being decoded, and the fields
+	/// decoded are listed in the order the reader's schema declares them.
+	/// Fails with `AvroErr::SchemaParse` if the schema does not match the writer's schema.
+	pub fn set_reader_schema(&mut self, schema: &AvroSchema) -> Result<(), AvroErr> {
+		self.projection = Some(Projection::new(&self.schema, &schema.compile()?)?);
+		Ok(())
+	}
+
+	/// Decodes only the fields at the given paths of the records, such as `MESSAGE` or `inner_rec.id`
+	/// for a field of a nested record. The other fields are skipped over without being decoded.
+	/// See `Projection::from_paths` for details. Fails with `AvroErr::SchemaParse` if a path
+	/// does not name a field of the writer's schema.
+	pub fn project(&mut self, paths: &[&str]) -> Result<(), AvroErr> {
+		self.projection = Some(Projection::from_paths(&self.schema, paths)?);
+		Ok(())
+	}
+
+	/// Reads the next data block as it is stored in the data file, checking the sync marker
+	/// that follows it. Returns `None` once the end of the data file is reached.
+	fn read_raw_block(&mut self) -> Result<Option<Block>, AvroErr> {
+		let offset = self.stream.position;
+		let mut first = [0u8; 1];
+		if self.stream.read(&mut first)? == 0 {
+			return Ok(None);
+		}
+		let (index, limits) = (self.next_block, self.limits);
+		let mut stream = (&first[..]).chain(&mut self.stream);
+		let count = read_block_long(&mut stream, offset)?;
+		let size = read_block_long(&mut stream, offset)?;
+		if count < 0 || size < 0 {
+			let err = AvroErr::decode(format!("Invalid block with {} records of {} bytes", count, size));
+			return Err(err.in_block(index, offset));
+		}
+		check_block_records(count as u64, limits.max_block_records).map_err(|e| e.in_block(index, offset))?;
+		check_block_size(size as u64, limits.max_block_size).map_err(|e| e.in_block(index, offset))?;
+		// The block size is not trusted for allocating the buffer up front, as it may be corrupt
+		let mut data = vec![];
+		(&mut stream).take(size as u64).read_to_end(&mut data)?;
+		let mut sync_marker = [0u8; SYNC_MARKER_SIZE];
+		let mut sync_len = 0;
+		while data.len() as i64 == size && sync_len < SYNC_MARKER_SIZE {
+			match stream.read(&mut sync_marker[sync_len..])? {
+				0 => break,
+				read => sync_len += read
+			}
+		}
+		if sync_len < SYNC_MARKER_SIZE {
+			self.truncated_block = Some(Block { count, data, codec: self.codec, offset });
+			return Err(AvroErr::TruncatedBlock(offset));
+		}
+		if sync_marker[..] != *self.header.sync_marker.as_bytes() {
+			error!("Possible data corruption! Sync markers do not match");
+			return Err(AvroErr::SyncMarkerMismatch(offset));
+		}
+		self.next_block = self.next_block.map(|block| block + 1);
+		Ok(Some(Block { count, data, codec: self.codec, offset }))
+	}
+
+	/// Reads and decompresses the next data block, returning false once the end of the data file is reached.
+	fn read_block(&mut self) -> Result<bool, AvroErr> {
+		let index = self.next_block;
+		let block = match self.read_raw_block() {
+			Ok(Some(block)) => block,
+			Ok(None) => return Ok(false),
+			Err(AvroErr::TruncatedBlock(offset)) => match self.truncated_block.take() {
+				Some(ref block) if self.read_partial_block && block.codec == Codec::Null => {
+					self.partial_block = Some(offset);
+					block.clone()
+				}
+				_ => return Err(AvroErr::TruncatedBlock(offset))
+			},
+			Err(e) => return Err(e)
+		};
+		let data = block.codec.decompress_limited(&block.data, self.limits.max_block_size)
+			.and_then(|data| check_block_data(&self.schema, block.count, data));
+		self.block = Cursor::new(data.map_err(|e| e.in_block(index, block.offset))?);
+		self.block_remaining = block.count;
+		self.block_zero_width_items = 0;
+		self.block_start = block.offset;
+		self.block_index = index;
+		Ok(true)
+	}
+
+	/// Converts this reader into an iterator over the data blocks of the data file, starting from
+	/// the next block. Records of the current block which were not read yet are skipped.
+	pub fn iter_block(self) -> BlockReader<R> {
+		BlockReader { reader: self }
+	}
+
+	/// Converts this reader into one which decompresses and decodes blocks on the given number
+	/// of worker threads, starting from the next block. Records are still yielded in the order
+	/// they are stored in the data file. Blocks are read ahead by up to twice the no of workers.
+	pub fn parallel(self, workers: usize) -> ParallelReader<R> {
+		let schema = self.schema.clone();
+		let projection = self.projection.clone();
+		let limits = self.limits;
+		let pool = OrderedPool::new(workers, move |(index, block): (Option<u64>, Block)| {
+			decode_block(&schema, projection.as_ref(), &limits, index, &block)
+		});
+		ParallelReader {
+			blocks: self.iter_block(),
+			pool,
+			read_ahead: 2 * workers.max(1) as u64,
+			records: vec![].into_iter(),
+			block_err: None,
+			finished: false
+		}
+	}
+
+	fn next_record(&mut self) -> Result<Option<Type>, AvroErr> {
+		while self.block_remaining == 0 {
+			if let Some(offset) = self.partial_block.take() {
+				return Err(AvroErr::TruncatedBlock(offset));
+			}
+			if !self.read_block()? {
+				return Ok(None);
+			}
+		}
+		match self.decode_record() {
+			// Records of a partial block are read until the first incomplete one
+			Err(_) if self.partial_block.is_some() => {
+				self.block_remaining = 0;
+				Err(AvroErr::TruncatedBlock(self.partial_block.take().unwrap_or(self.block_start)))
+			}
+			record => record.map(Some)
+		}
+	}
+
+	/// Decodes the next record out of the current block
+	fn decode_record(&mut self) -> Result<Type, AvroErr> {
+		let record = decode_datum(&self.schema, self.projection.as_ref(), &self.limits, &mut self.block_zero_width_items, &mut self.block)
+			.map_err(|e| e.in_block(self.block_index, self.block_start))?;
+		self.block_remaining -= 1;
+		Ok(record)
+	}
+}
+
+impl<R: Read + Seek> AvroReader<R> {
+	/// Converts this reader into one which skips over corrupt parts of the data file instead of
+	/// stopping at the first error. See `RecoveringReader` for details.
+	pub fn recover(self) -> RecoveringReader<R> {
+		RecoveringReader {
+			reader: self,
+			skipped: vec![],
+			good_bytes: 0,
+			good_records: 0
+		}
+	}
+
+	/// Moves the reader to the first block that starts at or after the given byte offset,
+	/// by scanning forward for the sync marker of the data file. Reading then resumes from
+	/// that block. If no block starts after the offset, the reader is moved to the end of
+	/// the data file.
+	///
+	/// This allows splitting a data file into byte ranges that are read independently: each
+	/// block is read by the range its starting offset (as given by `previous_sync`) falls in.
+	pub fn seek_to_sync(&mut self, offset: u64) -> Result<(), AvroErr> {
+		// Scanning starts a sync marker's length before the offset, so that a block
+		// starting exactly at the offset is not skipped.
+		let scan_start = if offset < self.data_start {
+			self.data_start - SYNC_MARKER_SIZE as u64
+		} else {
+			offset - SYNC_MARKER_SIZE as u64
+		};
+		self.stream.position = self.stream.inner.seek(SeekFrom::Start(scan_start))?;
+		self.block = Cursor::new(vec![]);
+		self.block_remaining = 0;
+		self.partial_block = None;
+		self.finished = false;
+
+		let marker = self.header.sync_marker.as_bytes();
+		let mut window = [0u8; SYNC_MARKER_SIZE];
+		let mut filled = 0;
+		let mut byte = [0u8; 1];
+		loop {
+			if self.stream.read(&mut byte)? == 0 {
+				self.block_start = self.stream.position;
+				self.next_block = None;
+				return Ok(());
+			}
+			if filled < SYNC_MARKER_SIZE {
+				window[filled] = byte[0];
+				filled += 1;
+			} else {
+				window.rotate_left(1);
+				window[SYNC_MARKER_SIZE - 1] = byte[0];
+			}
+			if filled == SYNC_MARKER_SIZE && window[..] == *marker {
+				self.block_start = self.stream.position;
+				// Blocks can only be counted from the start of the data
+				self.next_block = if self.block_start == self.data_start { Some(0) } else { None };
+				return Ok(());
+			}
+		}
+	}
+}
+
+impl<R: Read> Iterator for AvroReader<R> {
+	type Item = Result<Type, AvroErr>;
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.finished {
+			return None;
+		}
+		match self.next_record() {
+			Ok(Some(record)) => Some(Ok(record)),
+			Ok(None) => {
+				self.finished = true;
+				None
+			}
+			Err(e) => {
+				self.finished = true;
+				Some(Err(e))
+			}
+		}
+	}
+}
+
+/// Decodes a record with the projection if one is set, or else with the writer's schema. Items of
+/// arrays encoded in no bytes are added to `zero_width_items`, which spans the records of a block.
+fn decode_datum<R: Read>(schema: &CompiledSchema, projection: Option<&Projection>, limits: &DecoderLimits,
+                         zero_width_items: &mut u64, reader: &mut R) -> Result<Type, AvroErr> {
+	match projection {
+		Some(projection) => projection.decode_counted(limits, zero_width_items, reader),
+		None => Type::decode_limited(schema, schema.root(), limits, 0, zero_width_items, reader)
+	}
+}
+
+/// Checks that the decompressed data of a block can hold its records, given the fewest bytes a
+/// record of the schema is encoded in
+pub(crate) fn check_block_data(schema: &CompiledSchema, count: i64, data: Vec<u8>) -> Result<Vec<u8>, AvroErr> {
+	let min_size = (count as u64).saturating_mul(schema.min_size(schema.root()));
+	if min_size > data.len() as u64 {
+		return Err(AvroErr::decode(format!("Block of {} bytes is too small for its {} records", data.len(), count)));
+	}
+	Ok(data)
+}
+
+/// Decompresses a block and decodes all of its records
+fn decode_block(schema: &CompiledSchema, projection: Option<&Projection>, limits: &DecoderLimits, index: Option<u64>, block: &Block) -> Result<Vec<Type>, AvroErr> {
+	let data = block.codec.decompress_limited(&block.data, limits.max_block_size)
+		.and_then(|data| check_block_data(schema, block.count, data));
+	let mut data = Cursor::new(data.map_err(|e| e.in_block(index, block.offset))?);
+	let mut records = vec![];
+	let mut zero_width_items = 0;
+	for _ in 0..block.count {
+		records.push(decode_datum(schema, projection, limits, &mut zero_width_items, &mut data).map_err(|e| e.in_block(index, block.offset))?);
+	}
+	Ok(records)
+}
+
+/// An iterator over the records of a data file, which decompresses and decodes blocks on
+/// worker threads. Created with `AvroReader::parallel`.
+pub struct ParallelReader<R> {
+	blocks: BlockReader<R>,
+	/// Worker threads decoding the blocks read ahead
+	pool: OrderedPool<(Option<u64>, Block), Result<Vec<Type>, AvroErr>>,
+	/// Max no of blocks handed to the workers ahead of the records being yielded
+	read_ahead: u64,
+	/// Decoded records of the current block
+	records: vec::IntoIter<Type>,
+	/// The error reading blocks stopped at, yielded after the records of the blocks before it
+	block_err: Option<AvroErr>,
+	finished: bool
+}
+
+impl<R> ParallelReader<R> {
+	/// Retrieves a reference to the header of the data file
+	pub fn header(&self) -> &Header {
+		self.blocks.header()
+	}
+}
+
+impl<R: Read> Iterator for ParallelReader<R> {
+	type Item = Result<Type, AvroErr>;
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			if let Some(record) = self.records.next() {
+				return Some(Ok(record));
+			}
+			if self.finished {
+				return None;
+			}
+			while self.block_err.is_none() && self.pool.pending() < self.read_ahead {
+				let index = self.blocks.reader.next_block;
+				match self.blocks.next() {
+					Some(Ok(block)) => self.pool.submit((index, block)),
+					Some(Err(e)) => self.block_err = Some(e),
+					None => break
+				}
+			}
+			match self.pool.next_result(true) {
+				Some(Ok(records)) => self.records = records.into_iter(),
+				Some(Err(e)) => {
+					self.finished = true;
+					return Some(Err(e));
+				}
+				None => {
+					self.finished = true;
+					return self.block_err.take().map(Err);
+				}
+			}
+		}
+	}
+}
+
+/// A region of a data file which was skipped by a `RecoveringReader` because it could not be read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkippedRegion {
+	/// Byte offset in the data file where the skipped region starts
+	pub offset: u64,
+	/// Length of the skipped region in bytes
+	pub length: u64,
+	/// Estimated number of records lost in the skipped region. This is exact when a block was
+	/// intact apart from a record which could not be decoded. Otherwise it is extrapolated from
+	/// the average size of the records read so far.
+	pub estimated_records: u64,
+	/// Description of the error that caused the region to be skipped
+	pub reason: String
+}
+
+/// An iterator over the records of a data file, which recovers from corrupt data blocks.
+/// When a block can not be read, because of a mismatching sync marker, a bad checksum or
+/// undecodable data, the reader scans forward for the next sync marker and carries on reading
+/// from there. Every region skipped that way is logged and recorded, and can be retrieved
+/// with `skipped`. Created with `AvroReader::recover`.
+#[derive(Debug)]
+pub struct RecoveringReader<R> {
+	reader: AvroReader<R>,
+	skipped: Vec<SkippedRegion>,
+	/// Size of the blocks read successfully, used for estimating the records lost in skipped regions
+	good_bytes: u64,
+	/// No of records in the blocks read successfully
+	good_records: u64
+}
+
+impl<R> RecoveringReader<R> {
+	/// Retrieves a reference to the header of the data file
+	pub fn header(&self) -> &Header {
+		&self.reader.header
+	}
+
+	/// Returns the regions of the data file that were skipped so far
+	pub fn skipped(&self) -> &[SkippedRegion] {
+		&self.skipped
+	}
+
+	fn skip(&mut self, offset: u64, length: u64, estimated_records: u64, err: AvroErr) {
+		let region = SkippedRegion { offset, length, estimated_records, reason: err.to_string() };
+		warn!("Skipping corrupt data in avro data file: {:?}", region);
+		self.skipped.push(region);
+	}
+}
+
+impl<R: Read + Seek> Iterator for RecoveringReader<R> {
+	type Item = Type;
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			if self.reader.finished {
+				return None;
+			}
+			if self.reader.block_remaining > 0 {
+				match self.reader.decode_record() {
+					Ok(record) => return Some(record),
+					Err(e) => {
+						// The rest of the block can not be trusted once a record fails to decode
+						let (offset, end) = (self.reader.block_start, self.reader.tell());
+						let lost = self.reader.block_remaining as u64;
+						self.reader.block_remaining = 0;
+						self.skip(offset, end - offset, lost, e);
+						continue;
+					}
+				}
+			}
+			let offset = self.reader.tell();
+			match self.reader.read_block() {
+				Ok(true) => {
+					self.good_bytes += self.reader.tell() - offset;
+					self.good_records += self.reader.block_remaining as u64;
+				}
+				Ok(false) => {
+					self.reader.finished = true;
+					return None;
+				}
+				Err(e) => {
+					if let Err(seek_err) = self.reader.seek_to_sync(offset + 1) {
+						error!("Could not resynchronize after corrupt block at {}: {}", offset, seek_err);
+						self.reader.finished = true;
+						return None;
+					}
+					let length = self.reader.previous_sync() - offset;
+					let estimated_records = length.saturating_mul(self.good_records).checked_div(self.good_bytes).unwrap_or(0);
+					self.skip(offset, length, estimated_records, e);
+				}
+			}
+		}
+	}
+}
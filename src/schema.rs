@@ -3,13 +3,13 @@
 use std::collections::HashMap;
 use std::fs::OpenOptions;
 use std::path::Path;
-use serde_json::{self, Value, from_reader};
+use serde_json::{self, Map, Value, from_reader};
 use types::Type;
 use std::str;
 use writer::SchemaTag;
-use errors::SchemaParseErr;
-use failure::Error;
-use std::fmt::Debug;
+use errors::AvroErr;
+use std::fmt::{self, Debug};
+use std::convert::TryFrom;
 
 lazy_static! {
 	static ref PRIMITIVE: &'static [&'static str] = &["null", "boolean", "int", "long", "float", "double", "bytes", "string"];
@@ -27,49 +27,439 @@ pub enum AvroSchema {
 
 impl AvroSchema {
 	/// Parse an avro schema from a string
-	pub fn from_str(schema: &str) -> Result<Self, Error> {
-		let json_schema: Value = serde_json::from_str(schema).map_err(|e|{
+	pub fn from_str(schema: &str) -> Result<Self, AvroErr> {
+		let json_schema: Value = serde_json::from_str(schema).map_err(|e| {
 			debug!("Avro schema parse error: {:?}", e);
-			SchemaParseErr::InvalidSchema
+			e
 		})?;
 		map_from_json(json_schema)
 	}
 
 	/// Parse an avro schema from a file path
-	pub fn from_file<P: AsRef<Path> + Debug>(path: P) -> Result<Self, Error> {
+	pub fn from_file<P: AsRef<Path> + Debug>(path: P) -> Result<Self, AvroErr> {
 		let schema_file = OpenOptions::new().read(true).open(&path).map_err(|e| {
-			debug!("Schema file {:?} not found: {}", path, e);
-			SchemaParseErr::NotFound
+			debug!("Schema file {:?} could not be opened: {}", path, e);
+			e
 		})?;
-		let json_schema: Value = from_reader(schema_file).unwrap();
+		let json_schema: Value = from_reader(schema_file)?;
 		map_from_json(json_schema)
 	}
 
-	/// If the schema is a record then this method gives back the fields in the order
-	/// they are declared.
+	/// If the schema is a record then this method gives back the names and types of the fields
+	/// in the order they are declared. Types other than type names are given as their json declaration.
 	pub fn record_field_pairs(&self) -> Option<Vec<(String, String)>> {
 		match *self {
 			AvroSchema::Primitive(_) => None,
-			AvroSchema::Complex(ref schema) => if schema.is_object() {
-				let fields_vec = schema["fields"].as_array().unwrap();
+			AvroSchema::Complex(ref schema) => {
+				let fields_vec = schema.get("fields")?.as_array()?;
 				let mut fields = vec![];
 				for obj in fields_vec.iter() {
-					// TODO currently on primitive types as fields are supported
-					fields.push(
-						(obj["name"].as_str().unwrap().to_string(),
-						obj["type"].as_str().expect("Support for complex types as fields\
-						is not yet implemented").to_string())
-					);
+					let ty = match obj.get("type")? {
+						Value::String(ref ty) => ty.to_string(),
+						other => other.to_string()
+					};
+					fields.push((obj.get("name")?.as_str()?.to_string(), ty));
 				}
 				Some(fields)
-			} else {
-				None
 			}
 		}
 	}
+
+	/// Resolves the schema into a `CompiledSchema`
+	pub fn compile(&self) -> Result<CompiledSchema, AvroErr> {
+		match *self {
+			AvroSchema::Primitive(ref v) | AvroSchema::Complex(ref v) => CompiledSchema::new(v)
+		}
+	}
+}
+
+/// Qualifies the name of a named type with the enclosing namespace, unless it is a full name already
+pub(crate) fn fullname(name: &str, namespace: Option<&str>) -> String {
+	match namespace {
+		Some(ns) if !name.contains('.') && !ns.is_empty() => format!("{}.{}", ns, name),
+		_ => name.to_string()
+	}
+}
+
+/// A node of a `CompiledSchema`. Nodes refer to the nodes of their child types by index.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaNode {
+	/// Null schema
+	Null,
+	/// Boolean schema
+	Boolean,
+	/// Int schema
+	Int,
+	/// Long schema
+	Long,
+	/// Float schema
+	Float,
+	/// Double schema
+	Double,
+	/// Bytes schema
+	Bytes,
+	/// String schema
+	String,
+	/// Record schema with its fields in the order they are declared
+	Record {
+		/// Name of the record
+		name: String,
+		/// Fields of the record
+		fields: Vec<FieldNode>
+	},
+	/// Enum schema
+	Enum {
+		/// Name of the enum
+		name: String,
+		/// Symbols of the enum
+		symbols: Vec<String>
+	},
+	/// Array schema, with the node of its items
+	Array(usize),
+	/// Map schema, with the node of its values
+	Map(usize),
+	/// Union schema, with the nodes of its branches
+	Union(Vec<usize>),
+	/// Fixed schema
+	Fixed {
+		/// Name of the fixed type
+		name: String,
+		/// Size in bytes of the fixed values
+		size: usize
+	}
+}
+
+impl fmt::Display for SchemaNode {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			SchemaNode::Null => write!(f, "null"),
+			SchemaNode::Boolean => write!(f, "boolean"),
+			SchemaNode::Int => write!(f, "int"),
+			SchemaNode::Long => write!(f, "long"),
+			SchemaNode::Float => write!(f, "float"),
+			SchemaNode::Double => write!(f, "double"),
+			SchemaNode::Bytes => write!(f, "bytes"),
+			SchemaNode::String => write!(f, "string"),
+			SchemaNode::Record { ref name, ref fields } => write!(f, "record {} with {} fields", name, fields.len()),
+			SchemaNode::Enum { ref name, .. } => write!(f, "enum {}", name),
+			SchemaNode::Array(_) => write!(f, "array"),
+			SchemaNode::Map(_) => write!(f, "map"),
+			SchemaNode::Union(ref branches) => write!(f, "union of {} branches", branches.len()),
+			SchemaNode::Fixed { ref name, size } => write!(f, "fixed {} of {} bytes", name, size)
+		}
+	}
+}
+
+/// A field of a record node
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldNode {
+	/// Name of the field
+	pub name: String,
+	/// Index of the node of the field's type
+	pub node: usize,
+	/// How the field takes part in sorting records
+	pub order: Order
+}
+
+/// The sort order of a record field, given by its `order` attribute
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Order {
+	/// Values of the field are sorted in ascending order, the default
+	Ascending,
+	/// Values of the field are sorted in descending order
+	Descending,
+	/// The field is ignored when sorting
+	Ignore
+}
+
+/// A logical type annotating a node, as given by the `logicalType` attribute of its schema
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogicalType {
+	/// An arbitrary precision decimal, stored as the unscaled value in bytes or fixed
+	Decimal {
+		/// Maximum no of digits of the unscaled value
+		precision: usize,
+		/// No of digits after the decimal point
+		scale: usize
+	},
+	/// A uuid stored as a string
+	Uuid,
+	/// Days since the unix epoch, stored as an int
+	Date,
+	/// Milliseconds after midnight, stored as an int
+	TimeMillis,
+	/// Microseconds after midnight, stored as a long
+	TimeMicros,
+	/// Milliseconds since the unix epoch, stored as a long
+	TimestampMillis,
+	/// Microseconds since the unix epoch, stored as a long
+	TimestampMicros,
+	/// Milliseconds since the unix epoch in the local timezone, stored as a long
+	LocalTimestampMillis,
+	/// Microseconds since the unix epoch in the local timezone, stored as a long
+	LocalTimestampMicros,
+	/// Months, days and milliseconds, stored as a fixed of 12 bytes
+	Duration
+}
+
+/// A schema with its named types resolved, laid out as a flat list of nodes. References to a
+/// named type point to the node of its definition, so recursive types need no special handling.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledSchema {
+	nodes: Vec<SchemaNode>,
+	root: usize,
+	/// Full names of the nodes of named types, qualified with their namespace
+	fullnames: HashMap<usize, String>,
+	/// Logical types of the nodes annotated with a valid one
+	logical_types: HashMap<usize, LogicalType>,
+	/// Fewest bytes the values of each node are encoded in
+	min_sizes: Vec<u64>
+}
+
+impl CompiledSchema {
+	/// Compiles the json representation of a schema
+	pub fn new(schema: &Value) -> Result<Self, AvroErr> {
+		let mut compiler = SchemaCompiler { nodes: vec![], names: HashMap::new(), fullnames: HashMap::new(), logical_types: HashMap::new() };
+		let root = compiler.compile(schema, None)?;
+		let min_sizes = min_sizes(&compiler.nodes);
+		Ok(CompiledSchema { nodes: compiler.nodes, root, fullnames: compiler.fullnames, logical_types: compiler.logical_types, min_sizes })
+	}
+
+	/// Returns the index of the node of the top level type
+	pub fn root(&self) -> usize {
+		self.root
+	}
+
+	/// Returns the node at the given index
+	pub fn node(&self, idx: usize) -> &SchemaNode {
+		&self.nodes[idx]
+	}
+
+	/// Returns all nodes of the schema
+	pub fn nodes(&self) -> &[SchemaNode] {
+		&self.nodes
+	}
+
+	/// Returns the full name of the node at the given index, if it is a named type
+	pub fn fullname(&self, idx: usize) -> Option<&str> {
+		self.fullnames.get(&idx).map(|name| name.as_str())
+	}
+
+	/// Returns the logical type of the node at the given index. Unknown logical types, and ones
+	/// invalid for the type they annotate, are ignored as the spec asks.
+	pub fn logical_type(&self, idx: usize) -> Option<LogicalType> {
+		self.logical_types.get(&idx).cloned()
+	}
+
+	/// Returns the fewest bytes a value of the node at the given index is encoded in. Nulls, and
+	/// records holding nothing else, are encoded in no bytes at all.
+	pub fn min_size(&self, idx: usize) -> u64 {
+		self.min_sizes[idx]
+	}
+}
+
+/// Finds the fewest bytes the values of each node are encoded in. Sizes start out at zero and grow
+/// as the nodes referring to each other are revisited, so that they never exceed the real minimum.
+fn min_sizes(nodes: &[SchemaNode]) -> Vec<u64> {
+	let mut sizes = vec![0u64; nodes.len()];
+	// Sizes of recursive records without finite values would grow forever
+	for _ in 0..=nodes.len() {
+		let mut changed = false;
+		for (idx, node) in nodes.iter().enumerate() {
+			let size = match *node {
+				SchemaNode::Null => 0,
+				SchemaNode::Boolean | SchemaNode::Int | SchemaNode::Long | SchemaNode::Bytes | SchemaNode::String |
+				SchemaNode::Enum { .. } | SchemaNode::Array(_) | SchemaNode::Map(_) => 1,
+				SchemaNode::Float => 4,
+				SchemaNode::Double => 8,
+				SchemaNode::Record { ref fields, .. } => fields.iter().fold(0u64, |size, field| size.saturating_add(sizes[field.node])),
+				SchemaNode::Union(ref branches) => 1 + branches.iter().map(|&branch| sizes[branch]).min().unwrap_or(0),
+				SchemaNode::Fixed { size, .. } => size as u64
+			};
+			if size != sizes[idx] {
+				sizes[idx] = size;
+				changed = true;
+			}
+		}
+		if !changed {
+			break;
+		}
+	}
+	sizes
 }
 
-fn map_from_json(json_schema: Value) -> Result<AvroSchema, Error> {
+/// Builds the nodes of a `CompiledSchema`, keeping track of the named types defined so far
+struct SchemaCompiler {
+	nodes: Vec<SchemaNode>,
+	names: HashMap<String, usize>,
+	fullnames: HashMap<usize, String>,
+	logical_types: HashMap<usize, LogicalType>
+}
+
+impl SchemaCompiler {
+	fn push(&mut self, node: SchemaNode) -> usize {
+		self.nodes.push(node);
+		self.nodes.len() - 1
+	}
+
+	fn define(&mut self, name: &str, namespace: Option<&str>, idx: usize) {
+		let full = fullname(name, namespace);
+		let short = full.rsplit('.').next().unwrap_or(name).to_string();
+		self.names.entry(short).or_insert(idx);
+		self.names.insert(full.clone(), idx);
+		self.fullnames.insert(idx, full);
+	}
+
+	fn compile(&mut self, schema: &Value, namespace: Option<&str>) -> Result<usize, AvroErr> {
+		match *schema {
+			Value::String(ref name) => {
+				let node = match name.as_str() {
+					"null" => SchemaNode::Null,
+					"boolean" => SchemaNode::Boolean,
+					"int" => SchemaNode::Int,
+					"long" => SchemaNode::Long,
+					"float" => SchemaNode::Float,
+					"double" => SchemaNode::Double,
+					"bytes" => SchemaNode::Bytes,
+					"string" => SchemaNode::String,
+					other => {
+						return self.names.get(&fullname(other, namespace)).or_else(|| self.names.get(other))
+							.cloned().ok_or_else(|| AvroErr::schema(format!("Unknown type {}", other)));
+					}
+				};
+				Ok(self.push(node))
+			}
+			Value::Array(ref branches) => {
+				let idx = self.push(SchemaNode::Union(vec![]));
+				let mut nodes = Vec::with_capacity(branches.len());
+				for (i, branch) in branches.iter().enumerate() {
+					nodes.push(self.compile(branch, namespace).map_err(|e| e.within(&format!("[{}]", i)))?);
+				}
+				self.nodes[idx] = SchemaNode::Union(nodes);
+				Ok(idx)
+			}
+			Value::Object(ref obj) => {
+				let namespace = obj.get("namespace").and_then(|n| n.as_str()).or(namespace);
+				let ty = obj.get("type").ok_or_else(|| missing_attribute("type"))?;
+				let name = obj.get("name").and_then(|n| n.as_str());
+				let idx = match ty.as_str() {
+					Some("record") | Some("error") => {
+						let name = name.ok_or_else(|| missing_attribute("name"))?;
+						let field_schemas = obj.get("fields").and_then(|f| f.as_array()).ok_or_else(|| missing_attribute("fields"))?;
+						// The record is defined before its fields are compiled, so that they can refer to it
+						let idx = self.push(SchemaNode::Record { name: name.to_string(), fields: vec![] });
+						self.define(name, namespace, idx);
+						let mut fields = Vec::with_capacity(field_schemas.len());
+						for (i, field) in field_schemas.iter().enumerate() {
+							let field_name = field.get("name").and_then(|n| n.as_str())
+								.ok_or_else(|| missing_attribute("name").within(&format!(".fields[{}]", i)))?;
+							let field_ty = field.get("type").ok_or_else(|| missing_attribute("type"))
+								.and_then(|ty| self.compile(ty, namespace))
+								.map_err(|e| e.within(&format!(".{}", field_name)))?;
+							let order = match field.get("order") {
+								None => Order::Ascending,
+								Some(order) => match order.as_str() {
+									Some("ascending") => Order::Ascending,
+									Some("descending") => Order::Descending,
+									Some("ignore") => Order::Ignore,
+									_ => return Err(AvroErr::schema(format!("Invalid order {}", order)).within(&format!(".{}", field_name)))
+								}
+							};
+							fields.push(FieldNode { name: field_name.to_string(), node: field_ty, order });
+						}
+						self.nodes[idx] = SchemaNode::Record { name: name.to_string(), fields };
+						Ok(idx)
+					}
+					Some("enum") => {
+						let name = name.ok_or_else(|| missing_attribute("name"))?;
+						let symbols = obj.get("symbols").and_then(|s| s.as_array()).ok_or_else(|| missing_attribute("symbols"))?
+							.iter().map(|s| s.as_str().map(|s| s.to_string())
+								.ok_or_else(|| AvroErr::schema(format!("Enum symbol {} is not a string", s))))
+							.collect::<Result<Vec<_>, _>>()?;
+						let idx = self.push(SchemaNode::Enum { name: name.to_string(), symbols });
+						self.define(name, namespace, idx);
+						Ok(idx)
+					}
+					Some("fixed") => {
+						let name = name.ok_or_else(|| missing_attribute("name"))?;
+						let size = obj.get("size").and_then(|s| s.as_u64()).ok_or_else(|| missing_attribute("size"))?;
+						let idx = self.push(SchemaNode::Fixed { name: name.to_string(), size: size as usize });
+						self.define(name, namespace, idx);
+						Ok(idx)
+					}
+					Some("array") => {
+						let idx = self.push(SchemaNode::Array(0));
+						let items = obj.get("items").ok_or_else(|| missing_attribute("items"))
+							.and_then(|items| self.compile(items, namespace))
+							.map_err(|e| e.within("[*]"))?;
+						self.nodes[idx] = SchemaNode::Array(items);
+						Ok(idx)
+					}
+					Some("map") => {
+						let idx = self.push(SchemaNode::Map(0));
+						let values = obj.get("values").ok_or_else(|| missing_attribute("values"))
+							.and_then(|values| self.compile(values, namespace))
+							.map_err(|e| e.within("[*]"))?;
+						self.nodes[idx] = SchemaNode::Map(values);
+						Ok(idx)
+					}
+					// Either a primitive type written as `{"type": "int"}`, possibly carrying a logical type,
+					// or a reference to a named type
+					_ => self.compile(ty, namespace)
+				}?;
+				// Logical types on references to named types are ignored, as they would annotate the definition
+				let defines_node = ty.as_str().is_some_and(|ty| ty == "fixed" || PRIMITIVE.contains(&ty));
+				if let Some(logical_type) = logical_type(obj, &self.nodes[idx]).filter(|_| defines_node) {
+					self.logical_types.insert(idx, logical_type);
+				}
+				Ok(idx)
+			}
+			ref other => Err(AvroErr::schema(format!("Expected a type name, union or object, found {}", other)))
+		}
+	}
+}
+
+/// Gives the logical type of a schema object, if it is known and valid for the node compiled from it
+fn logical_type(obj: &Map<String, Value>, node: &SchemaNode) -> Option<LogicalType> {
+	let logical_type = match (obj.get("logicalType").and_then(|l| l.as_str())?, node) {
+		("decimal", &SchemaNode::Bytes) | ("decimal", &SchemaNode::Fixed { .. }) => {
+			let precision = obj.get("precision").and_then(|p| p.as_u64())? as usize;
+			let scale = match obj.get("scale") {
+				Some(scale) => scale.as_u64()? as usize,
+				None => 0
+			};
+			if precision == 0 || scale > precision {
+				return None;
+			}
+			if let SchemaNode::Fixed { size, .. } = *node {
+				// The digits a signed value of the given no of bytes can hold
+				let max_precision = ((8 * size as i64 - 1) as f64 * 2f64.log10()).floor();
+				if precision as f64 > max_precision {
+					return None;
+				}
+			}
+			LogicalType::Decimal { precision, scale }
+		}
+		("uuid", &SchemaNode::String) => LogicalType::Uuid,
+		("date", &SchemaNode::Int) => LogicalType::Date,
+		("time-millis", &SchemaNode::Int) => LogicalType::TimeMillis,
+		("time-micros", &SchemaNode::Long) => LogicalType::TimeMicros,
+		("timestamp-millis", &SchemaNode::Long) => LogicalType::TimestampMillis,
+		("timestamp-micros", &SchemaNode::Long) => LogicalType::TimestampMicros,
+		("local-timestamp-millis", &SchemaNode::Long) => LogicalType::LocalTimestampMillis,
+		("local-timestamp-micros", &SchemaNode::Long) => LogicalType::LocalTimestampMicros,
+		("duration", &SchemaNode::Fixed { size: 12, .. }) => LogicalType::Duration,
+		_ => return None
+	};
+	Some(logical_type)
+}
+
+/// Error for a schema object lacking a required attribute
+fn missing_attribute(attribute: &str) -> AvroErr {
+	AvroErr::schema(format!("Missing attribute \"{}\"", attribute))
+}
+
+fn map_from_json(json_schema: Value) -> Result<AvroSchema, AvroErr> {
 	if json_schema.is_string() {
 		Ok(AvroSchema::Primitive(json_schema))
 	} else if json_schema.is_array() {
@@ -77,53 +467,55 @@ fn map_from_json(json_schema: Value) -> Result<AvroSchema, Error> {
 	} else if json_schema.is_object() {
 		Ok(AvroSchema::Complex(json_schema))
 	} else {
-		bail!(SchemaParseErr::InvalidSchema);
+		Err(AvroErr::schema(format!("Expected a json string, array or object, found {}", json_schema)))
 	}
 }
 
-fn parse_schema_tag(schema_str: &str) -> SchemaTag {
+pub(crate) fn parse_schema_tag(schema_str: &str) -> Result<SchemaTag, AvroErr> {
 	match schema_str {
-		"null" => SchemaTag::Null,
-		"boolean" => SchemaTag::Boolean,
-		"int" => SchemaTag::Int,
-		"long" => SchemaTag::Long,
-		"float" => SchemaTag::Float,
-		"double" => SchemaTag::Double,
-		"bytes" => SchemaTag::Bytes,
-		"string" => SchemaTag::String,
-		"record" => SchemaTag::Record,
-		"enum" => SchemaTag::Enum,
-		"array" => SchemaTag::Array,
-		"map" => SchemaTag::Map,
-		"union" => SchemaTag::Union,
-		"fixed" => SchemaTag::Fixed,
-		_ => panic!("Unknown avro schema")
+		"null" => Ok(SchemaTag::Null),
+		"boolean" => Ok(SchemaTag::Boolean),
+		"int" => Ok(SchemaTag::Int),
+		"long" => Ok(SchemaTag::Long),
+		"float" => Ok(SchemaTag::Float),
+		"double" => Ok(SchemaTag::Double),
+		"bytes" => Ok(SchemaTag::Bytes),
+		"string" => Ok(SchemaTag::String),
+		"record" => Ok(SchemaTag::Record),
+		"enum" => Ok(SchemaTag::Enum),
+		"array" => Ok(SchemaTag::Array),
+		"map" => Ok(SchemaTag::Map),
+		"union" => Ok(SchemaTag::Union),
+		"fixed" => Ok(SchemaTag::Fixed),
+		other => Err(AvroErr::schema(format!("Unknown avro schema {}", other)))
 	}
 }
 
 // Converts a serde json avro schema to SchemaTag. SchemaTag is mainly used by data writer instances
 // to type check the data being written into the datafile.
-impl Into<SchemaTag> for AvroSchema {
-	fn into(self) -> SchemaTag {
-		match self {
+impl TryFrom<AvroSchema> for SchemaTag {
+	type Error = AvroErr;
+	fn try_from(schema: AvroSchema) -> Result<Self, AvroErr> {
+		match schema {
 			AvroSchema::Primitive(v) => {
-				let v_str = v.as_str().unwrap();
-				if PRIMITIVE.contains(&v_str) {
-					return parse_schema_tag(v.as_str().unwrap())
-				} else {
-					panic!("Json strings can only represent primitive avro formats");
+				match v.as_str() {
+					Some(v_str) if PRIMITIVE.contains(&v_str) => parse_schema_tag(v_str),
+					_ => Err(AvroErr::schema("Json strings can only represent primitive avro formats"))
 				}
 			}
 			AvroSchema::Complex(obj) => {
+				if obj.is_array() {
+					return Ok(SchemaTag::Union)
+				}
 				if let Some(&Value::String(ref s)) = obj.get("type") {
-					return parse_schema_tag(s)
+					parse_schema_tag(s)
 				} else {
-					panic!("Could not find type attribute in complex avro schema");
+					Err(AvroErr::schema("Could not find type attribute in complex avro schema"))
 				}
 			}
 		}
 	}
-} 
+}
 
 impl From<Type> for String {
 	fn from(schema: Type) -> Self {
@@ -0,0 +1,241 @@
+//! This module declares a `SliceReader` which reads an avro data file held in memory, such as
+//! a memory mapped file, decoding records into `TypeRef` values that borrow from it instead of
+//! copying strings and bytes out of it.
+
+#[cfg(feature = "memmap")]
+use std::fs::File;
+#[cfg(feature = "memmap")]
+use std::path::Path;
+
+#[cfg(feature = "memmap")]
+use memmap::Mmap;
+
+use codec::{Decoder, DecoderLimits};
+use errors::AvroErr;
+use schema::CompiledSchema;
+use types::TypeRef;
+use writer::{split_block, Codec, Header, SYNC_MARKER_SIZE};
+
+/// Memory maps an avro data file, so that it can be read with a `SliceReader`.
+/// The file must not be modified while it is mapped.
+#[cfg(feature = "memmap")]
+pub fn map_file<P: AsRef<Path>>(path: P) -> Result<Mmap, AvroErr> {
+	let datafile = File::open(path)?;
+	let mmap = unsafe { Mmap::map(&datafile)? };
+	Ok(mmap)
+}
+
+/// A data block of an avro data file, borrowed from the buffer holding the data file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SliceBlock<'a> {
+	/// No of records in the block
+	pub count: i64,
+	/// The serialized records of the block, compressed with the codec of the data file
+	pub data: &'a [u8],
+	/// The codec the block data is compressed with
+	pub codec: Codec,
+	/// Byte offset of the block in the data file
+	pub offset: u64
+}
+
+impl<'a> SliceBlock<'a> {
+	/// Decompresses the block data, giving back the serialized records of the block
+	pub fn decompress(&self) -> Result<Vec<u8>, AvroErr> {
+		self.codec.decompress(self.data)
+	}
+}
+
+/// An iterator over the data blocks of a data file held in memory. Created with `SliceReader::blocks`.
+#[derive(Debug, Clone)]
+pub struct SliceBlocks<'a> {
+	data: &'a [u8],
+	position: usize,
+	sync_marker: &'a [u8],
+	codec: Codec,
+	limits: DecoderLimits,
+	finished: bool
+}
+
+impl<'a> SliceBlocks<'a> {
+	fn read_block(&mut self) -> Result<SliceBlock<'a>, AvroErr> {
+		let offset = self.position as u64;
+		let (count, data, len) = split_block(&self.data[self.position..], self.sync_marker, offset, &self.limits)?;
+		self.position += len;
+		Ok(SliceBlock { count, data, codec: self.codec, offset })
+	}
+}
+
+impl<'a> Iterator for SliceBlocks<'a> {
+	type Item = Result<SliceBlock<'a>, AvroErr>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.finished || self.position == self.data.len() {
+			return None;
+		}
+		let block = self.read_block();
+		self.finished = block.is_err();
+		Some(block)
+	}
+}
+
+/// An iterator over records decoded as `TypeRef` values. Created with `SliceReader::records`
+/// or `SliceReader::block_records`.
+#[derive(Debug, Clone)]
+pub struct SliceRecords<'a> {
+	schema: &'a CompiledSchema,
+	limits: DecoderLimits,
+	blocks: Option<SliceBlocks<'a>>,
+	block: &'a [u8],
+	block_remaining: i64,
+	/// No of items of arrays encoded in no bytes decoded out of the current block
+	block_zero_width_items: u64,
+	/// No of blocks read from `blocks`
+	blocks_read: u64,
+	/// Byte offset of the current block, when it was read from `blocks`
+	block_offset: Option<u64>,
+	finished: bool
+}
+
+impl<'a> Iterator for SliceRecords<'a> {
+	type Item = Result<TypeRef<'a>, AvroErr>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.finished {
+			return None;
+		}
+		while self.block_remaining == 0 {
+			match self.blocks.as_mut().and_then(|blocks| blocks.next()) {
+				Some(Ok(block)) => {
+					self.block = block.data;
+					self.block_remaining = block.count;
+					self.block_zero_width_items = 0;
+					self.block_offset = Some(block.offset);
+					self.blocks_read += 1;
+				}
+				Some(Err(e)) => {
+					self.finished = true;
+					return Some(Err(e));
+				}
+				None => {
+					self.finished = true;
+					return None;
+				}
+			}
+		}
+		self.block_remaining -= 1;
+		let record = TypeRef::decode_limited(self.schema, self.schema.root(), &self.limits, 0, &mut self.block_zero_width_items, &mut self.block);
+		let record = match self.block_offset {
+			Some(offset) => record.map_err(|e| e.in_block(Some(self.blocks_read - 1), offset)),
+			None => record
+		};
+		self.finished = record.is_err();
+		Some(record)
+	}
+}
+
+/// `SliceReader` reads an avro data file held in a byte slice. Records decoded by it borrow
+/// their strings and bytes from the slice, so reading `null` codec data files does not
+/// allocate for them. Records of compressed data files can be decoded block by block with
+/// `block_records`, borrowing from the decompressed block data instead.
+#[derive(Debug)]
+pub struct SliceReader<'a> {
+	/// The header of the data file being read
+	header: Header,
+	/// The codec the data blocks are compressed with
+	codec: Codec,
+	/// The schema the data file was written with
+	schema: CompiledSchema,
+	/// The whole data file
+	data: &'a [u8],
+	/// Byte offset in the data file where the data blocks start
+	data_start: usize,
+	/// Limits on the blocks and records decoded
+	limits: DecoderLimits
+}
+
+impl<'a> SliceReader<'a> {
+	/// Creates a reader over the avro data file held in the given buffer. The data file header is read right away.
+	pub fn new(data: &'a [u8]) -> Result<Self, AvroErr> {
+		let mut buf = data;
+		let header = Header::decode(&mut buf)?;
+		let codec = header.get_codec()?;
+		let schema = header.schema.compile()?;
+		let data_start = data.len() - buf.len();
+		Ok(SliceReader {
+			header,
+			codec,
+			schema,
+			data,
+			data_start,
+			limits: DecoderLimits::default()
+		})
+	}
+
+	/// Retrieves a reference to the header of the data file
+	pub fn header(&self) -> &Header {
+		&self.header
+	}
+
+	/// Retrieves the codec the data file was written with
+	pub fn get_codec(&self) -> Codec {
+		self.codec
+	}
+
+	/// Sets the limits on the blocks and records read, which guard against corrupt or malicious data files.
+	/// Data exceeding them fails with `AvroErr::LimitExceeded`. `DecoderLimits::default()` applies otherwise.
+	pub fn set_limits(&mut self, limits: DecoderLimits) {
+		self.limits = limits;
+	}
+
+	/// Retrieves the compiled schema the records are decoded with
+	pub fn compiled_schema(&self) -> &CompiledSchema {
+		&self.schema
+	}
+
+	/// Returns an iterator over the data blocks of the data file
+	pub fn blocks(&self) -> SliceBlocks<'a> {
+		SliceBlocks {
+			data: self.data,
+			position: self.data_start,
+			// The sync marker is the last part of the header
+			sync_marker: &self.data[self.data_start - SYNC_MARKER_SIZE..self.data_start],
+			codec: self.codec,
+			limits: self.limits,
+			finished: false
+		}
+	}
+
+	/// Returns an iterator over all records of the data file. As the records borrow from the
+	/// data file, this fails with `AvroErr::Codec` for compressed data files.
+	pub fn records(&self) -> Result<SliceRecords<'_>, AvroErr> {
+		if self.codec != Codec::Null {
+			return Err(AvroErr::codec(self.codec, "Records can only be borrowed from data files without compression"));
+		}
+		Ok(SliceRecords {
+			schema: &self.schema,
+			limits: self.limits,
+			blocks: Some(self.blocks()),
+			block: &[],
+			block_remaining: 0,
+			block_zero_width_items: 0,
+			blocks_read: 0,
+			block_offset: None,
+			finished: false
+		})
+	}
+
+	/// Returns an iterator over the `count` records serialized in the given decompressed block data
+	pub fn block_records<'b>(&'b self, data: &'b [u8], count: i64) -> SliceRecords<'b> {
+		SliceRecords {
+			schema: &self.schema,
+			limits: self.limits,
+			blocks: None,
+			block: data,
+			block_remaining: count.max(0),
+			block_zero_width_items: 0,
+			blocks_read: 0,
+			block_offset: None,
+			finished: false
+		}
+	}
+}
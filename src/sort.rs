@@ -0,0 +1,369 @@
+//! This module declares a `Sorter`, which sorts the records of avro data files too large to be held
+//! in memory. Records are read in runs of bounded size, which are sorted and spilled to temporary
+//! data files, and the runs are then merged into the sorted data file. Records are compared in
+//! their encoded form, either in the sort order of the schema or by a list of key fields.
+
+use std::cmp::Ordering;
+use std::env;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::mem;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::vec;
+
+use rand::{thread_rng, Rng};
+
+use compare::{compare, compare_node};
+use errors::AvroErr;
+use reader::{AvroReader, Block, BlockReader};
+use schema::{AvroSchema, CompiledSchema, SchemaNode};
+use types::Type;
+use writer::{AvroWriter, Codec};
+
+/// No of records written to sorted data files between flushes, matching the records of a block
+const FLUSH_RECORDS: u64 = 4096;
+
+/// Estimated memory held for each buffered record, besides its encoded bytes
+const RECORD_OVERHEAD: usize = 64;
+
+/// A field that records are sorted by
+#[derive(Debug)]
+struct KeyField {
+	/// Path of the field, as given to `Sorter::set_keys`
+	name: String,
+	/// Positions of the field and its enclosing fields in their records, from the outermost record
+	path: Vec<usize>,
+	/// Index of the node of the field's type
+	node: usize
+}
+
+/// An encoded record, along with the positions of its key fields in the encoding
+#[derive(Debug)]
+struct Entry {
+	data: Vec<u8>,
+	keys: Vec<Range<usize>>
+}
+
+/// How records are compared
+#[derive(Debug)]
+struct SortOrder {
+	schema: CompiledSchema,
+	/// The key fields records are compared by, in turn. Empty to compare whole records in the
+	/// sort order of the schema.
+	keys: Vec<KeyField>
+}
+
+impl SortOrder {
+	/// Resolves the paths of the key fields in the schema, with nested fields named after their
+	/// enclosing fields as in `inner_rec.id`
+	fn new(schema: CompiledSchema, keys: &[String]) -> Result<Self, AvroErr> {
+		let mut key_fields = vec![];
+		for key in keys {
+			let mut node = schema.root();
+			let mut path = vec![];
+			for name in key.split('.') {
+				let fields = match *schema.node(node) {
+					SchemaNode::Record { ref fields, .. } => fields,
+					_ => return Err(AvroErr::schema(format!("Field {} is not in the schema", key)))
+				};
+				let position = fields.iter().position(|field| field.name == name)
+					.ok_or_else(|| AvroErr::schema(format!("Field {} is not in the schema", key)))?;
+				path.push(position);
+				node = fields[position].node;
+			}
+			key_fields.push(KeyField { name: key.clone(), path, node });
+		}
+		Ok(SortOrder { schema, keys: key_fields })
+	}
+
+	/// Locates the key fields in an encoded record
+	fn entry(&self, data: Vec<u8>) -> Result<Entry, AvroErr> {
+		let mut keys = Vec::with_capacity(self.keys.len());
+		for key in &self.keys {
+			keys.push(self.locate(key, &data).map_err(|e| e.within(&format!(".{}", key.name)))?);
+		}
+		Ok(Entry { data, keys })
+	}
+
+	/// Gives the range of bytes that the key field is encoded in, skipping over the fields before it
+	fn locate(&self, key: &KeyField, data: &[u8]) -> Result<Range<usize>, AvroErr> {
+		let mut buf = data;
+		let mut node = self.schema.root();
+		for &position in &key.path {
+			if let SchemaNode::Record { ref fields, .. } = *self.schema.node(node) {
+				for field in &fields[..position] {
+					Type::skip_node(&self.schema, field.node, &mut buf)?;
+				}
+				node = fields[position].node;
+			}
+		}
+		let start = data.len() - buf.len();
+		Type::skip_node(&self.schema, node, &mut buf)?;
+		Ok(start..data.len() - buf.len())
+	}
+
+	fn compare(&self, a: &Entry, b: &Entry) -> Result<Ordering, AvroErr> {
+		if self.keys.is_empty() {
+			return compare(&self.schema, &a.data, &b.data);
+		}
+		for (key, (a_key, b_key)) in self.keys.iter().zip(a.keys.iter().zip(&b.keys)) {
+			let order = compare_node(&self.schema, key.node, &a.data[a_key.clone()], &b.data[b_key.clone()])
+				.map_err(|e| e.within(&format!(".{}", key.name)))?;
+			if order != Ordering::Equal {
+				return Ok(order);
+			}
+		}
+		Ok(Ordering::Equal)
+	}
+
+	/// Sorts the entries, keeping equal ones in the order they were read
+	fn sort(&self, entries: &mut [Entry]) -> Result<(), AvroErr> {
+		let mut failure = None;
+		entries.sort_by(|a, b| match self.compare(a, b) {
+			Ok(order) => order,
+			Err(e) => {
+				failure.get_or_insert(e);
+				Ordering::Equal
+			}
+		});
+		failure.map_or(Ok(()), Err)
+	}
+}
+
+/// Splits the records of a data block into their encodings
+fn split_records(schema: &CompiledSchema, block: &Block) -> Result<Vec<Vec<u8>>, AvroErr> {
+	let data = block.decompress()?;
+	let mut buf = &data[..];
+	let mut records = vec![];
+	for _ in 0..block.count {
+		let start = buf;
+		Type::skip(schema, &mut buf)?;
+		records.push(start[..start.len() - buf.len()].to_vec());
+	}
+	Ok(records)
+}
+
+/// A temporary data file holding a sorted run of records, which is removed once it is dropped
+struct TempRun(PathBuf);
+
+impl Drop for TempRun {
+	fn drop(&mut self) {
+		if let Err(e) = fs::remove_file(&self.0) {
+			warn!("Failed to remove temporary run {:?}: {}", self.0, e);
+		}
+	}
+}
+
+/// Reads back the records of a sorted data file, one at a time
+struct RunReader {
+	blocks: BlockReader<BufReader<File>>,
+	records: vec::IntoIter<Vec<u8>>
+}
+
+impl RunReader {
+	fn open(path: &Path) -> Result<Self, AvroErr> {
+		Ok(RunReader { blocks: AvroReader::from_path(path)?.iter_block(), records: vec![].into_iter() })
+	}
+
+	fn next(&mut self, order: &SortOrder) -> Result<Option<Entry>, AvroErr> {
+		loop {
+			if let Some(data) = self.records.next() {
+				return order.entry(data).map(Some);
+			}
+			match self.blocks.next() {
+				Some(block) => {
+					let block = block?;
+					self.records = split_records(&order.schema, &block)
+						.map_err(|e| e.in_block(None, block.offset))?
+						.into_iter();
+				}
+				None => return Ok(None)
+			}
+		}
+	}
+}
+
+/// Writes sorted records to a new data file, flushing them to it as blocks fill up
+struct RunWriter {
+	writer: AvroWriter,
+	written: u64,
+	/// No of records written since the last flush
+	pending: u64
+}
+
+impl RunWriter {
+	fn create(path: &Path, schema: &AvroSchema, codec: Codec) -> Result<Self, AvroErr> {
+		// Truncating the file makes the writer start it over with a new header
+		File::create(path)?;
+		let mut builder = AvroWriter::with_schema(schema.clone());
+		builder.set_codec(codec);
+		Ok(RunWriter { writer: builder.append_to(path)?, written: 0, pending: 0 })
+	}
+
+	fn write(&mut self, entry: &Entry) -> Result<(), AvroErr> {
+		self.writer.write_encoded(&entry.data)?;
+		self.written += 1;
+		self.pending += 1;
+		if self.pending == FLUSH_RECORDS {
+			self.writer.flush()?;
+			self.pending = 0;
+		}
+		Ok(())
+	}
+
+	fn finish(mut self) -> Result<u64, AvroErr> {
+		self.writer.flush()?;
+		Ok(self.written)
+	}
+}
+
+/// Sorts the records of avro data files with bounded memory, spilling sorted runs of records to
+/// temporary data files and merging them into the sorted data file. The sorted data file is written
+/// with the schema and codec of the data file being sorted. Records which compare equal keep the
+/// order they were read in.
+#[derive(Debug, Clone)]
+pub struct Sorter {
+	keys: Vec<String>,
+	max_run_bytes: usize,
+	max_merge_runs: usize,
+	temp_dir: PathBuf
+}
+
+impl Default for Sorter {
+	fn default() -> Self {
+		Sorter {
+			keys: vec![],
+			max_run_bytes: 64 * 1024 * 1024,
+			max_merge_runs: 64,
+			temp_dir: env::temp_dir()
+		}
+	}
+}
+
+impl Sorter {
+	/// Creates a sorter which sorts records in the sort order of their schema
+	pub fn new() -> Self {
+		Sorter::default()
+	}
+
+	/// Sorts records by the given fields instead, comparing them by the first field and then
+	/// by each next one while they are equal. Nested fields are named after their enclosing
+	/// fields, as in `inner_rec.id`.
+	pub fn set_keys(&mut self, keys: &[&str]) {
+		self.keys = keys.iter().map(|key| key.to_string()).collect();
+	}
+
+	/// Sets the approximate no of bytes of records held in memory before they are sorted and
+	/// spilled to a temporary data file. Defaults to 64 MiB.
+	pub fn set_max_run_bytes(&mut self, max_run_bytes: usize) {
+		self.max_run_bytes = max_run_bytes;
+	}
+
+	/// Sets the no of temporary data files merged at once, which bounds the no of files kept open.
+	/// More runs are merged in several passes. Defaults to 64.
+	pub fn set_max_merge_runs(&mut self, max_merge_runs: usize) {
+		self.max_merge_runs = max_merge_runs.max(2);
+	}
+
+	/// Sets the directory temporary data files are written to. Defaults to the temporary
+	/// directory of the system.
+	pub fn set_temp_dir<P: AsRef<Path>>(&mut self, temp_dir: P) {
+		self.temp_dir = temp_dir.as_ref().to_path_buf();
+	}
+
+	/// Sorts the records of the input data file into the output data file, which is replaced if
+	/// it exists. Returns the no of records sorted.
+	pub fn sort<P: AsRef<Path>, Q: AsRef<Path>>(&self, input: P, output: Q) -> Result<u64, AvroErr> {
+		let reader = AvroReader::from_path(input)?;
+		let schema = reader.get_schema().clone();
+		let codec = reader.get_codec();
+		let order = SortOrder::new(schema.compile()?, &self.keys)?;
+		let run_prefix = format!("ravro-sort-{}-{:016x}", process::id(), thread_rng().gen::<u64>());
+		let mut runs = vec![];
+		let mut entries = vec![];
+		let mut run_bytes = 0;
+		for (index, block) in reader.iter_block().enumerate() {
+			let block = block?;
+			let records = split_records(&order.schema, &block).map_err(|e| e.in_block(Some(index as u64), block.offset))?;
+			for data in records {
+				run_bytes += data.len() + RECORD_OVERHEAD;
+				entries.push(order.entry(data).map_err(|e| e.in_block(Some(index as u64), block.offset))?);
+				if run_bytes >= self.max_run_bytes {
+					let run = TempRun(self.temp_dir.join(format!("{}-{}.avro", run_prefix, runs.len())));
+					self.spill(&order, mem::take(&mut entries), &run.0, &schema, codec)?;
+					runs.push(run);
+					run_bytes = 0;
+				}
+			}
+		}
+		// Input which fits in a single run is written out without temporary data files
+		if runs.is_empty() {
+			return self.spill(&order, entries, output.as_ref(), &schema, codec);
+		}
+		if !entries.is_empty() {
+			let run = TempRun(self.temp_dir.join(format!("{}-{}.avro", run_prefix, runs.len())));
+			self.spill(&order, entries, &run.0, &schema, codec)?;
+			runs.push(run);
+		}
+		let mut pass = 0;
+		while runs.len() > self.max_merge_runs {
+			let mut merged = vec![];
+			// Merging consecutive runs keeps equal records in the order they were read
+			for (i, group) in runs.chunks(self.max_merge_runs).enumerate() {
+				let run = TempRun(self.temp_dir.join(format!("{}-merge-{}-{}.avro", run_prefix, pass, i)));
+				self.merge(&order, group, &run.0, &schema, codec)?;
+				merged.push(run);
+			}
+			runs = merged;
+			pass += 1;
+		}
+		self.merge(&order, &runs, output.as_ref(), &schema, codec)
+	}
+
+	/// Sorts the buffered records and writes them to a data file
+	fn spill(&self, order: &SortOrder, mut entries: Vec<Entry>, path: &Path, schema: &AvroSchema, codec: Codec) -> Result<u64, AvroErr> {
+		order.sort(&mut entries)?;
+		let mut writer = RunWriter::create(path, schema, codec)?;
+		for entry in &entries {
+			writer.write(entry)?;
+		}
+		writer.finish()
+	}
+
+	/// Merges sorted runs into a data file, taking the smallest of the records at the head of the
+	/// runs in turn, and the one of the earliest run among equal records
+	fn merge(&self, order: &SortOrder, runs: &[TempRun], path: &Path, schema: &AvroSchema, codec: Codec) -> Result<u64, AvroErr> {
+		let mut readers = vec![];
+		let mut heads = vec![];
+		for run in runs {
+			let mut reader = RunReader::open(&run.0)?;
+			heads.push(reader.next(order)?);
+			readers.push(reader);
+		}
+		let mut writer = RunWriter::create(path, schema, codec)?;
+		loop {
+			let mut smallest: Option<(usize, &Entry)> = None;
+			for (i, head) in heads.iter().enumerate() {
+				if let Some(ref entry) = *head {
+					let is_smaller = match smallest {
+						Some((_, s)) => order.compare(entry, s)? == Ordering::Less,
+						None => true
+					};
+					if is_smaller {
+						smallest = Some((i, entry));
+					}
+				}
+			}
+			match smallest.map(|(i, _)| i) {
+				Some(i) => {
+					let next = readers[i].next(order)?;
+					let entry = mem::replace(&mut heads[i], next).expect("Head of run");
+					writer.write(&entry)?;
+				}
+				None => return writer.finish()
+			}
+		}
+	}
+}
@@ -1,14 +1,17 @@
 //! Contains definitions of various avro types.
 
-use std::io::{Read, Write};
+use std::io::{self, Read, Write};
 use std::mem;
-use std::str;
 use std::collections::HashMap;
-use complex::Record;
+use complex::{Field, Record};
 use errors::AvroErr;
-use codec::{Encoder, Decoder};
+use codec::{Encoder, Decoder, DecoderLimits, EncoderOptions};
 use complex::Enum;
-use std::error::Error;
+use schema::{CompiledSchema, SchemaNode};
+use std::str;
+use std::fmt::Debug;
+use std::cmp::Ordering;
+use compare;
 
 fn zig_zag(num: i64) -> u64 {
     if num < 0 {
@@ -19,31 +22,46 @@ fn zig_zag(num: i64) -> u64 {
 }
 
 fn encode_var_len<W: Write>(writer: &mut W, mut num: u64) -> Result<usize, AvroErr> {
+    // The bytes are gathered first, so that the writer is called once per number
+    let mut buf = [0u8; 10];
     let mut write_cnt = 0;
     loop {
         let mut b = (num & 0b0111_1111) as u8;
         num >>= 7;
+        if num != 0 {
+            b |= 0b1000_0000;
+        }
+        buf[write_cnt] = b;
+        write_cnt += 1;
         if num == 0 {
-            writer.write_all(&[b]).map_err(|e| AvroErr::EncodeErr(e.description().to_string()))?;
-            write_cnt += 1;
             break;
         }
-        b |= 0b1000_0000;
-        writer.write_all(&[b]).map_err(|e| AvroErr::EncodeErr(e.description().to_string()))?;
-        write_cnt += 1;
     }
+    writer.write_all(&buf[..write_cnt])?;
     Ok(write_cnt)
 }
 
+/// Writes the given bytes as they are
+fn encode_raw<W: Write>(writer: &mut W, bytes: &[u8]) -> Result<usize, AvroErr> {
+    writer.write_all(bytes)?;
+    Ok(bytes.len())
+}
+
+/// Writes the given bytes prefixed with their length
+fn encode_bytes<W: Write>(writer: &mut W, bytes: &[u8]) -> Result<usize, AvroErr> {
+    let len = encode_var_len(writer, zig_zag(bytes.len() as i64))?;
+    Ok(len + encode_raw(writer, bytes)?)
+}
+
 /// Decodes a variable length encoded u64 from the given reader
 fn decode_var_len_u64<R: Read>(reader: &mut R) -> Result<u64, AvroErr> {
     let mut num = 0;
     let mut i = 0;
     loop {
         let mut buf = [0u8; 1];
-        reader.read_exact(&mut buf).map_err(|_| AvroErr::DecodeErr("Could not read buffer while decoding u64".to_string()))?;
+        reader.read_exact(&mut buf).map_err(AvroErr::read)?;
         if i >= 9 && buf[0] & 0b1111_1110 != 0 {
-            return Err(AvroErr::DecodeErr("Failed decoding u64".to_string()));
+            return Err(AvroErr::decode("Failed decoding u64"));
         }
         num |= (buf[0] as u64 & 0b0111_1111) << (i * 7);
         if buf[0] & 0b1000_0000 == 0 {
@@ -82,7 +100,7 @@ pub enum Type {
     Bytes(Vec<u8>),
     /// String avro type
     Str(String),
-    /// Map avro type
+    /// Map avro type. Entries are encoded in ascending order of their keys.
     Map(HashMap<String, Type>),
     /// Record avro type
     Record(Record),
@@ -91,9 +109,9 @@ pub enum Type {
     /// Enum avro type
     Enum(Enum),
     /// Fixed avro type
-    Fixed,
-    /// Union avro type
-    Union
+    Fixed(Vec<u8>),
+    /// Union avro type, holding the index of the union branch and the value of that branch
+    Union(usize, Box<Type>)
 }
 
 // These methods are meant to be called only in contexts where we know before hand
@@ -198,7 +216,7 @@ impl Decoder for bool {
         match reader.bytes().next() {
             Some(Ok(0x00)) => Ok(false),
             Some(Ok(0x01)) => Ok(true),
-            b => Err(AvroErr::DecodeErr(format!("Expected boolean byte to be 0x00 or 0x01, Found: {:?}", b)))
+            b => Err(AvroErr::decode(format!("Expected boolean byte to be 0x00 or 0x01, Found: {:?}", b)))
         }
     }
 }
@@ -206,18 +224,48 @@ impl Decoder for bool {
 impl Decoder for Vec<u8> {
     type Out=Vec<u8>;
     fn decode<R: Read>(reader: &mut R) -> Result<Self::Out, AvroErr> {
-        let bytes_len_decoded = i64::decode(reader)?;
-        let mut data_buf = vec![0u8; bytes_len_decoded as usize];
-        reader.read_exact(&mut data_buf).map_err(|_| AvroErr::AvroReadErr)?;
-        Ok(data_buf.to_vec())
+        Vec::<u8>::decode_with_limits(reader, &DecoderLimits::default())
+    }
+
+    fn decode_with_limits<R: Read>(reader: &mut R, limits: &DecoderLimits) -> Result<Self::Out, AvroErr> {
+        let len = decode_len(reader, limits.max_length)?;
+        read_len(reader, len)
+    }
+}
+
+/// Decodes the length prefix of bytes or a string, which may neither be negative nor exceed `max_length`
+pub(crate) fn decode_len<R: Read>(reader: &mut R, max_length: u64) -> Result<u64, AvroErr> {
+    let len = i64::decode(reader)?;
+    if len < 0 {
+        return Err(AvroErr::decode(format!("Invalid length {}", len)));
     }
+    check_length(len as u64, max_length)
+}
+
+/// Checks the length of bytes, a string or a fixed value against the limit
+fn check_length(len: u64, max_length: u64) -> Result<u64, AvroErr> {
+    if len > max_length {
+        return Err(AvroErr::limit("max_length", len, max_length));
+    }
+    Ok(len)
+}
+
+/// Reads the given no of bytes. The buffer grows as data is read rather than being allocated
+/// up front, as the length may be corrupt.
+fn read_len<R: Read>(reader: &mut R, len: u64) -> Result<Vec<u8>, AvroErr> {
+    let mut data = vec![];
+    reader.take(len).read_to_end(&mut data).map_err(AvroErr::read)?;
+    if (data.len() as u64) < len {
+        return Err(AvroErr::decode("Unexpected end of data"));
+    }
+    Ok(data)
 }
 
 impl Decoder for f32 {
     type Out=f32;
     fn decode<R: Read>(reader: &mut R) -> Result<Self::Out, AvroErr> {
         let mut a = [0u8; 4];
-        reader.read_exact(&mut a).map_err(|e| AvroErr::DecodeErr(e.description().to_string()))?;
+        reader.read_exact(&mut a).map_err(AvroErr::read)?;
         Ok(unsafe { mem::transmute(a) })
     }
 }
@@ -226,7 +274,7 @@ impl Decoder for f64 {
     type Out=f64;
     fn decode<R: Read>(reader: &mut R) -> Result<Self::Out, AvroErr> {
         let mut a = [0u8; 8];
-        reader.read_exact(&mut a).map_err(|e| AvroErr::DecodeErr(e.description().to_string()))?;
+        reader.read_exact(&mut a).map_err(AvroErr::read)?;
         Ok(unsafe { mem::transmute(a) })
     }
 }
@@ -234,11 +282,18 @@ impl Decoder for f64 {
 impl Decoder for HashMap<String, String> {
     type Out=HashMap<String, String>;
     fn decode<R: Read>(reader: &mut R) -> Result<Self::Out, AvroErr> {
+        HashMap::<String, String>::decode_with_limits(reader, &DecoderLimits::default())
+    }
+
+    fn decode_with_limits<R: Read>(reader: &mut R, limits: &DecoderLimits) -> Result<Self::Out, AvroErr> {
         let mut map = HashMap::new();
-        let sz = i64::decode(reader).unwrap();
+        let sz = i64::decode(reader)?;
+        if sz > 0 && sz as u64 > limits.max_items {
+            return Err(AvroErr::limit("max_items", sz as u64, limits.max_items));
+        }
         for _ in 0..sz {
-            let decoded_key = String::decode(reader).unwrap();
-            let decoded_val = String::decode(reader).unwrap();
+            let decoded_key = String::decode_with_limits(reader, limits)?;
+            let decoded_val = String::decode_with_limits(reader, limits)?;
             map.insert(decoded_key, decoded_val);
         }
         Ok(map)
@@ -248,23 +303,18 @@ impl Decoder for HashMap<String, String> {
 impl Decoder for String {
     type Out=Self;
     fn decode<R: Read>(reader: &mut R) -> Result<Self::Out, AvroErr> {
-        let strlen = i64::decode(reader).unwrap();
-        let mut str_buf = vec![0u8; strlen as usize];
-        reader.read_exact(&mut str_buf).map_err(|e| AvroErr::DecodeErr(e.description().to_string()))?;
-        let st = str::from_utf8(str_buf.as_slice()).unwrap().to_string();
-        Ok(st)
+        String::decode_with_limits(reader, &DecoderLimits::default())
+    }
+
+    fn decode_with_limits<R: Read>(reader: &mut R, limits: &DecoderLimits) -> Result<Self::Out, AvroErr> {
+        decode_string(reader, limits)
     }
 }
 
 impl Encoder for String {
     fn encode<W: Write>(&self, writer: &mut W) -> Result<usize, AvroErr> {
-        let mut total_len = 0;
-        let strlen = self.chars().count();
-        total_len += Type::Long(strlen as i64).encode(writer)?;
-        let bytes = self.clone().into_bytes();
-        total_len += bytes.len();
-        writer.write_all(bytes.as_slice()).map_err(|e| AvroErr::EncodeErr(e.description().to_string()))?;
-        Ok(total_len)
+        // The length prefix counts the bytes of the UTF-8 encoding, not the chars
+        encode_bytes(writer, self.as_bytes())
     }
 }
 
@@ -274,9 +324,9 @@ impl Encoder for Type {
             Type::Null => Ok(0),
             Type::Bool(val) => {
                 if val {
-                    writer.write_all(&[0x01]).map_err(|e| AvroErr::EncodeErr(e.description().to_string()))?;
+                    writer.write_all(&[0x01])?;
                 } else {
-                    writer.write_all(&[0x00]).map_err(|e| AvroErr::EncodeErr(e.description().to_string()))?;
+                    writer.write_all(&[0x00])?;
                 }
                 Ok(1)
             }
@@ -284,12 +334,12 @@ impl Encoder for Type {
             Type::Long(val) => encode_var_len(writer, zig_zag(val)),
             Type::Float(val) => {
                 let buf: [u8; 4] = unsafe { mem::transmute(val) };
-                writer.write_all(&buf).map_err(|e| AvroErr::EncodeErr(e.description().to_string()))?;
+                writer.write_all(&buf)?;
                 Ok(4)
             }
             Type::Double(val) => {
                 let buf: [u8; 8] = unsafe { mem::transmute(val) };
-                writer.write_all(&buf).map_err(|_| AvroErr::AvroWriteErr)?;
+                writer.write_all(&buf)?;
                 Ok(8)
             }
             Type::Bytes(ref bytes) => {
@@ -297,7 +347,7 @@ impl Encoder for Type {
                 let byte_len = Type::Long(bytes.len() as i64);
                 total_len += byte_len.encode(writer)?;
                 total_len += bytes.len();
-                let _ = writer.write_all(bytes);
+                writer.write_all(bytes)?;
                 Ok(total_len)
             }
             Type::Str(ref s) => s.encode(writer),
@@ -309,21 +359,21 @@ impl Encoder for Type {
                  Ok(total_len)
             }
             Type::Map(ref bmap) => {
-                let mut total_len = 0;
-                let block_len = Type::Long(bmap.keys().len() as i64);
-                total_len += block_len.encode(writer)?;
-                for i in bmap.keys().zip(bmap.values()) {
-                    total_len += i.0.encode(writer)?;
-                    total_len += i.1.encode(writer)?;
+                let mut total_len = encode_block_count(writer, bmap.len())?;
+                // Entries are written sorted by key so that encoding the same map
+                // always gives the same bytes, irrespective of hashing order.
+                let mut keys: Vec<&String> = bmap.keys().collect();
+                keys.sort();
+                for key in keys {
+                    total_len += key.encode(writer)?;
+                    total_len += bmap[key].encode(writer)?;
                 }
                 // Mark the end of map type
                 total_len += Type::Long(0i64).encode(writer)?;
                 Ok(total_len)
             }
             Type::Array(ref arr) => {
-                let mut total_len = 0;
-                let block_len = Type::Long(arr.len() as i64);
-                total_len += block_len.encode(writer)?;
+                let mut total_len = encode_block_count(writer, arr.len())?;
                 for i in arr {
                     total_len += i.encode(writer)?;
                 }
@@ -333,7 +383,759 @@ impl Encoder for Type {
             Type::Enum(ref enum_type) => {
                 enum_type.encode(writer)
             }
-            Type::Fixed | Type::Union => unimplemented!(),
+            Type::Fixed(ref bytes) => {
+                writer.write_all(bytes)?;
+                Ok(bytes.len())
+            }
+            Type::Union(idx, ref val) => {
+                let mut total_len = Type::Long(idx as i64).encode(writer)?;
+                total_len += val.encode(writer)?;
+                Ok(total_len)
+            }
+        }
+    }
+}
+
+/// Error for a value which does not conform to the schema node it is encoded with
+pub(crate) fn schema_mismatch<F: Into<String>>(found: F, node: &SchemaNode) -> AvroErr {
+    AvroErr::validation(node.to_string(), found)
+}
+
+/// Describes a value in schema mismatch errors
+pub(crate) fn describe_value(value: &Type) -> String {
+    match *value {
+        Type::Null => "null".to_string(),
+        Type::Bool(b) => format!("boolean {}", b),
+        Type::Int(i) => format!("int {}", i),
+        Type::Long(l) => format!("long {}", l),
+        Type::Float(f) => format!("float {}", f),
+        Type::Double(d) => format!("double {}", d),
+        Type::Bytes(ref b) => format!("bytes of length {}", b.len()),
+        Type::Str(_) => "string".to_string(),
+        Type::Map(ref map) => format!("map of {} entries", map.len()),
+        Type::Record(ref rec) => format!("record with {} fields", rec.fields.len()),
+        Type::Array(ref arr) => format!("array of {} items", arr.len()),
+        Type::Enum(ref e) => format!("enum value {:?}", e.get_value()),
+        Type::Fixed(ref b) => format!("fixed of {} bytes", b.len()),
+        Type::Union(idx, _) => format!("union branch {}", idx)
+    }
+}
+
+/// Describes a borrowed value in schema mismatch errors
+fn describe_value_ref(value: &TypeRef) -> String {
+    match *value {
+        TypeRef::Null => "null".to_string(),
+        TypeRef::Bool(b) => format!("boolean {}", b),
+        TypeRef::Int(i) => format!("int {}", i),
+        TypeRef::Long(l) => format!("long {}", l),
+        TypeRef::Float(f) => format!("float {}", f),
+        TypeRef::Double(d) => format!("double {}", d),
+        TypeRef::Bytes(b) => format!("bytes of length {}", b.len()),
+        TypeRef::Str(_) => "string".to_string(),
+        TypeRef::Map(ref entries) => format!("map of {} entries", entries.len()),
+        TypeRef::Record { name, ref fields } => format!("record {} with {} fields", name, fields.len()),
+        TypeRef::Array(ref arr) => format!("array of {} items", arr.len()),
+        TypeRef::Enum { symbols, index, .. } => format!("enum value {:?}", symbols.get(index)),
+        TypeRef::Fixed(b) => format!("fixed of {} bytes", b.len()),
+        TypeRef::Union(idx, _) => format!("union branch {}", idx)
+    }
+}
+
+/// Writes the item count of a block of an array or map, or nothing if there are no items
+fn encode_block_count<W: Write>(writer: &mut W, count: usize) -> Result<usize, AvroErr> {
+    if count == 0 {
+        Ok(0)
+    } else {
+        encode_var_len(writer, zig_zag(count as i64))
+    }
+}
+
+/// Writes the `len` items of an array or map in blocks, followed by the zero count ending them
+fn encode_blocks<W: Write, F>(writer: &mut W, len: usize, options: &EncoderOptions, mut encode_item: F) -> Result<usize, AvroErr>
+    where F: FnMut(usize, &mut dyn Write) -> Result<usize, AvroErr> {
+    let mut total_len = 0;
+    match options.block_items {
+        Some(block_items) => {
+            let block_items = block_items.max(1);
+            let mut block = vec![];
+            for start in (0..len).step_by(block_items) {
+                let end = len.min(start + block_items);
+                block.clear();
+                for i in start..end {
+                    encode_item(i, &mut block)?;
+                }
+                // A negative count tells readers that the size of the block follows
+                total_len += encode_var_len(writer, zig_zag(-((end - start) as i64)))?;
+                total_len += encode_bytes(writer, &block)?;
+            }
+        }
+        None => {
+            total_len += encode_block_count(writer, len)?;
+            for i in 0..len {
+                total_len += encode_item(i, writer)?;
+            }
+        }
+    }
+    total_len += encode_var_len(writer, 0)?;
+    Ok(total_len)
+}
+
+impl Type {
+    /// Decodes a value of the given schema out of the reader, within the default `DecoderLimits`
+    pub fn decode_with<R: Read>(schema: &CompiledSchema, reader: &mut R) -> Result<Type, AvroErr> {
+        Type::decode_with_limits(schema, &DecoderLimits::default(), reader)
+    }
+
+    /// Decodes a value of the given schema out of the reader, failing with `AvroErr::LimitExceeded`
+    /// on data exceeding the given limits
+    pub fn decode_with_limits<R: Read>(schema: &CompiledSchema, limits: &DecoderLimits, reader: &mut R) -> Result<Type, AvroErr> {
+        Type::decode_limited(schema, schema.root(), limits, 0, &mut 0, reader)
+    }
+
+    /// Decodes a value of the schema node at the given index out of the reader, within the default `DecoderLimits`
+    pub fn decode_node<R: Read>(schema: &CompiledSchema, node: usize, reader: &mut R) -> Result<Type, AvroErr> {
+        Type::decode_limited(schema, node, &DecoderLimits::default(), 0, &mut 0, reader)
+    }
+
+    /// Decodes a value of the schema node at the given index, nested `depth` values deep. Items of
+    /// arrays encoded in no bytes are added to `zero_width_items`, which may span several values.
+    pub(crate) fn decode_limited<R: Read>(schema: &CompiledSchema, node: usize, limits: &DecoderLimits, depth: usize,
+                                          zero_width_items: &mut u64, reader: &mut R) -> Result<Type, AvroErr> {
+        if depth > limits.max_depth {
+            return Err(AvroErr::limit("max_depth", depth as u64, limits.max_depth as u64));
+        }
+        match *schema.node(node) {
+            SchemaNode::Null => Ok(Type::Null),
+            SchemaNode::Boolean => bool::decode(reader).map(Type::Bool),
+            SchemaNode::Int => i32::decode(reader).map(Type::Int),
+            SchemaNode::Long => i64::decode(reader).map(Type::Long),
+            SchemaNode::Float => f32::decode(reader).map(Type::Float),
+            SchemaNode::Double => f64::decode(reader).map(Type::Double),
+            SchemaNode::Bytes => {
+                let len = decode_len(reader, limits.max_length)?;
+                read_len(reader, len).map(Type::Bytes)
+            }
+            SchemaNode::String => decode_string(reader, limits).map(Type::Str),
+            SchemaNode::Record { ref name, ref fields } => {
+                let mut values = Vec::with_capacity(fields.len());
+                for field in fields {
+                    let value = Type::decode_limited(schema, field.node, limits, depth + 1, zero_width_items, reader).map_err(|e| e.within(&format!(".{}", field.name)))?;
+                    values.push(Field::new(&field.name, value));
+                }
+                Ok(Type::Record(Record::new(name, None, values)))
+            }
+            SchemaNode::Enum { ref name, ref symbols } => {
+                let index = i64::decode(reader)?;
+                let symbol = if index < 0 { None } else { symbols.get(index as usize) };
+                let symbol = symbol.ok_or_else(|| AvroErr::decode(format!("Enum symbol {} does not exist", index)))?;
+                let mut enum_val = Enum::from_symbols(name, symbols.clone());
+                enum_val.set_value(symbol);
+                Ok(Type::Enum(enum_val))
+            }
+            SchemaNode::Array(items) => {
+                let mut arr = vec![];
+                loop {
+                    let count = decode_block_count(reader, arr.len(), limits)?;
+                    if count == 0 {
+                        return Ok(Type::Array(arr));
+                    }
+                    count_zero_width_items(schema.min_size(items), count, limits, zero_width_items)?;
+                    arr.reserve(count.min(RESERVE_LIMIT));
+                    for _ in 0..count {
+                        let item = Type::decode_limited(schema, items, limits, depth + 1, zero_width_items, reader).map_err(|e| e.within(&format!("[{}]", arr.len())))?;
+                        arr.push(item);
+                    }
+                }
+            }
+            SchemaNode::Map(values) => {
+                let mut map = HashMap::new();
+                loop {
+                    let count = decode_block_count(reader, map.len(), limits)?;
+                    if count == 0 {
+                        return Ok(Type::Map(map));
+                    }
+                    map.reserve(count.min(RESERVE_LIMIT));
+                    for _ in 0..count {
+                        let key = decode_string(reader, limits)?;
+                        let value = Type::decode_limited(schema, values, limits, depth + 1, zero_width_items, reader).map_err(|e| e.within(&format!("[{:?}]", key)))?;
+                        map.insert(key, value);
+                    }
+                }
+            }
+            SchemaNode::Fixed { size, .. } => {
+                let len = check_length(size as u64, limits.max_length)?;
+                read_len(reader, len).map(Type::Fixed)
+            }
+            SchemaNode::Union(ref branches) => {
+                let idx = i64::decode(reader)?;
+                let branch = if idx < 0 { None } else { branches.get(idx as usize) };
+                let branch = branch.ok_or_else(|| AvroErr::decode(format!("Union branch {} does not exist", idx)))?;
+                let value = Type::decode_limited(schema, *branch, limits, depth + 1, zero_width_items, reader).map_err(|e| e.within(&format!("[{}]", idx)))?;
+                Ok(Type::Union(idx as usize, Box::new(value)))
+            }
+        }
+    }
+
+    /// Skips over a value of the given schema in the reader without decoding it. Lengths are
+    /// jumped over and arrays and maps written with their block sizes are skipped a block at a time.
+    pub fn skip<R: Read>(schema: &CompiledSchema, reader: &mut R) -> Result<(), AvroErr> {
+        Type::skip_limited(schema, schema.root(), &DecoderLimits::default(), 0, reader)
+    }
+
+    /// Skips over a value of the given schema, failing with `AvroErr::LimitExceeded` on data exceeding the given limits
+    pub fn skip_with_limits<R: Read>(schema: &CompiledSchema, limits: &DecoderLimits, reader: &mut R) -> Result<(), AvroErr> {
+        Type::skip_limited(schema, schema.root(), limits, 0, reader)
+    }
+
+    /// Skips over a value of the schema node at the given index, within the default `DecoderLimits`
+    pub fn skip_node<R: Read>(schema: &CompiledSchema, node: usize, reader: &mut R) -> Result<(), AvroErr> {
+        Type::skip_limited(schema, node, &DecoderLimits::default(), 0, reader)
+    }
+
+    /// Skips over a value of the schema node at the given index, nested `depth` values deep
+    pub(crate) fn skip_limited<R: Read>(schema: &CompiledSchema, node: usize, limits: &DecoderLimits, depth: usize, reader: &mut R) -> Result<(), AvroErr> {
+        if depth > limits.max_depth {
+            return Err(AvroErr::limit("max_depth", depth as u64, limits.max_depth as u64));
+        }
+        match *schema.node(node) {
+            SchemaNode::Null => Ok(()),
+            SchemaNode::Boolean => skip_len(reader, 1),
+            SchemaNode::Int | SchemaNode::Long | SchemaNode::Enum { .. } => skip_var_len(reader),
+            SchemaNode::Float => skip_len(reader, 4),
+            SchemaNode::Double => skip_len(reader, 8),
+            SchemaNode::Bytes | SchemaNode::String => {
+                let len = decode_len(reader, limits.max_length)?;
+                skip_len(reader, len)
+            }
+            SchemaNode::Record { ref fields, .. } => {
+                for field in fields {
+                    Type::skip_limited(schema, field.node, limits, depth + 1, reader).map_err(|e| e.within(&format!(".{}", field.name)))?;
+                }
+                Ok(())
+            }
+            SchemaNode::Array(items) => {
+                let mut skipped = 0;
+                loop {
+                    let (count, size) = decode_block_header(reader, skipped, limits)?;
+                    if count == 0 {
+                        return Ok(());
+                    }
+                    match size {
+                        Some(size) => skip_len(reader, size)?,
+                        None => for i in skipped..skipped + count {
+                            Type::skip_limited(schema, items, limits, depth + 1, reader).map_err(|e| e.within(&format!("[{}]", i)))?;
+                        }
+                    }
+                    skipped += count;
+                }
+            }
+            SchemaNode::Map(values) => {
+                let mut skipped = 0;
+                loop {
+                    let (count, size) = decode_block_header(reader, skipped, limits)?;
+                    if count == 0 {
+                        return Ok(());
+                    }
+                    match size {
+                        Some(size) => skip_len(reader, size)?,
+                        None => for _ in 0..count {
+                            // Keys are skipped like strings, so errors point at any entry of the map
+                            let key_len = decode_len(reader, limits.max_length)?;
+                            skip_len(reader, key_len)?;
+                            Type::skip_limited(schema, values, limits, depth + 1, reader).map_err(|e| e.within("[*]"))?;
+                        }
+                    }
+                    skipped += count;
+                }
+            }
+            SchemaNode::Fixed { size, .. } => skip_len(reader, size as u64),
+            SchemaNode::Union(ref branches) => {
+                let idx = i64::decode(reader)?;
+                let branch = if idx < 0 { None } else { branches.get(idx as usize) };
+                let branch = branch.ok_or_else(|| AvroErr::decode(format!("Union branch {} does not exist", idx)))?;
+                Type::skip_limited(schema, *branch, limits, depth + 1, reader).map_err(|e| e.within(&format!("[{}]", idx)))
+            }
+        }
+    }
+
+    /// Encodes the value with the given schema, failing with `AvroErr::Validation` if it
+    /// does not conform to the schema. Part of the value may have been written on failure.
+    pub fn encode_with<W: Write>(&self, schema: &CompiledSchema, writer: &mut W) -> Result<usize, AvroErr> {
+        self.encode_node(schema, schema.root(), writer)
+    }
+
+    /// Compares the value with another value of the given schema, in the sort order of the avro spec.
+    /// Values compare the same way as their encodings do with `compare::compare`.
+    pub fn cmp_with(&self, other: &Type, schema: &CompiledSchema) -> Result<Ordering, AvroErr> {
+        compare::compare_values(schema, self, other)
+    }
+
+    /// Encodes the value with the given schema, laying out arrays and maps as the options say
+    pub fn encode_with_options<W: Write>(&self, schema: &CompiledSchema, options: &EncoderOptions, writer: &mut W) -> Result<usize, AvroErr> {
+        self.encode_node_with(schema, schema.root(), options, writer)
+    }
+
+    /// Encodes the value with the schema node at the given index, with the default `EncoderOptions`
+    pub fn encode_node<W: Write>(&self, schema: &CompiledSchema, node: usize, writer: &mut W) -> Result<usize, AvroErr> {
+        self.encode_node_with(schema, node, &EncoderOptions::default(), writer)
+    }
+
+    /// Encodes the value with the schema node at the given index and the given options
+    fn encode_node_with<W: Write>(&self, schema: &CompiledSchema, node: usize, options: &EncoderOptions, writer: &mut W) -> Result<usize, AvroErr> {
+        let schema_node = schema.node(node);
+        match (schema_node, self) {
+            (SchemaNode::Null, Type::Null) => Ok(0),
+            (SchemaNode::Boolean, Type::Bool(b)) => encode_raw(writer, &[u8::from(*b)]),
+            (SchemaNode::Float, Type::Float(f)) => encode_raw(writer, &f.to_bits().to_le_bytes()),
+            (SchemaNode::Double, Type::Double(d)) => encode_raw(writer, &d.to_bits().to_le_bytes()),
+            (SchemaNode::Bytes, Type::Bytes(bytes)) => encode_bytes(writer, bytes),
+            (SchemaNode::String, Type::Str(s)) => s.encode(writer),
+            // Int and Long are encoded in same way
+            (SchemaNode::Int, Type::Int(val)) |
+            (SchemaNode::Long, Type::Int(val)) => encode_var_len(writer, zig_zag(i64::from(*val))),
+            (SchemaNode::Long, Type::Long(val)) => encode_var_len(writer, zig_zag(*val)),
+            (SchemaNode::Int, Type::Long(val)) if *val as i32 as i64 == *val => encode_var_len(writer, zig_zag(*val)),
+            (SchemaNode::Record { fields, .. }, Type::Record(rec)) if fields.len() == rec.fields.len() => {
+                let mut total_len = 0;
+                for (field, value) in fields.iter().zip(&rec.fields) {
+                    if field.name != value.name {
+                        let found = format!("field {}", value.name);
+                        return Err(AvroErr::validation(format!("field {}", field.name), found));
+                    }
+                    total_len += value.ty.encode_node_with(schema, field.node, options, writer)
+                        .map_err(|e| e.within(&format!(".{}", field.name)))?;
+                }
+                Ok(total_len)
+            }
+            (SchemaNode::Enum { symbols, .. }, Type::Enum(enum_val)) => {
+                let idx = enum_val.get_value().and_then(|val| symbols.iter().position(|s| s == val))
+                    .ok_or_else(|| schema_mismatch(describe_value(self), schema_node))?;
+                encode_var_len(writer, zig_zag(idx as i64))
+            }
+            (SchemaNode::Array(items), Type::Array(arr)) => {
+                encode_blocks(writer, arr.len(), options, |i, mut w| {
+                    arr[i].encode_node_with(schema, *items, options, &mut w).map_err(|e| e.within(&format!("[{}]", i)))
+                })
+            }
+            (SchemaNode::Map(values), Type::Map(map)) => {
+                // Entries are written sorted by key, as with `Type::encode`. A single entry
+                // needs no sorting, which spares allocating the sorted entries for it.
+                let single = if map.len() == 1 { map.iter().next() } else { None };
+                let mut sorted = vec![];
+                if single.is_none() {
+                    sorted.extend(map.iter());
+                    sorted.sort_unstable_by_key(|&(key, _)| key);
+                }
+                encode_blocks(writer, map.len(), options, |i, mut w| {
+                    let (key, value) = single.unwrap_or_else(|| sorted[i]);
+                    let key_len = key.encode(&mut w)?;
+                    let value_len = value.encode_node_with(schema, *values, options, &mut w)
+                        .map_err(|e| e.within(&format!("[{:?}]", key)))?;
+                    Ok(key_len + value_len)
+                })
+            }
+            (SchemaNode::Fixed { size, .. }, Type::Fixed(bytes)) if bytes.len() == *size => encode_raw(writer, bytes),
+            (SchemaNode::Union(branches), Type::Union(idx, value)) if *idx < branches.len() => {
+                let total_len = encode_var_len(writer, zig_zag(*idx as i64))?;
+                let value_len = value.encode_node_with(schema, branches[*idx], options, writer)
+                    .map_err(|e| e.within(&format!("[{}]", idx)))?;
+                Ok(total_len + value_len)
+            }
+            _ => Err(schema_mismatch(describe_value(self), schema_node))
+        }
+    }
+}
+
+/// A borrowed counterpart of `Type`. Strings and bytes point into the buffer the value was
+/// decoded from, while record, field and enum names point into the `CompiledSchema` it was decoded with.
+#[derive(Debug, PartialEq, Clone)]
+pub enum TypeRef<'a> {
+    /// Null avro type
+    Null,
+    /// Bool avro type
+    Bool(bool),
+    /// Int avro type
+    Int(i32),
+    /// Long avro type
+    Long(i64),
+    /// Float avro type
+    Float(f32),
+    /// Double avro type
+    Double(f64),
+    /// Bytes avro type
+    Bytes(&'a [u8]),
+    /// String avro type
+    Str(&'a str),
+    /// Map avro type, with its entries in the order they were decoded
+    Map(Vec<(&'a str, TypeRef<'a>)>),
+    /// Record avro type
+    Record {
+        /// Name of the record
+        name: &'a str,
+        /// Names and values of the record fields
+        fields: Vec<(&'a str, TypeRef<'a>)>
+    },
+    /// Array avro type
+    Array(Vec<TypeRef<'a>>),
+    /// Enum avro type
+    Enum {
+        /// Name of the enum
+        name: &'a str,
+        /// Symbols of the enum
+        symbols: &'a [String],
+        /// Index of the symbol of this value
+        index: usize
+    },
+    /// Fixed avro type
+    Fixed(&'a [u8]),
+    /// Union avro type, holding the index of the union branch and the value of that branch
+    Union(usize, Box<TypeRef<'a>>)
+}
+
+/// Splits the next `len` bytes off the buffer
+fn take_slice<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8], AvroErr> {
+    let data = *buf;
+    if len > data.len() {
+        return Err(AvroErr::decode(format!("Expected {} bytes, found {}", len, data.len())));
+    }
+    let (head, tail) = data.split_at(len);
+    *buf = tail;
+    Ok(head)
+}
+
+/// Decodes a length prefixed byte slice out of the buffer, without copying it
+fn decode_bytes_ref<'a>(buf: &mut &'a [u8], limits: &DecoderLimits) -> Result<&'a [u8], AvroErr> {
+    let len = decode_len(buf, limits.max_length)?;
+    take_slice(buf, len as usize)
+}
+
+/// Decodes a string out of the buffer, without copying it
+fn decode_str_ref<'a>(buf: &mut &'a [u8], limits: &DecoderLimits) -> Result<&'a str, AvroErr> {
+    str::from_utf8(decode_bytes_ref(buf, limits)?).map_err(|e| AvroErr::decode(e.to_string()))
+}
+
+/// Decodes a string out of the reader
+pub(crate) fn decode_string<R: Read>(reader: &mut R, limits: &DecoderLimits) -> Result<String, AvroErr> {
+    let len = decode_len(reader, limits.max_length)?;
+    String::from_utf8(read_len(reader, len)?).map_err(|e| AvroErr::decode(e.to_string()))
+}
+
+/// Max no of items reserved up front when decoding an array or map, as item counts may be corrupt
+pub(crate) const RESERVE_LIMIT: usize = 1024;
+
+/// Decodes the item count of the next block of an array or map, which already holds `decoded` items,
+/// along with the size of the block in bytes if the writer gave it
+pub(crate) fn decode_block_header<R: Read>(reader: &mut R, decoded: usize, limits: &DecoderLimits) -> Result<(usize, Option<u64>), AvroErr> {
+    let count = i64::decode(reader)?;
+    // A negative count is followed by the size of the block in bytes, which allows skipping it
+    let (count, size) = if count < 0 {
+        let size = i64::decode(reader)?;
+        if size < 0 {
+            return Err(AvroErr::decode(format!("Block size {} is negative", size)));
+        }
+        let count = count.checked_neg().ok_or_else(|| AvroErr::decode(format!("Block count {} is out of range", count)))?;
+        (count, Some(size as u64))
+    } else {
+        (count, None)
+    };
+    let total = (decoded as u64).saturating_add(count as u64);
+    if total > limits.max_items {
+        return Err(AvroErr::limit("max_items", total, limits.max_items));
+    }
+    Ok((count as usize, size))
+}
+
+/// Adds the `count` items of a block of an array to `zero_width_items` if its items may be encoded
+/// in no bytes, given their minimum size, as a few bytes of data could otherwise ask for millions of them
+pub(crate) fn count_zero_width_items(item_size: u64, count: usize, limits: &DecoderLimits, zero_width_items: &mut u64) -> Result<(), AvroErr> {
+    if item_size == 0 {
+        *zero_width_items = zero_width_items.saturating_add(count as u64);
+        if *zero_width_items > limits.max_zero_width_items {
+            return Err(AvroErr::limit("max_zero_width_items", *zero_width_items, limits.max_zero_width_items));
+        }
+    }
+    Ok(())
+}
+
+/// Decodes the item count of the next block of an array or map, which already holds `decoded` items
+pub(crate) fn decode_block_count<R: Read>(reader: &mut R, decoded: usize, limits: &DecoderLimits) -> Result<usize, AvroErr> {
+    decode_block_header(reader, decoded, limits).map(|(count, _)| count)
+}
+
+/// Skips the given no of bytes without keeping them
+fn skip_len<R: Read>(reader: &mut R, len: u64) -> Result<(), AvroErr> {
+    let skipped = io::copy(&mut reader.take(len), &mut io::sink()).map_err(AvroErr::read)?;
+    if skipped < len {
+        return Err(AvroErr::decode("Unexpected end of data"));
+    }
+    Ok(())
+}
+
+/// Skips a variable length encoded int or long
+fn skip_var_len<R: Read>(reader: &mut R) -> Result<(), AvroErr> {
+    let mut buf = [0u8; 1];
+    for _ in 0..10 {
+        reader.read_exact(&mut buf).map_err(AvroErr::read)?;
+        if buf[0] & 0b1000_0000 == 0 {
+            return Ok(());
+        }
+    }
+    Err(AvroErr::decode("Failed decoding u64"))
+}
+
+impl<'a> TypeRef<'a> {
+    /// Decodes a value of the given schema out of the buffer, advancing the buffer past it. The
+    /// default `DecoderLimits` apply.
+    pub fn decode(schema: &'a CompiledSchema, buf: &mut &'a [u8]) -> Result<TypeRef<'a>, AvroErr> {
+        TypeRef::decode_with_limits(schema, &DecoderLimits::default(), buf)
+    }
+
+    /// Decodes a value of the given schema out of the buffer, failing with `AvroErr::LimitExceeded`
+    /// on data exceeding the given limits
+    pub fn decode_with_limits(schema: &'a CompiledSchema, limits: &DecoderLimits, buf: &mut &'a [u8]) -> Result<TypeRef<'a>, AvroErr> {
+        TypeRef::decode_limited(schema, schema.root(), limits, 0, &mut 0, buf)
+    }
+
+    /// Decodes a value of the schema node at the given index out of the buffer, within the default `DecoderLimits`
+    pub fn decode_node(schema: &'a CompiledSchema, node: usize, buf: &mut &'a [u8]) -> Result<TypeRef<'a>, AvroErr> {
+        TypeRef::decode_limited(schema, node, &DecoderLimits::default(), 0, &mut 0, buf)
+    }
+
+    /// Decodes a value of the schema node at the given index, nested `depth` values deep. Items of
+    /// arrays encoded in no bytes are added to `zero_width_items`, which may span several values.
+    pub(crate) fn decode_limited(schema: &'a CompiledSchema, node: usize, limits: &DecoderLimits, depth: usize,
+                                 zero_width_items: &mut u64, buf: &mut &'a [u8]) -> Result<TypeRef<'a>, AvroErr> {
+        if depth > limits.max_depth {
+            return Err(AvroErr::limit("max_depth", depth as u64, limits.max_depth as u64));
+        }
+        match *schema.node(node) {
+            SchemaNode::Null => Ok(TypeRef::Null),
+            SchemaNode::Boolean => bool::decode(buf).map(TypeRef::Bool),
+            SchemaNode::Int => i32::decode(buf).map(TypeRef::Int),
+            SchemaNode::Long => i64::decode(buf).map(TypeRef::Long),
+            SchemaNode::Float => f32::decode(buf).map(TypeRef::Float),
+            SchemaNode::Double => f64::decode(buf).map(TypeRef::Double),
+            SchemaNode::Bytes => decode_bytes_ref(buf, limits).map(TypeRef::Bytes),
+            SchemaNode::String => decode_str_ref(buf, limits).map(TypeRef::Str),
+            SchemaNode::Record { ref name, ref fields } => {
+                let mut values = Vec::with_capacity(fields.len());
+                for field in fields {
+                    let value = TypeRef::decode_limited(schema, field.node, limits, depth + 1, zero_width_items, buf).map_err(|e| e.within(&format!(".{}", field.name)))?;
+                    values.push((field.name.as_str(), value));
+                }
+                Ok(TypeRef::Record { name: name.as_str(), fields: values })
+            }
+            SchemaNode::Enum { ref name, ref symbols } => {
+                let index = i64::decode(buf)?;
+                if index < 0 || index as usize >= symbols.len() {
+                    return Err(AvroErr::decode(format!("Enum symbol {} does not exist", index)));
+                }
+                Ok(TypeRef::Enum { name: name.as_str(), symbols, index: index as usize })
+            }
+            SchemaNode::Array(items) => {
+                let mut arr = vec![];
+                loop {
+                    let count = decode_block_count(buf, arr.len(), limits)?;
+                    if count == 0 {
+                        return Ok(TypeRef::Array(arr));
+                    }
+                    count_zero_width_items(schema.min_size(items), count, limits, zero_width_items)?;
+                    for _ in 0..count {
+                        let item = TypeRef::decode_limited(schema, items, limits, depth + 1, zero_width_items, buf).map_err(|e| e.within(&format!("[{}]", arr.len())))?;
+                        arr.push(item);
+                    }
+                }
+            }
+            SchemaNode::Map(values) => {
+                let mut map = vec![];
+                loop {
+                    let count = decode_block_count(buf, map.len(), limits)?;
+                    if count == 0 {
+                        return Ok(TypeRef::Map(map));
+                    }
+                    for _ in 0..count {
+                        let key = decode_str_ref(buf, limits)?;
+                        let value = TypeRef::decode_limited(schema, values, limits, depth + 1, zero_width_items, buf).map_err(|e| e.within(&format!("[{:?}]", key)))?;
+                        map.push((key, value));
+                    }
+                }
+            }
+            SchemaNode::Fixed { size, .. } => {
+                check_length(size as u64, limits.max_length)?;
+                take_slice(buf, size).map(TypeRef::Fixed)
+            }
+            SchemaNode::Union(ref branches) => {
+                let idx = i64::decode(buf)?;
+                let branch = if idx < 0 { None } else { branches.get(idx as usize) };
+                let branch = branch.ok_or_else(|| AvroErr::decode(format!("Union branch {} does not exist", idx)))?;
+                let value = TypeRef::decode_limited(schema, *branch, limits, depth + 1, zero_width_items, buf).map_err(|e| e.within(&format!("[{}]", idx)))?;
+                Ok(TypeRef::Union(idx as usize, Box::new(value)))
+            }
+        }
+    }
+}
+
+impl<'a> TypeRef<'a> {
+    /// Converts the value into an owned `Type`, copying the data it borrows
+    pub fn into_owned(self) -> Type {
+        match self {
+            TypeRef::Null => Type::Null,
+            TypeRef::Bool(b) => Type::Bool(b),
+            TypeRef::Int(i) => Type::Int(i),
+            TypeRef::Long(l) => Type::Long(l),
+            TypeRef::Float(f) => Type::Float(f),
+            TypeRef::Double(d) => Type::Double(d),
+            TypeRef::Bytes(b) => Type::Bytes(b.to_vec()),
+            TypeRef::Str(s) => Type::Str(s.to_string()),
+            TypeRef::Map(entries) => {
+                Type::Map(entries.into_iter().map(|(k, v)| (k.to_string(), v.into_owned())).collect())
+            }
+            TypeRef::Record { name, fields } => {
+                let fields = fields.into_iter().map(|(name, value)| Field::new(name, value.into_owned())).collect();
+                Type::Record(Record::new(name, None, fields))
+            }
+            TypeRef::Array(items) => Type::Array(items.into_iter().map(TypeRef::into_owned).collect()),
+            TypeRef::Enum { name, symbols, index } => {
+                let mut enum_val = Enum::from_symbols(name, symbols.to_vec());
+                enum_val.set_value(&symbols[index]);
+                Type::Enum(enum_val)
+            }
+            TypeRef::Fixed(b) => Type::Fixed(b.to_vec()),
+            TypeRef::Union(idx, value) => Type::Union(idx, Box::new(value.into_owned()))
+        }
+    }
+
+    /// Encodes the value with the given schema, failing with `AvroErr::Validation` if it
+    /// does not conform to the schema. Part of the value may have been written on failure.
+    pub fn encode_with<W: Write>(&self, schema: &CompiledSchema, writer: &mut W) -> Result<usize, AvroErr> {
+        self.encode_node(schema, schema.root(), writer)
+    }
+
+    /// Encodes the value with the given schema, laying out arrays and maps as the options say
+    pub fn encode_with_options<W: Write>(&self, schema: &CompiledSchema, options: &EncoderOptions, writer: &mut W) -> Result<usize, AvroErr> {
+        self.encode_node_with(schema, schema.root(), options, writer)
+    }
+
+    /// Encodes the value with the schema node at the given index, with the default `EncoderOptions`
+    pub fn encode_node<W: Write>(&self, schema: &CompiledSchema, node: usize, writer: &mut W) -> Result<usize, AvroErr> {
+        self.encode_node_with(schema, node, &EncoderOptions::default(), writer)
+    }
+
+    /// Encodes the value with the schema node at the given index and the given options
+    fn encode_node_with<W: Write>(&self, schema: &CompiledSchema, node: usize, options: &EncoderOptions, writer: &mut W) -> Result<usize, AvroErr> {
+        let schema_node = schema.node(node);
+        match (schema_node, self) {
+            (SchemaNode::Null, TypeRef::Null) => Ok(0),
+            (SchemaNode::Boolean, TypeRef::Bool(b)) => encode_raw(writer, &[u8::from(*b)]),
+            (SchemaNode::Float, TypeRef::Float(f)) => encode_raw(writer, &f.to_bits().to_le_bytes()),
+            (SchemaNode::Double, TypeRef::Double(d)) => encode_raw(writer, &d.to_bits().to_le_bytes()),
+            (SchemaNode::Bytes, TypeRef::Bytes(bytes)) => encode_bytes(writer, bytes),
+            (SchemaNode::String, TypeRef::Str(s)) => encode_bytes(writer, s.as_bytes()),
+            (SchemaNode::Int, TypeRef::Int(val)) |
+            (SchemaNode::Long, TypeRef::Int(val)) => encode_var_len(writer, zig_zag(i64::from(*val))),
+            (SchemaNode::Long, TypeRef::Long(val)) => encode_var_len(writer, zig_zag(*val)),
+            (SchemaNode::Int, TypeRef::Long(val)) if *val as i32 as i64 == *val => encode_var_len(writer, zig_zag(*val)),
+            (SchemaNode::Record { fields, .. }, TypeRef::Record { fields: values, .. }) if fields.len() == values.len() => {
+                let mut total_len = 0;
+                for (field, (name, value)) in fields.iter().zip(values) {
+                    if field.name != *name {
+                        return Err(AvroErr::validation(format!("field {}", field.name), format!("field {}", name)));
+                    }
+                    total_len += value.encode_node_with(schema, field.node, options, writer)
+                        .map_err(|e| e.within(&format!(".{}", field.name)))?;
+                }
+                Ok(total_len)
+            }
+            (SchemaNode::Enum { symbols, .. }, TypeRef::Enum { symbols: value_symbols, index, .. }) => {
+                let idx = value_symbols.get(*index).and_then(|val| symbols.iter().position(|s| s == val))
+                    .ok_or_else(|| schema_mismatch(describe_value_ref(self), schema_node))?;
+                encode_var_len(writer, zig_zag(idx as i64))
+            }
+            (SchemaNode::Array(items), TypeRef::Array(arr)) => {
+                encode_blocks(writer, arr.len(), options, |i, mut w| {
+                    arr[i].encode_node_with(schema, *items, options, &mut w).map_err(|e| e.within(&format!("[{}]", i)))
+                })
+            }
+            (SchemaNode::Map(values), TypeRef::Map(entries)) => {
+                encode_blocks(writer, entries.len(), options, |i, mut w| {
+                    let (key, ref value) = entries[i];
+                    let key_len = encode_bytes(&mut w, key.as_bytes())?;
+                    let value_len = value.encode_node_with(schema, *values, options, &mut w)
+                        .map_err(|e| e.within(&format!("[{:?}]", key)))?;
+                    Ok(key_len + value_len)
+                })
+            }
+            (SchemaNode::Fixed { size, .. }, TypeRef::Fixed(bytes)) if bytes.len() == *size => encode_raw(writer, bytes),
+            (SchemaNode::Union(branches), TypeRef::Union(idx, value)) if *idx < branches.len() => {
+                let total_len = encode_var_len(writer, zig_zag(*idx as i64))?;
+                let value_len = value.encode_node_with(schema, branches[*idx], options, writer)
+                    .map_err(|e| e.within(&format!("[{}]", idx)))?;
+                Ok(total_len + value_len)
+            }
+            _ => Err(schema_mismatch(describe_value_ref(self), schema_node))
+        }
+    }
+}
+
+impl<'a> From<TypeRef<'a>> for Type {
+    fn from(value: TypeRef<'a>) -> Self {
+        value.into_owned()
+    }
+}
+
+impl<'a> Encoder for TypeRef<'a> {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<usize, AvroErr> {
+        match *self {
+            TypeRef::Null => Ok(0),
+            TypeRef::Bool(b) => Type::Bool(b).encode(writer),
+            TypeRef::Int(i) => Type::Int(i).encode(writer),
+            TypeRef::Long(l) => Type::Long(l).encode(writer),
+            TypeRef::Float(f) => Type::Float(f).encode(writer),
+            TypeRef::Double(d) => Type::Double(d).encode(writer),
+            TypeRef::Bytes(b) => encode_bytes(writer, b),
+            TypeRef::Str(s) => encode_bytes(writer, s.as_bytes()),
+            // Map entries are written in the order they are held, which for decoded
+            // values is the order they were read in.
+            TypeRef::Map(ref entries) => {
+                let mut total_len = 0;
+                if !entries.is_empty() {
+                    total_len += Type::Long(entries.len() as i64).encode(writer)?;
+                    for &(key, ref value) in entries {
+                        total_len += encode_bytes(writer, key.as_bytes())?;
+                        total_len += value.encode(writer)?;
+                    }
+                }
+                total_len += Type::Long(0).encode(writer)?;
+                Ok(total_len)
+            }
+            TypeRef::Record { ref fields, .. } => {
+                let mut total_len = 0;
+                for (_, value) in fields {
+                    total_len += value.encode(writer)?;
+                }
+                Ok(total_len)
+            }
+            TypeRef::Array(ref items) => {
+                let mut total_len = 0;
+                if !items.is_empty() {
+                    total_len += Type::Long(items.len() as i64).encode(writer)?;
+                    for item in items {
+                        total_len += item.encode(writer)?;
+                    }
+                }
+                total_len += Type::Long(0).encode(writer)?;
+                Ok(total_len)
+            }
+            TypeRef::Enum { index, .. } => Type::Long(index as i64).encode(writer),
+            TypeRef::Fixed(b) => {
+                writer.write_all(b)?;
+                Ok(b.len())
+            }
+            TypeRef::Union(idx, ref value) => {
+                let mut total_len = Type::Long(idx as i64).encode(writer)?;
+                total_len += value.encode(writer)?;
+                Ok(total_len)
+            }
         }
     }
 }
@@ -444,3 +1246,65 @@ fn test_str_encode_decode() {
     assert_eq!("foo".to_string(), v);
     assert_eq!(4, len);
 }
+
+#[test]
+fn test_multibyte_str_length_is_in_bytes() {
+    let mut v = vec![];
+    let len = Type::Str("日本".to_string()).encode(&mut v).unwrap();
+    assert_eq!(v, vec![0x0c, 0xe6, 0x97, 0xa5, 0xe6, 0x9c, 0xac]);
+    assert_eq!(7, len);
+    assert_eq!("日本".to_string(), String::decode(&mut v.as_slice()).unwrap());
+}
+
+#[test]
+fn test_map_encoding_is_sorted_by_key() {
+    let mut map = HashMap::new();
+    for key in &["zeta", "alpha", "mu", "beta"] {
+        map.insert(key.to_string(), Type::Int(key.len() as i32));
+    }
+    let mut v = vec![];
+    Type::Map(map).encode(&mut v).unwrap();
+    let mut expected = vec![];
+    Type::Long(4).encode(&mut expected).unwrap();
+    for key in &["alpha", "beta", "mu", "zeta"] {
+        key.to_string().encode(&mut expected).unwrap();
+        Type::Int(key.len() as i32).encode(&mut expected).unwrap();
+    }
+    Type::Long(0).encode(&mut expected).unwrap();
+    assert_eq!(expected, v);
+}
+
+#[test]
+fn test_type_ref_encode_decode() {
+    let schema = ::AvroSchema::from_str(r#"{"type": "record", "name": "rec", "fields": [
+        {"name": "name", "type": "string"},
+        {"name": "tags", "type": {"type": "map", "values": "bytes"}},
+        {"name": "suit", "type": {"type": "enum", "name": "suit", "symbols": ["CLUB", "SPADE"]}},
+        {"name": "scores", "type": {"type": "array", "items": ["null", "long"]}}
+    ]}"#).unwrap().compile().unwrap();
+    let symbols = vec!["CLUB".to_string(), "SPADE".to_string()];
+    let value = TypeRef::Record { name: "rec", fields: vec![
+        ("name", TypeRef::Str("ünïcödé")),
+        ("tags", TypeRef::Map(vec![("b", TypeRef::Bytes(b"x")), ("a", TypeRef::Bytes(b""))])),
+        ("suit", TypeRef::Enum { name: "suit", symbols: &symbols, index: 1 }),
+        ("scores", TypeRef::Array(vec![TypeRef::Union(1, Box::new(TypeRef::Long(-3))), TypeRef::Union(0, Box::new(TypeRef::Null))]))
+    ]};
+    let mut v = vec![];
+    let len = value.encode(&mut v).unwrap();
+    assert_eq!(len, v.len());
+    let mut buf = v.as_slice();
+    let decoded = TypeRef::decode(&schema, &mut buf).unwrap();
+    assert!(buf.is_empty());
+    assert_eq!(decoded, value);
+
+    let mut suit = Enum::from_symbols("suit", symbols.clone());
+    suit.set_value("SPADE");
+    match decoded.into_owned() {
+        Type::Record(rec) => {
+            assert_eq!(rec.fields[0].ty, Type::Str("ünïcödé".to_string()));
+            assert_eq!(rec.fields[1].ty.map_ref()["b"], Type::Bytes(b"x".to_vec()));
+            assert_eq!(rec.fields[2].ty, Type::Enum(suit));
+        }
+        other => panic!("Expected a record, found {:?}", other)
+    }
+}
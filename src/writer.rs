@@ -1,63 +1,132 @@
 //! This module declares a `DataWriter` instance which does actual writing of data
 //! through give avro schema
 
-use std::io::{Write, Read};
+use std::io::{Write, Read, BufReader, ErrorKind};
 use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
 
-use types::Type;
-use codec::{Decoder, Encoder};
-use rand::thread_rng;
-use rand::Rng;
+use types::{Type, TypeRef};
+use codec::{Decoder, DecoderLimits, Encoder, EncoderOptions};
+use rand::{thread_rng, Isaac64Rng, Rng, SeedableRng};
 use complex::Record;
 
-use schema::AvroSchema;
-use std::str;
+use schema::{parse_schema_tag, AvroSchema, CompiledSchema};
+use std::str::{self, FromStr};
 
-use crc::crc32;
 use byteorder::{BigEndian, WriteBytesExt};
 
 use snap::Encoder as SnapEncoder;
 use snap::Decoder as SnapDecoder;
+use snap::decompress_len;
 
 use errors::AvroErr;
+use pool::OrderedPool;
+use reader::Block;
 use std::io::Cursor;
 use std::mem;
 use std::path::Path;
 use serde_json::Value;
 use flate2::Compression;
 use flate2::write::DeflateEncoder;
-use std::fmt::Debug;
-use std::error::Error;
+use flate2::read::DeflateDecoder;
+use std::fmt::{self, Debug};
+#[cfg(feature = "async")]
+use std::task::Waker;
 
-const SYNC_MARKER_SIZE: usize = 16;
+pub(crate) const SYNC_MARKER_SIZE: usize = 16;
 const MAGIC_BYTES: [u8;4] = [b'O', b'b', b'j', 1 as u8];
 const CRC_CHECKSUM_LEN: usize = 4;
 
 fn get_crc_uncompressed(pre_comp_buf: &[u8]) -> Vec<u8> {
-	let crc_checksum = crc32::checksum_ieee(pre_comp_buf);
+	let crc_checksum = crc32fast::hash(pre_comp_buf);
 	let mut checksum_bytes = vec![];
 	checksum_bytes.write_u32::<BigEndian>(crc_checksum).unwrap();
 	checksum_bytes
 }
 
-fn compress_snappy(uncompressed_buffer: &[u8]) -> Vec<u8> {
+fn compress_snappy(uncompressed_buffer: &[u8]) -> Result<Vec<u8>, AvroErr> {
 	let mut snapper = SnapEncoder::new();
-	snapper.compress_vec(uncompressed_buffer).expect("Snappy: Failed to compress data")
+	snapper.compress_vec(uncompressed_buffer).map_err(|e| AvroErr::codec(Codec::Snappy, e.to_string()))
 }
 
-fn compress_deflate(uncompressed_buffer: &[u8]) -> Vec<u8> {
+fn compress_deflate(uncompressed_buffer: &[u8]) -> Result<Vec<u8>, AvroErr> {
 	let mut e = DeflateEncoder::new(Vec::new(), Compression::default());
-	e.write(uncompressed_buffer).unwrap();
-	e.finish().expect("Deflate: Failed to compress data")
+	e.write_all(uncompressed_buffer).map_err(|e| AvroErr::codec(Codec::Deflate, e.to_string()))?;
+	e.finish().map_err(|e| AvroErr::codec(Codec::Deflate, e.to_string()))
 }
 
-#[allow(dead_code)]
-fn decompress_snappy(compressed_buffer: &[u8]) -> Vec<u8> {
-	SnapDecoder::new().decompress_vec(compressed_buffer).unwrap()
+fn decompress_snappy(compressed_buffer: &[u8], max_size: u64) -> Result<Vec<u8>, AvroErr> {
+	// The decompressed buffer is allocated up front, so its size is checked first
+	let len = decompress_len(compressed_buffer).map_err(|e| AvroErr::codec(Codec::Snappy, e.to_string()))?;
+	check_block_size(len as u64, max_size)?;
+	SnapDecoder::new().decompress_vec(compressed_buffer).map_err(|e| AvroErr::codec(Codec::Snappy, e.to_string()))
+}
+
+fn decompress_deflate(compressed_buffer: &[u8], max_size: u64) -> Result<Vec<u8>, AvroErr> {
+	let mut decompressed = vec![];
+	DeflateDecoder::new(compressed_buffer).take(max_size.saturating_add(1)).read_to_end(&mut decompressed)
+		.map_err(|e| AvroErr::codec(Codec::Deflate, e.to_string()))?;
+	check_block_size(decompressed.len() as u64, max_size)?;
+	Ok(decompressed)
+}
+
+/// Checks the size of a block, before or after decompression, against the limit
+pub(crate) fn check_block_size(size: u64, max_size: u64) -> Result<(), AvroErr> {
+	if size > max_size {
+		return Err(AvroErr::limit("max_block_size", size, max_size));
+	}
+	Ok(())
+}
+
+/// Checks the no of records of a block against the limit on it
+pub(crate) fn check_block_records(count: u64, max_records: u64) -> Result<(), AvroErr> {
+	if count > max_records {
+		return Err(AvroErr::limit("max_block_records", count, max_records));
+	}
+	Ok(())
+}
+
+/// Frames the data of a block with its record count, size and the sync marker ending it
+pub(crate) fn write_block_frame<W: Write>(writer: &mut W, count: i64, block_data: &[u8], sync_marker: &SyncMarker) -> Result<(), AvroErr> {
+	Type::Long(count).encode(writer)?;
+	Type::Long(block_data.len() as i64).encode(writer)?;
+	writer.write_all(block_data)?;
+	sync_marker.encode(writer)?;
+	Ok(())
+}
+
+/// Reads a long from the start of a block, telling apart the data file ending in the middle of it
+fn read_block_long(buf: &mut &[u8], block_offset: u64) -> Result<i64, AvroErr> {
+	i64::decode(buf).map_err(|e| if buf.is_empty() { AvroErr::TruncatedBlock(block_offset) } else { e })
+}
+
+/// Splits the block at the start of `buf` into its record count and data, checking the sync
+/// marker ending it. Also gives back the no of bytes taken up by the block. Fails with
+/// `AvroErr::TruncatedBlock` if `buf` ends before the block does, and with `AvroErr::LimitExceeded`
+/// if the block has more records than `max_block_records` or is larger than `max_block_size`.
+pub(crate) fn split_block<'a>(buf: &'a [u8], sync_marker: &[u8], block_offset: u64, limits: &DecoderLimits) -> Result<(i64, &'a [u8], usize), AvroErr> {
+	let mut rest = buf;
+	let count = read_block_long(&mut rest, block_offset)?;
+	let size = read_block_long(&mut rest, block_offset)?;
+	if count < 0 || size < 0 {
+		return Err(AvroErr::decode(format!("Invalid block with {} records of {} bytes", count, size)));
+	}
+	check_block_records(count as u64, limits.max_block_records)?;
+	check_block_size(size as u64, limits.max_block_size)?;
+	let size = size as usize;
+	if rest.len() < size || rest.len() - size < SYNC_MARKER_SIZE {
+		return Err(AvroErr::TruncatedBlock(block_offset));
+	}
+	let (block_data, rest) = rest.split_at(size);
+	if rest[..SYNC_MARKER_SIZE] != *sync_marker {
+		error!("Possible data corruption! Sync markers do not match");
+		return Err(AvroErr::SyncMarkerMismatch(block_offset));
+	}
+	Ok((count, block_data, buf.len() - rest.len() + SYNC_MARKER_SIZE))
 }
 
 /// Compression codec to use before writing to data file.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Codec {
 	/// No compression
 	Null,
@@ -67,6 +136,75 @@ pub enum Codec {
 	Snappy
 }
 
+impl fmt::Display for Codec {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Codec::Null => write!(f, "null"),
+			Codec::Deflate => write!(f, "deflate"),
+			Codec::Snappy => write!(f, "snappy")
+		}
+	}
+}
+
+impl FromStr for Codec {
+	type Err = AvroErr;
+	fn from_str(codec: &str) -> Result<Self, Self::Err> {
+		match codec {
+			"null" => Ok(Codec::Null),
+			"deflate" => Ok(Codec::Deflate),
+			"snappy" => Ok(Codec::Snappy),
+			_ => Err(AvroErr::codec(codec, "Unsupported codec"))
+		}
+	}
+}
+
+impl Codec {
+	/// Compresses the serialized records of a block, giving back the block data to write
+	pub fn compress(&self, block_data: &[u8]) -> Result<Vec<u8>, AvroErr> {
+		match *self {
+			Codec::Null => Ok(block_data.to_vec()),
+			Codec::Snappy | Codec::Deflate => {
+				let mut compressed = if *self == Codec::Snappy {
+					compress_snappy(block_data)?
+				} else {
+					compress_deflate(block_data)?
+				};
+				compressed.extend_from_slice(&get_crc_uncompressed(block_data));
+				Ok(compressed)
+			}
+		}
+	}
+
+	/// Decompresses the data of a block that was written with this codec, within the default `DecoderLimits`
+	pub fn decompress(&self, block_data: &[u8]) -> Result<Vec<u8>, AvroErr> {
+		self.decompress_limited(block_data, DecoderLimits::default().max_block_size)
+	}
+
+	/// Decompresses the data of a block that was written with this codec, failing with
+	/// `AvroErr::LimitExceeded` if it decompresses to more than `max_size` bytes
+	pub fn decompress_limited(&self, block_data: &[u8], max_size: u64) -> Result<Vec<u8>, AvroErr> {
+		match *self {
+			Codec::Null => {
+				check_block_size(block_data.len() as u64, max_size)?;
+				Ok(block_data.to_vec())
+			}
+			Codec::Snappy => {
+				if block_data.len() < CRC_CHECKSUM_LEN {
+					return Err(AvroErr::codec(Codec::Snappy, "Block is too short to hold a checksum"));
+				}
+				let (compressed, checksum) = block_data.split_at(block_data.len() - CRC_CHECKSUM_LEN);
+				let decompressed = decompress_snappy(compressed, max_size)?;
+				if get_crc_uncompressed(&decompressed) != checksum {
+					return Err(AvroErr::codec(Codec::Snappy, "Block checksum does not match"));
+				}
+				Ok(decompressed)
+			}
+			// The deflate stream is self terminating, so any trailing bytes are ignored
+			Codec::Deflate => decompress_deflate(block_data, max_size)
+		}
+	}
+}
+
 /// Type tag acts as a sentinel which checks for the schema that is being written to the data file
 /// during write calls
 #[derive(Debug)]
@@ -101,6 +239,9 @@ pub enum SchemaTag {
 	Fixed
 }
 
+/// Worker threads compressing blocks, handed their record count and serialized records
+type Compressor = OrderedPool<(i64, Vec<u8>), (i64, Result<Vec<u8>, AvroErr>)>;
+
 /// `AvroWriter` provides api, to write data in an avro data file.
 pub struct AvroWriter {
 	/// The header is used to perform integrity checks on an avro data file and also contains schema information
@@ -112,16 +253,28 @@ pub struct AvroWriter {
 	/// In memory buffer for the avro data file which can be flushed to disk or returned
 	/// to user by take_datafile method
 	master_buffer: Cursor<Vec<u8>>,
-	/// This schema tag acts as a sentinel which checks for the schema that is being written to the data file
-	tag: SchemaTag,
+	/// The compiled schema, which written values are validated and encoded with
+	schema: CompiledSchema,
 	/// the codec to be used
-	codec: Codec
+	codec: Codec,
+	/// The data file that committed blocks are appended to, when created with `WriterBuilder::append_to`
+	datafile: Option<File>,
+	/// Worker threads compressing committed blocks, when enabled with `WriterBuilder::set_workers`
+	compressor: Option<Compressor>,
+	/// No of worker threads compressing blocks
+	workers: usize,
+	/// Options for encoding written values
+	encoder_options: EncoderOptions
 }
 
 /// Builder for AvroWriter, allows setting up schema and codecs
 pub struct WriterBuilder {
 	schema: AvroSchema,
-	codec: Codec
+	codec: Codec,
+	sync_marker: Option<SyncMarker>,
+	workers: usize,
+	encoder_options: EncoderOptions,
+	metadata: HashMap<String, Vec<u8>>
 }
 
 impl WriterBuilder {
@@ -130,9 +283,98 @@ impl WriterBuilder {
 		self.codec = codec;
 	}
 
+	/// Sets the sync marker used to delimit data blocks. If not set, a random
+	/// sync marker is generated when the writer is built.
+	pub fn set_sync_marker(&mut self, sync_marker: SyncMarker) {
+		self.sync_marker = Some(sync_marker);
+	}
+
+	/// Derives the sync marker from the given seed, so that writing identical data
+	/// produces byte-identical data files.
+	pub fn set_seed(&mut self, seed: u64) {
+		self.sync_marker = Some(SyncMarker::from_seed(seed));
+	}
+
+	/// Compresses committed blocks on the given number of worker threads, while the caller
+	/// goes on filling the next block. Blocks are still written in the order they were committed.
+	/// Zero, the default, compresses blocks on the caller's thread in `commit_block`.
+	pub fn set_workers(&mut self, workers: usize) {
+		self.workers = workers;
+	}
+
+	/// Sets the options that written values are encoded with. Setting `EncoderOptions::block_items`
+	/// writes arrays and maps in size-prefixed blocks, which readers can skip without decoding them.
+	pub fn set_encoder_options(&mut self, options: EncoderOptions) {
+		self.encoder_options = options;
+	}
+
+	/// Adds a key and value to the metadata of the data file header. Keys starting with `avro.` are
+	/// reserved for the schema and codec, and are rejected with `AvroErr::Validation`. Data files
+	/// appended to with `append_to` keep the metadata they were written with.
+	pub fn set_metadata<K: Into<String>>(&mut self, key: K, value: Vec<u8>) -> Result<(), AvroErr> {
+		let key = key.into();
+		if key.starts_with("avro.") {
+			return Err(AvroErr::validation("a metadata key outside the avro namespace", key));
+		}
+		self.metadata.insert(key, value);
+		Ok(())
+	}
+
 	/// creates an AvroWriter instance
 	pub fn build(self) -> Result<AvroWriter, AvroErr> {
-		AvroWriter::new(self.schema, self.codec)
+		let sync_marker = self.sync_marker.unwrap_or_else(|| gen_sync_marker(&mut thread_rng()));
+		let mut writer = AvroWriter::new(self.schema, self.codec, sync_marker, self.metadata)?;
+		writer.encoder_options = self.encoder_options;
+		writer.start_workers(self.workers);
+		Ok(writer)
+	}
+
+	/// Creates an AvroWriter which appends data blocks to an existing avro data file.
+	/// The codec and sync marker are taken from the header of the data file, and the schema
+	/// of this builder must match the one the data file was written with, failing with
+	/// `AvroErr::SchemaParse` otherwise.
+	/// If the file does not exist or is empty, it is created and a new header is written to it.
+	/// Written data reaches the file on calls to `AvroWriter::flush`.
+	pub fn append_to<P: AsRef<Path>>(self, path: P) -> Result<AvroWriter, AvroErr> {
+		let is_empty = match fs::metadata(&path) {
+			Ok(meta) => meta.len() == 0,
+			Err(ref e) if e.kind() == ErrorKind::NotFound => true,
+			Err(e) => return Err(e.into())
+		};
+		if is_empty {
+			let mut writer = self.build()?;
+			let mut datafile = OpenOptions::new().write(true).create(true).truncate(true).open(&path)?;
+			datafile.write_all(&writer.swap_buffer())?;
+			writer.datafile = Some(datafile);
+			return Ok(writer);
+		}
+		let header = Header::decode(&mut BufReader::new(File::open(&path)?))?;
+		let schema_json = match self.schema {
+			AvroSchema::Primitive(ref v) | AvroSchema::Complex(ref v) => v
+		};
+		let datafile_schema_json = match header.schema {
+			AvroSchema::Primitive(ref v) | AvroSchema::Complex(ref v) => v
+		};
+		if schema_json != datafile_schema_json {
+			return Err(AvroErr::schema(format!("Expected the schema of the data file {}, found {}", datafile_schema_json, schema_json)));
+		}
+		let codec = header.get_codec()?;
+		// Appending mode positions all writes at the current end of the file
+		let datafile = OpenOptions::new().append(true).open(&path)?;
+		let mut writer = AvroWriter {
+			schema: header.schema.compile()?,
+			header,
+			block_count: 0,
+			block_buffer: vec![],
+			master_buffer: Cursor::new(vec![]),
+			codec,
+			datafile: Some(datafile),
+			compressor: None,
+			workers: 0,
+			encoder_options: self.encoder_options
+		};
+		writer.start_workers(self.workers);
+		Ok(writer)
 	}
 }
 
@@ -140,20 +382,24 @@ impl AvroWriter {
 	/// Create a AvroWriter from a schema in a file
 	pub fn from_schema<P: AsRef<Path> + Debug>(schema: P) -> Result<WriterBuilder, AvroErr> {
 		let schema = AvroSchema::from_file(schema)?;
-		let writer_builder = WriterBuilder {
-			schema: schema,
-			codec: Codec::Null
-		};
-		Ok(writer_builder)
+		Ok(AvroWriter::with_schema(schema))
 	}
 	/// Create a DataWriter from a schema provided as string
 	pub fn from_str(schema: &str) -> Result<WriterBuilder, AvroErr> {
 		let schema = AvroSchema::from_str(schema)?;
-		let writer_builder = WriterBuilder {
-			schema: schema,
-			codec: Codec::Null
-		};
-		Ok(writer_builder)
+		Ok(AvroWriter::with_schema(schema))
+	}
+
+	/// Create a AvroWriter from a parsed schema, such as the one of a data file being read
+	pub fn with_schema(schema: AvroSchema) -> WriterBuilder {
+		WriterBuilder {
+			schema,
+			codec: Codec::Null,
+			sync_marker: None,
+			workers: 0,
+			encoder_options: EncoderOptions::default(),
+			metadata: HashMap::new()
+		}
 	}
 
 	/// Retrieves a reference to the avro schema
@@ -164,20 +410,25 @@ impl AvroWriter {
 	/// Creates a new `DataWriter` instance which can be
 	/// used to write data to the provided `Write` instance
 	/// It writes the avro data header and gets the buffer ready for incoming data writes 
-	fn new(schema: AvroSchema, codec: Codec) -> Result<Self, AvroErr> {
+	fn new(schema: AvroSchema, codec: Codec, sync_marker: SyncMarker, metadata: HashMap<String, Vec<u8>>) -> Result<Self, AvroErr> {
 		let mut master_buffer = Cursor::new(vec![]);
-		let sync_marker = SyncMarker(gen_sync_marker());
-		let mut header = Header::from_schema(&schema, sync_marker.clone());
+		let mut header = Header::from_schema(&schema, sync_marker);
 		header.append_codec(codec);
+		if let Type::Map(ref mut map) = header.metadata {
+			map.extend(metadata.into_iter().map(|(key, value)| (key, Type::Bytes(value))));
+		}
 		header.encode(&mut master_buffer)?;
-		let tag = schema.into();
 		let writer = AvroWriter {
+			schema: schema.compile()?,
 			header: header,
 			block_count: 0,
 			block_buffer: vec![],
 			master_buffer: master_buffer,
-			tag: tag,
-			codec: codec
+			codec: codec,
+			datafile: None,
+			compressor: None,
+			workers: 0,
+			encoder_options: EncoderOptions::default()
 		};
 		Ok(writer)
 	}
@@ -187,75 +438,193 @@ impl AvroWriter {
 	/// This can then be used to either send over RPC or flush to disk
 	pub fn take_datafile(&mut self) -> Result<Vec<u8>, AvroErr> {
 		self.commit_block()?;
+		self.drain_blocks(true)?;
 		let written_datafile = mem::replace(&mut self.master_buffer, Cursor::new(vec![]));
 		// Is there a reason for failure the second time ?
 		self.header.encode(&mut self.master_buffer).expect("Failed to re-encode header on new datafile");
 		Ok(written_datafile.into_inner())
 	}
 
+	/// Commits any pending data as a block and appends all committed blocks to the data file
+	/// this writer was opened on with `WriterBuilder::append_to`. Fails with `AvroErr::NotAppending`
+	/// for other writers, leaving the data written to them in place.
+	pub fn flush(&mut self) -> Result<(), AvroErr> {
+		if self.datafile.is_none() {
+			return Err(AvroErr::NotAppending);
+		}
+		self.commit_pending()?;
+		let blocks = self.try_swap_buffer()?;
+		if let Some(ref mut datafile) = self.datafile {
+			datafile.write_all(&blocks)?;
+			datafile.flush()?;
+		}
+		Ok(())
+	}
+
 	// TODO implement get past header
 	/// Commits the written blocks of data to the master buffer. Compression_happens at block level.
+	/// With worker threads enabled, the block is handed to them and reaches the master buffer
+	/// once it is compressed.
 	pub fn commit_block(&mut self) -> Result<(), AvroErr> {
-		Type::Long(self.block_count as i64).encode(&mut self.master_buffer)?;
-		match self.codec {
-			Codec::Null => {
-				Type::Long(self.block_buffer.len() as i64).encode(&mut self.master_buffer).unwrap();
-				self.master_buffer.write_all(&self.block_buffer).map_err(|_| AvroErr::AvroWriteErr)?;
-			}
-			Codec::Snappy => {
-				let checksum_bytes = get_crc_uncompressed(&self.block_buffer);
-				let compressed_data = compress_snappy(&self.block_buffer);
-				Type::Long((compressed_data.len() + CRC_CHECKSUM_LEN) as i64).encode(&mut self.master_buffer)?;
-				self.master_buffer.write_all(&*compressed_data).map_err(|_| AvroErr::AvroWriteErr)?;
-				self.master_buffer.write_all(&*checksum_bytes).map_err(|_| AvroErr::AvroWriteErr)?;
-			}
-			Codec::Deflate => {
-				let checksum_bytes = get_crc_uncompressed(&self.block_buffer);
-				let compressed_data = compress_deflate(&self.block_buffer);
-				Type::Long((compressed_data.len() + CRC_CHECKSUM_LEN) as i64).encode(&mut self.master_buffer)?;
-				self.master_buffer.write_all(&*compressed_data).map_err(|_| AvroErr::AvroWriteErr)?;
-				self.master_buffer.write_all(&*checksum_bytes).map_err(|_| AvroErr::AvroWriteErr)?;
-			}
-		}
-		self.header.sync_marker.encode(&mut self.master_buffer).map_err(|_| AvroErr::AvroWriteErr)?;
+		let count = self.block_count;
 		self.block_count = 0;
-		self.block_buffer.clear();
+		if let Some(ref mut compressor) = self.compressor {
+			compressor.submit((count, mem::take(&mut self.block_buffer)));
+		} else {
+			let block_data = self.codec.compress(&self.block_buffer);
+			self.block_buffer.clear();
+			let block_data = block_data?;
+			return self.write_block(count, &block_data);
+		}
+		self.drain_blocks(false)
+	}
+
+	/// Commits the current block, unless no records were written to it
+	pub(crate) fn commit_pending(&mut self) -> Result<(), AvroErr> {
+		if self.block_count > 0 {
+			self.commit_block()?;
+		}
 		Ok(())
 	}
 
-	/// Returns the in-memory buffer of written avro data
+	/// Returns the in-memory buffer of written avro data, waiting for the blocks being compressed.
+	/// If a block fails to be compressed, the error is only logged and the blocks after it are left
+	/// out of the returned data, so `try_swap_buffer` should be preferred.
 	pub fn swap_buffer(&mut self) -> Vec<u8> {
+		if let Err(e) = self.drain_blocks(true) {
+			error!("Failed to write compressed blocks: {}", e);
+		}
 		mem::replace(&mut self.master_buffer, Cursor::new(vec![])).into_inner()
 	}
 
+	/// Returns the in-memory buffer of written avro data, waiting for the blocks being compressed.
+	/// Fails if a block could not be compressed, leaving the blocks written before it in the buffer.
+	pub fn try_swap_buffer(&mut self) -> Result<Vec<u8>, AvroErr> {
+		self.drain_blocks(true)?;
+		Ok(mem::replace(&mut self.master_buffer, Cursor::new(vec![])).into_inner())
+	}
+
+	/// Returns the in-memory buffer of written avro data without waiting for the blocks being
+	/// compressed, along with the no of blocks left being compressed
+	#[cfg(feature = "async")]
+	pub(crate) fn take_compressed(&mut self) -> Result<(Vec<u8>, u64), AvroErr> {
+		let mut compressing = 0;
+		if let Some(ref mut compressor) = self.compressor {
+			while let Some((count, block_data)) = compressor.next_result(false) {
+				write_block_frame(&mut self.master_buffer, count, &block_data?, &self.header.sync_marker)?;
+			}
+			compressing = compressor.pending();
+		}
+		Ok((mem::replace(&mut self.master_buffer, Cursor::new(vec![])).into_inner(), compressing))
+	}
+
+	/// Wakes the given task once the worker threads finish compressing the next block
+	#[cfg(feature = "async")]
+	pub(crate) fn wake_on_compressed(&self, waker: &Waker) {
+		if let Some(ref compressor) = self.compressor {
+			compressor.wake_on_result(waker);
+		}
+	}
+
+	/// Returns the no of worker threads compressing blocks
+	#[cfg(feature = "async")]
+	pub(crate) fn workers(&self) -> usize {
+		self.workers
+	}
+
+	fn start_workers(&mut self, workers: usize) {
+		if workers > 0 {
+			let codec = self.codec;
+			self.compressor = Some(OrderedPool::new(workers, move |(count, data): (i64, Vec<u8>)| {
+				(count, codec.compress(&data))
+			}));
+			self.workers = workers;
+		}
+	}
+
+	/// Writes the blocks compressed by the worker threads to the master buffer, in the order they
+	/// were committed. Waits for all of them if `wait` is set, or else only for as many as needed
+	/// to bound the blocks held in memory.
+	fn drain_blocks(&mut self, wait: bool) -> Result<(), AvroErr> {
+		loop {
+			let block = match self.compressor {
+				Some(ref mut compressor) => {
+					let wait = wait || compressor.pending() > 2 * self.workers as u64;
+					let block = compressor.next_result(wait);
+					if block.is_none() && wait && compressor.pending() > 0 {
+						return Err(AvroErr::codec(self.codec, format!("Worker threads stopped with {} blocks left to compress", compressor.pending())));
+					}
+					block
+				}
+				None => None
+			};
+			match block {
+				Some((count, block_data)) => self.write_block(count, &block_data?)?,
+				None => return Ok(())
+			}
+		}
+	}
+
+	/// Frames a compressed block with its record count, size and the sync marker
+	fn write_block(&mut self, count: i64, block_data: &[u8]) -> Result<(), AvroErr> {
+		write_block_frame(&mut self.master_buffer, count, block_data, &self.header.sync_marker)
+	}
+
 	/// Writes the provided data to a block buffer. This write constitutes the content
 	/// of the current block. Clients can configure the number of items in the block.
 	/// Its only on calling commit_block that the block buffer gets written to master buffer
-	/// along with any compression(if specified).
-	pub fn write<T: Into<Type>>(&mut self, schema: T) -> Result<(), AvroErr> {
-		let schema = schema.into();
-		match (&schema, &self.tag) {
-			(&Type::Null, &SchemaTag::Null) |
-			(&Type::Bool(_), &SchemaTag::Boolean) |
-			(&Type::Int(_), &SchemaTag::Int) |
-			(&Type::Long(_), &SchemaTag::Long) |
-			// Int and Long are encoded in same way
-			(&Type::Long(_), &SchemaTag::Int) |
-			(&Type::Int(_), &SchemaTag::Long) |
-			(&Type::Float(_), &SchemaTag::Float) |
-			(&Type::Double(_), &SchemaTag::Double) |
-			(&Type::Bytes(_), &SchemaTag::Bytes) |
-			(&Type::Str(_), &SchemaTag::String) |
-			(&Type::Record(_), &SchemaTag::Record) |
-			(&Type::Enum(_), &SchemaTag::Enum) |
-			(&Type::Array(_), &SchemaTag::Array) |
-			(&Type::Map(_), &SchemaTag::Map) |
-			(&Type::Fixed, &SchemaTag::Fixed) => {}
-			_ => return Err(AvroErr::UnexpectedSchema)
-			// TODO implement union
+	/// along with any compression(if specified). Values which do not conform to the schema
+	/// are rejected with `AvroErr::Validation`, leaving the block as it was.
+	pub fn write<T: Into<Type>>(&mut self, value: T) -> Result<(), AvroErr> {
+		let value = value.into();
+		self.write_datum(|schema, options, buffer| value.encode_with_options(schema, options, buffer))
+	}
+
+	/// Writes a value to the block buffer without taking ownership of it, so that the same
+	/// value can be written again without cloning it.
+	pub fn write_type(&mut self, value: &Type) -> Result<(), AvroErr> {
+		self.write_datum(|schema, options, buffer| value.encode_with_options(schema, options, buffer))
+	}
+
+	/// Writes a borrowed value to the block buffer, the same way as `write` does for owned
+	/// values. This allows re-encoding decoded values without copying them.
+	pub fn write_ref(&mut self, value: &TypeRef) -> Result<(), AvroErr> {
+		self.write_datum(|schema, options, buffer| value.encode_with_options(schema, options, buffer))
+	}
+
+	/// Appends a block read from a data file written with the same schema, without decoding
+	/// its records. The block is framed with the sync marker of this writer, and its data is
+	/// copied as it is when it is compressed with the codec of this writer, or else recompressed.
+	/// Records written before are committed as a block of their own first.
+	pub fn append_block(&mut self, block: &Block) -> Result<(), AvroErr> {
+		self.commit_pending()?;
+		self.drain_blocks(true)?;
+		if block.codec == self.codec {
+			return self.write_block(block.count, &block.data);
+		}
+		let block_data = self.codec.compress(&block.decompress().map_err(|e| e.in_block(None, block.offset))?)?;
+		self.write_block(block.count, &block_data)
+	}
+
+	/// Writes a datum which is already encoded with the schema of this writer, such as one
+	/// taken from the block of another data file. The datum is not validated.
+	pub(crate) fn write_encoded(&mut self, datum: &[u8]) -> Result<(), AvroErr> {
+		self.write_datum(|_, _, buffer| {
+			buffer.extend_from_slice(datum);
+			Ok(datum.len())
+		})
+	}
+
+	/// Encodes a datum into the block buffer, committing the block once it is full
+	fn write_datum<F>(&mut self, encode: F) -> Result<(), AvroErr>
+		where F: FnOnce(&CompiledSchema, &EncoderOptions, &mut Vec<u8>) -> Result<usize, AvroErr> {
+		let block_len = self.block_buffer.len();
+		if let Err(e) = encode(&self.schema, &self.encoder_options, &mut self.block_buffer) {
+			// Drop the part of the rejected value that was encoded
+			self.block_buffer.truncate(block_len);
+			return Err(e);
 		}
 		self.block_count += 1;
-		schema.encode(&mut self.block_buffer)?;
 		// The approximate number of uncompressed bytes to write in each block
 		// TODO should be user configurable ?
 		// From java impl: https://github.com/apache/avro/blob/5c270dad2a281f4e70fb8c8a657d93a0cc72b7a8/lang/java/avro/src/main/java/org/apache/avro/file/DataFileWriter.java#L101
@@ -268,49 +637,25 @@ impl AvroWriter {
 	}
 }
 
-fn gen_sync_marker() -> Vec<u8> {
-    let mut vec = [0u8; SYNC_MARKER_SIZE];
-    thread_rng().fill_bytes(&mut vec[..]);
-    vec.to_vec()
-}
-
-fn get_schema_tag(s: &str, _parent_json: &Value) -> SchemaTag {
-	match s {
-		"null" => SchemaTag::Null,
-		"string" => SchemaTag::String,
-		"boolean" => SchemaTag::Boolean,
-		"double" => SchemaTag::Double,
-		"int" => SchemaTag::Int,
-		"float" => SchemaTag::Float,
-		"record" => {
-			// TODO use this when we are implementing reader
-			// let rec = Record::from_json(parent_json).unwrap();
-			// FromAvro::Record(rec)
-			SchemaTag::Record
-		}
-		"map" => {
-			// TODO as above
-			// let map_val_schema = parent_json.get("values").unwrap();
-			// FromAvro::Map(Box::new(get_schema_util(map_val_schema)))
-			SchemaTag::Map
-		}
-		_ => unimplemented!()
-	}
+fn gen_sync_marker<R: Rng>(rng: &mut R) -> SyncMarker {
+	let mut marker = [0u8; SYNC_MARKER_SIZE];
+	rng.fill_bytes(&mut marker[..]);
+	SyncMarker(marker.to_vec())
 }
 
 /// Recursive helper for parsing nested schemas
-pub fn get_schema_util(s: &Value) -> SchemaTag {
-	return match *s {
+pub fn get_schema_util(s: &Value) -> Result<SchemaTag, AvroErr> {
+	match *s {
 		Value::Object(ref obj) => {
 			if let Some(&Value::String(ref inner_str)) = obj.get("type") {
-				get_schema_tag(&inner_str, s)
+				parse_schema_tag(inner_str)
 			} else {
-				panic!("Expected type attribute to be as a Json string");
+				Err(AvroErr::schema("Expected type attribute to be a json string"))
 			}
 		}
-		Value::String(ref inner_str) => get_schema_tag(&inner_str, s),
-		Value::Array(_) => SchemaTag::Array,
-		ref other => unreachable!(format!("Invalid schema: {}", other))
+		Value::String(ref inner_str) => parse_schema_tag(inner_str),
+		Value::Array(_) => Ok(SchemaTag::Union),
+		ref other => Err(AvroErr::schema(format!("Invalid schema: {}", other)))
 	}
 }
 
@@ -346,14 +691,27 @@ impl Header {
 		}
 	}
 
-	fn append_codec(&mut self, codec: Codec) {
-		let codec = match codec {
-			Codec::Null => "null",
-			Codec::Deflate => "deflate",
-			Codec::Snappy => "snappy"
+	/// Retrieves the codec declared in the `avro.codec` metadata. A missing codec means `null`.
+	pub fn get_codec(&self) -> Result<Codec, AvroErr> {
+		let metadata = match self.metadata {
+			Type::Map(ref metadata) => metadata,
+			_ => return Err(AvroErr::decode("Header metadata is not a map"))
 		};
+		match metadata.get("avro.codec") {
+			None => Ok(Codec::Null),
+			Some(Type::Bytes(codec)) => match &codec[..] {
+				b"null" => Ok(Codec::Null),
+				b"deflate" => Ok(Codec::Deflate),
+				b"snappy" => Ok(Codec::Snappy),
+				_ => Err(AvroErr::codec(String::from_utf8_lossy(codec), "Unsupported codec"))
+			},
+			Some(_) => Err(AvroErr::decode("Codec in the header metadata is not bytes"))
+		}
+	}
+
+	fn append_codec(&mut self, codec: Codec) {
 		if let Type::Map(ref mut bmap) = self.metadata {
-			bmap.insert("avro.codec".to_string(), Type::Bytes(codec.as_bytes().to_vec()));
+			bmap.insert("avro.codec".to_string(), Type::Bytes(codec.to_string().into_bytes()));
 		} else {
 			debug!("Metadata type should be a Type::Map (HashMap<K, V>)");
 		}
@@ -363,7 +721,7 @@ impl Header {
 impl Encoder for Header {
 	fn encode<W: Write>(&self, writer: &mut W) -> Result<usize, AvroErr> {
 		let mut total_len = self.magic.len();
-		writer.write_all(&self.magic).unwrap();
+		writer.write_all(&self.magic)?;
 		total_len += self.metadata.encode(writer)?;
 		total_len += SYNC_MARKER_SIZE;
 		total_len += self.sync_marker.encode(writer)?;
@@ -371,36 +729,55 @@ impl Encoder for Header {
 	}
 }
 
-// impl Decoder for Header {
-// 	type Out=Self;
-// 	fn decode<R: Read>(reader: &mut R) -> Result<Self::Out, AvroErr> {
-// 		let mut magic_buf = [0u8;4];
-// 		reader.read_exact(&mut magic_buf[..]).unwrap();
-// 		let decoded_magic = str::from_utf8(&magic_buf[..]).unwrap();
-// 		if decoded_magic != "Obj\u{1}" {
-// 			return Err(AvroErr::UnexpectedData)
-// 		}
-// 		let map_block_count = i64::decode(reader)?;
-// 		let count = i64::from(map_block_count);
-// 		let mut map = HashMap::new();
-// 		for _ in 0..count as usize {
-// 			let key = String::decode(reader)?;
-// 			let a = String::from(key);
-// 			let val = Vec::<u8>::decode(reader)?;
-// 			map.insert(a, Type::Bytes(val));
-// 		}
-// 		let _zero_map_marker = i64::decode(reader)?;
-// 		let sync_marker = SyncMarker::decode(reader)?;
-// 		let magic_arr = [magic_buf[0], magic_buf[1], magic_buf[2], magic_buf[3]];
-// 		let header = Header {
-// 			magic: magic_arr,
-// 			metadata: Type::Map(map),
-// 			sync_marker: sync_marker,
-
-// 		};
-// 		Ok(header)
-// 	}
-// }
+impl Decoder for Header {
+	type Out=Self;
+	fn decode<R: Read>(reader: &mut R) -> Result<Self::Out, AvroErr> {
+		Header::decode_with_limits(reader, &DecoderLimits::default())
+	}
+
+	fn decode_with_limits<R: Read>(reader: &mut R, limits: &DecoderLimits) -> Result<Self::Out, AvroErr> {
+		let mut magic = [0u8; 4];
+		reader.read_exact(&mut magic[..]).map_err(AvroErr::read)?;
+		if magic != MAGIC_BYTES {
+			return Err(AvroErr::decode("Not an avro data file"));
+		}
+		let mut map = HashMap::new();
+		loop {
+			let mut count = i64::decode(reader)?;
+			if count == 0 {
+				break;
+			} else if count < 0 {
+				// A negative count is followed by the size of the block in bytes, which we don't need
+				count = count.checked_neg().ok_or_else(|| AvroErr::decode(format!("Invalid metadata block count {}", count)))?;
+				let _block_size = i64::decode(reader)?;
+			}
+			let total = (map.len() as u64).saturating_add(count as u64);
+			if total > limits.max_items {
+				return Err(AvroErr::limit("max_items", total, limits.max_items));
+			}
+			for _ in 0..count {
+				let key = String::decode_with_limits(reader, limits)?;
+				let val = Vec::<u8>::decode_with_limits(reader, limits)?;
+				map.insert(key, Type::Bytes(val));
+			}
+		}
+		let sync_marker = SyncMarker::decode(reader)?;
+		let schema = match map.get("avro.schema") {
+			Some(Type::Bytes(schema)) => {
+				let schema = str::from_utf8(schema).map_err(|_| AvroErr::decode("Schema in the header is not valid utf-8"))?;
+				AvroSchema::from_str(schema)?
+			}
+			_ => return Err(AvroErr::decode("Header has no schema"))
+		};
+		let header = Header {
+			magic,
+			metadata: Type::Map(map),
+			sync_marker,
+			schema
+		};
+		Ok(header)
+	}
+}
 
 /// A 16 byte sequence for keeping integrity checks when writing data blocks.
 /// Each data block is delimited with the `sync_marker` contained in the datafile header.
@@ -412,11 +789,28 @@ impl SyncMarker {
 	pub fn new() -> Self {
 		SyncMarker(vec![0u8;16])
 	}
+
+	/// Generates a sync marker deterministically from the given seed
+	pub fn from_seed(seed: u64) -> Self {
+		let mut rng = Isaac64Rng::from_seed(&[seed][..]);
+		gen_sync_marker(&mut rng)
+	}
+
+	/// Returns the 16 bytes of this sync marker
+	pub fn as_bytes(&self) -> &[u8] {
+		&self.0
+	}
+}
+
+impl From<[u8; SYNC_MARKER_SIZE]> for SyncMarker {
+	fn from(bytes: [u8; SYNC_MARKER_SIZE]) -> Self {
+		SyncMarker(bytes.to_vec())
+	}
 }
 
 impl Encoder for SyncMarker {
 	fn encode<W: Write>(&self, writer: &mut W) -> Result<usize, AvroErr> {
-		writer.write_all(&self.0).map_err(|_| AvroErr::AvroWriteErr)?;
+		writer.write_all(&self.0)?;
 		Ok(SYNC_MARKER_SIZE)
 	}
 }
@@ -425,7 +819,7 @@ impl Decoder for SyncMarker {
 	type Out=Self;
 	fn decode<R: Read>(reader: &mut R) -> Result<Self, AvroErr> {
 		let mut sync_marker = SyncMarker(vec![0u8;16]);
-		reader.read_exact(&mut sync_marker.0).map_err(|e| AvroErr::DecodeErr(e.description().to_string()))?;
+		reader.read_exact(&mut sync_marker.0).map_err(AvroErr::read)?;
 		Ok(sync_marker)
 	}
 }
@@ -0,0 +1,136 @@
+#![warn(unused_variables, unused_must_use)]
+
+extern crate ravro;
+
+use ravro::{AvroReader, AvroWriter, Type};
+use ravro::Codec;
+use ravro::errors::AvroErr;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+
+fn datafile_path(name: &str) -> String {
+	env::temp_dir().join(name).to_str().unwrap().to_string()
+}
+
+fn read_file(path: &str) -> Vec<u8> {
+	let mut buf = vec![];
+	OpenOptions::new().read(true).open(path).unwrap().read_to_end(&mut buf).unwrap();
+	buf
+}
+
+#[test]
+fn append_matches_single_writer_output() {
+	for (i, codec) in vec![Codec::Null, Codec::Snappy, Codec::Deflate].into_iter().enumerate() {
+		let datafile_name = datafile_path(&format!("ravro_append_encoded_{}.avro", i));
+		let mut builder = AvroWriter::from_schema("tests/schemas/long_schema.avsc").unwrap();
+		builder.set_codec(codec);
+		builder.set_seed(11);
+		let mut data_writer = builder.build().unwrap();
+		data_writer.write(1i64).unwrap();
+		data_writer.write(2i64).unwrap();
+		let mut datafile = OpenOptions::new().truncate(true).write(true).create(true).open(&datafile_name).unwrap();
+		datafile.write_all(&data_writer.take_datafile().unwrap()).unwrap();
+
+		// Appending picks up codec and sync marker from the data file header
+		let builder = AvroWriter::from_schema("tests/schemas/long_schema.avsc").unwrap();
+		let mut appender = builder.append_to(&datafile_name).unwrap();
+		appender.write(3i64).unwrap();
+		appender.write(4i64).unwrap();
+		appender.flush().unwrap();
+
+		let mut builder = AvroWriter::from_schema("tests/schemas/long_schema.avsc").unwrap();
+		builder.set_codec(codec);
+		builder.set_seed(11);
+		let mut expected_writer = builder.build().unwrap();
+		expected_writer.write(1i64).unwrap();
+		expected_writer.write(2i64).unwrap();
+		expected_writer.commit_block().unwrap();
+		expected_writer.write(3i64).unwrap();
+		expected_writer.write(4i64).unwrap();
+		assert_eq!(expected_writer.take_datafile().unwrap(), read_file(&datafile_name));
+	}
+}
+
+#[test]
+fn append_creates_missing_datafile() {
+	let datafile_name = &datafile_path("ravro_append_created.avro");
+	let _ = fs::remove_file(datafile_name);
+	let mut builder = AvroWriter::from_schema("tests/schemas/string_schema.avsc").unwrap();
+	builder.set_seed(3);
+	let mut appender = builder.append_to(datafile_name).unwrap();
+	appender.write("abcd".to_string()).unwrap();
+	appender.flush().unwrap();
+
+	let mut builder = AvroWriter::from_schema("tests/schemas/string_schema.avsc").unwrap();
+	builder.set_seed(3);
+	let mut expected_writer = builder.build().unwrap();
+	expected_writer.write("abcd".to_string()).unwrap();
+	assert_eq!(expected_writer.take_datafile().unwrap(), read_file(datafile_name));
+}
+
+#[test]
+fn flush_requires_a_writer_opened_for_appending() {
+	let mut data_writer = AvroWriter::from_schema("tests/schemas/long_schema.avsc").unwrap().build().unwrap();
+	data_writer.write(1i64).unwrap();
+	data_writer.commit_block().unwrap();
+	data_writer.write(2i64).unwrap();
+	match data_writer.flush() {
+		Err(AvroErr::NotAppending) => {}
+		other => panic!("Expected flush to fail, found {:?}", other)
+	}
+	// The header and the records written so far are kept
+	let datafile = data_writer.take_datafile().unwrap();
+	let records: Vec<Type> = AvroReader::new(&datafile[..]).unwrap().map(|r| r.unwrap()).collect();
+	assert_eq!(records, vec![Type::Long(1), Type::Long(2)]);
+}
+
+#[test]
+fn append_rejects_different_schema() {
+	let datafile_name = &datafile_path("ravro_append_schema_mismatch.avro");
+	let mut data_writer = AvroWriter::from_schema("tests/schemas/long_schema.avsc").unwrap().build().unwrap();
+	data_writer.write(1i64).unwrap();
+	let mut datafile = OpenOptions::new().truncate(true).write(true).create(true).open(datafile_name).unwrap();
+	datafile.write_all(&data_writer.take_datafile().unwrap()).unwrap();
+
+	let builder = AvroWriter::from_schema("tests/schemas/string_schema.avsc").unwrap();
+	match builder.append_to(datafile_name) {
+		Err(AvroErr::SchemaParse { ref reason, .. }) => assert!(reason.starts_with("Expected the schema of the data file")),
+		other => panic!("Expected a schema error, found {:?}", other.map(|_| ()))
+	}
+}
+
+#[test]
+fn append_block_copies_or_recompresses_blocks() {
+	let mut source = AvroWriter::from_schema("tests/schemas/string_schema.avsc").unwrap();
+	source.set_codec(Codec::Snappy);
+	let mut source = source.build().unwrap();
+	source.write("abcd".to_string()).unwrap();
+	source.commit_block().unwrap();
+	source.write("efgh".to_string()).unwrap();
+	source.write("ijkl".to_string()).unwrap();
+	let source = source.take_datafile().unwrap();
+	let blocks: Vec<_> = AvroReader::new(&source[..]).unwrap().iter_block().map(|b| b.unwrap()).collect();
+	assert_eq!(blocks.len(), 2);
+
+	for codec in vec![Codec::Snappy, Codec::Null, Codec::Deflate] {
+		let mut builder = AvroWriter::from_schema("tests/schemas/string_schema.avsc").unwrap();
+		builder.set_codec(codec);
+		let mut writer = builder.build().unwrap();
+		writer.write("first".to_string()).unwrap();
+		for block in &blocks {
+			writer.append_block(block).unwrap();
+		}
+		let datafile = writer.take_datafile().unwrap();
+		let copied: Vec<_> = AvroReader::new(&datafile[..]).unwrap().iter_block().map(|b| b.unwrap()).collect();
+		// The written record makes a block of its own, and the datafile ends with an empty block
+		assert_eq!(copied.iter().map(|b| b.count).collect::<Vec<_>>(), vec![1, 1, 2, 0]);
+		if codec == Codec::Snappy {
+			assert_eq!(copied[1].data, blocks[0].data);
+			assert_eq!(copied[2].data, blocks[1].data);
+		}
+		let records: Vec<Type> = AvroReader::new(&datafile[..]).unwrap().map(|r| r.unwrap()).collect();
+		let expected: Vec<Type> = vec!["first", "abcd", "efgh", "ijkl"].into_iter().map(|s| Type::Str(s.to_string())).collect();
+		assert_eq!(records, expected);
+	}
+}
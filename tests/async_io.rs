@@ -0,0 +1,245 @@
+#![cfg(feature = "async")]
+#![warn(unused_variables, unused_must_use)]
+
+extern crate futures;
+extern crate ravro;
+extern crate tokio;
+
+use futures::executor::block_on;
+use futures::future::poll_fn;
+use futures::{Sink, SinkExt, StreamExt};
+use ravro::async_io::{AsyncAvroReader, AsyncAvroWriter};
+use ravro::errors::AvroErr;
+use ravro::{AvroReader, AvroWriter, Codec, DecoderLimits, Type};
+use std::io::{self, Cursor};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Hands out a few bytes on every other read, and asks to be polled again in between
+struct Trickle {
+	data: Vec<u8>,
+	position: usize,
+	ready: bool
+}
+
+impl Trickle {
+	fn new(data: Vec<u8>) -> Self {
+		Trickle { data, position: 0, ready: false }
+	}
+}
+
+impl AsyncRead for Trickle {
+	fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context, buf: &mut ReadBuf) -> Poll<io::Result<()>> {
+		self.ready = !self.ready;
+		if !self.ready {
+			cx.waker().wake_by_ref();
+			return Poll::Pending;
+		}
+		let end = (self.position + 7).min(self.data.len()).min(self.position + buf.remaining());
+		buf.put_slice(&self.data[self.position..end]);
+		self.position = end;
+		Poll::Ready(Ok(()))
+	}
+}
+
+/// Accepts a few bytes on every other write, and asks to be polled again in between
+struct Drip {
+	data: Vec<u8>,
+	ready: bool
+}
+
+impl AsyncWrite for Drip {
+	fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+		self.ready = !self.ready;
+		if !self.ready {
+			cx.waker().wake_by_ref();
+			return Poll::Pending;
+		}
+		let len = buf.len().min(5);
+		self.data.extend_from_slice(&buf[..len]);
+		Poll::Ready(Ok(len))
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+		Poll::Ready(Ok(()))
+	}
+
+	fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+		Poll::Ready(Ok(()))
+	}
+}
+
+/// Hands out the given data followed by as many zeros as asked for, and fails once it is read
+/// past `max_len` bytes
+struct Endless {
+	data: Vec<u8>,
+	position: usize,
+	max_len: usize
+}
+
+impl AsyncRead for Endless {
+	fn poll_read(mut self: Pin<&mut Self>, _cx: &mut Context, buf: &mut ReadBuf) -> Poll<io::Result<()>> {
+		assert!(self.position <= self.max_len, "Read {} bytes, past {}", self.position, self.max_len);
+		let len = buf.remaining();
+		for _ in 0..len {
+			let byte = self.data.get(self.position).cloned().unwrap_or(0);
+			buf.put_slice(&[byte]);
+			self.position += 1;
+		}
+		Poll::Ready(Ok(()))
+	}
+}
+
+fn long_datafile(codec: Codec) -> Vec<u8> {
+	let mut builder = AvroWriter::from_schema("tests/schemas/long_schema.avsc").unwrap();
+	builder.set_codec(codec);
+	builder.set_seed(3);
+	let mut data_writer = builder.build().unwrap();
+	for i in 0..200i64 {
+		data_writer.write(i * 7).unwrap();
+		if i % 50 == 49 {
+			data_writer.commit_block().unwrap();
+		}
+	}
+	data_writer.take_datafile().unwrap()
+}
+
+#[test]
+fn async_reader_streams_records() {
+	for codec in vec![Codec::Null, Codec::Snappy, Codec::Deflate].into_iter() {
+		let reader = AsyncAvroReader::new(Trickle::new(long_datafile(codec)));
+		let records: Vec<Type> = block_on(reader.map(|r| r.unwrap()).collect());
+		let expected: Vec<Type> = (0..200).map(|i| Type::Long(i * 7)).collect();
+		assert_eq!(records, expected);
+	}
+}
+
+#[test]
+fn async_reader_reads_header_lazily() {
+	let datafile = long_datafile(Codec::Snappy);
+	let mut reader = AsyncAvroReader::new(&datafile[..]);
+	assert!(reader.header().is_none());
+	let first = block_on(reader.next()).unwrap().unwrap();
+	assert_eq!(first, Type::Long(0));
+	assert_eq!(reader.header().unwrap().get_codec().unwrap(), Codec::Snappy);
+}
+
+#[test]
+fn async_reader_reports_truncated_data_file() {
+	let mut datafile = long_datafile(Codec::Null);
+	let len = datafile.len();
+	// Drops the sync marker of the empty block ending the data file and a few bytes of the last block of records
+	datafile.truncate(len - 30);
+	let reader = AsyncAvroReader::new(Trickle::new(datafile));
+	let results: Vec<_> = block_on(reader.collect());
+	assert_eq!(results.len(), 151);
+	assert!(results[..150].iter().all(|r| r.is_ok()));
+	match results[150] {
+		Err(AvroErr::TruncatedBlock(_)) => (),
+		ref other => panic!("Expected a truncated block, found {:?}", other)
+	}
+}
+
+#[test]
+fn async_reader_rejects_bad_magic_without_reading_on() {
+	let reader = AsyncAvroReader::new(Endless { data: b"Obj\x02".to_vec(), position: 0, max_len: 8 * 1024 });
+	let results: Vec<_> = block_on(reader.collect());
+	assert_eq!(results.len(), 1);
+	match results[0] {
+		Err(AvroErr::Decode { .. }) => (),
+		ref other => panic!("Expected a decoding error, found {:?}", other)
+	}
+}
+
+#[test]
+fn async_reader_bounds_the_buffered_header() {
+	// A metadata key of 2^20 bytes, which goes on and on
+	let header = vec![b'O', b'b', b'j', 1, 2, 0x80, 0x80, 0x80, 0x01];
+	let mut reader = AsyncAvroReader::new(Endless { data: header, position: 0, max_len: 64 * 1024 });
+	reader.set_limits(DecoderLimits { max_block_size: 32 * 1024, ..DecoderLimits::default() });
+	let results: Vec<_> = block_on(reader.collect());
+	assert_eq!(results.len(), 1);
+	match results[0] {
+		Err(AvroErr::LimitExceeded { limit, .. }) => assert_eq!(limit, "max_block_size"),
+		ref other => panic!("Expected the header to exceed the block size limit, found {:?}", other)
+	}
+}
+
+#[test]
+fn async_writer_with_workers_writes_all_blocks() {
+	for codec in vec![Codec::Snappy, Codec::Deflate].into_iter() {
+		let mut builder = AvroWriter::from_schema("tests/schemas/long_schema.avsc").unwrap();
+		builder.set_codec(codec);
+		builder.set_workers(2);
+		let mut writer = AsyncAvroWriter::new(builder, Drip { data: vec![], ready: false }).unwrap();
+		for i in 0..2000i64 {
+			block_on(writer.feed(i)).unwrap();
+			if i % 50 == 49 {
+				block_on(SinkExt::<Type>::flush(&mut writer)).unwrap();
+			}
+		}
+		block_on(SinkExt::<Type>::close(&mut writer)).unwrap();
+		let reader = AvroReader::new(Cursor::new(writer.into_inner().data)).unwrap();
+		let records: Vec<Type> = reader.map(|r| r.unwrap()).collect();
+		assert_eq!(records, (0..2000).map(Type::Long).collect::<Vec<_>>());
+	}
+}
+
+#[test]
+fn async_writer_sleeps_while_blocks_are_compressing() {
+	let mut builder = AvroWriter::from_schema("tests/schemas/long_schema.avsc").unwrap();
+	builder.set_codec(Codec::Deflate);
+	builder.set_workers(1);
+	let mut writer = AsyncAvroWriter::new(builder, vec![]).unwrap();
+	for i in 0..500_000i64 {
+		writer.write(i * 7919).unwrap();
+	}
+	let mut polls = 0;
+	block_on(poll_fn(|cx| {
+		polls += 1;
+		Sink::<Type>::poll_close(Pin::new(&mut writer), cx)
+	})).unwrap();
+	// Polled once to hand the block to the workers, and once more when they wake the task
+	assert!(polls <= 3, "Polled {} times while the block was compressing", polls);
+	let reader = AvroReader::new(Cursor::new(writer.into_inner())).unwrap();
+	assert_eq!(reader.count(), 500_000);
+}
+
+#[test]
+fn async_writer_matches_blocking_writer() {
+	for codec in vec![Codec::Null, Codec::Snappy, Codec::Deflate].into_iter() {
+		let mut builder = AvroWriter::from_schema("tests/schemas/long_schema.avsc").unwrap();
+		builder.set_codec(codec);
+		builder.set_seed(3);
+		let mut writer = AsyncAvroWriter::new(builder, Drip { data: vec![], ready: false }).unwrap();
+		for i in 0..200i64 {
+			block_on(writer.feed(i * 7)).unwrap();
+			if i % 50 == 49 {
+				block_on(SinkExt::<Type>::flush(&mut writer)).unwrap();
+			}
+		}
+		block_on(SinkExt::<Type>::close(&mut writer)).unwrap();
+		let written = writer.into_inner().data;
+
+		// Taking the data file from the blocking writer also commits an empty block
+		let mut expected = long_datafile(codec);
+		let empty_block_len = 2 + codec.compress(&[]).unwrap().len() + 16;
+		let len = expected.len();
+		expected.truncate(len - empty_block_len);
+		assert_eq!(written, expected);
+	}
+}
+
+#[test]
+fn async_writer_flushes_pending_records_on_close() {
+	let builder = AvroWriter::from_schema("tests/schemas/long_schema.avsc").unwrap();
+	let mut writer = AsyncAvroWriter::new(builder, vec![]).unwrap();
+	for i in 0..10i64 {
+		writer.write(i).unwrap();
+	}
+	block_on(SinkExt::<Type>::close(&mut writer)).unwrap();
+	let reader = AvroReader::new(Cursor::new(writer.into_inner())).unwrap();
+	let records: Vec<Type> = reader.map(|r| r.unwrap()).collect();
+	assert_eq!(records, (0..10).map(Type::Long).collect::<Vec<_>>());
+}
@@ -0,0 +1,70 @@
+#![warn(unused_variables, unused_must_use)]
+
+extern crate ravro;
+
+mod common;
+
+use common::test_writer;
+use ravro::{AvroReader, Codec};
+use std::io::Cursor;
+
+fn blocked_datafile(codec: Codec) -> Vec<u8> {
+	let mut data_writer = test_writer("tests/schemas/string_schema.avsc", codec);
+	for i in 0..1000 {
+		data_writer.write(format!("record number {}", i)).unwrap();
+		if i % 300 == 299 {
+			data_writer.commit_block().unwrap();
+		}
+	}
+	data_writer.take_datafile().unwrap()
+}
+
+#[test]
+fn iterate_blocks() {
+	for codec in vec![Codec::Null, Codec::Snappy, Codec::Deflate].into_iter() {
+		let datafile = blocked_datafile(codec);
+		let reader = AvroReader::new(Cursor::new(datafile.clone())).unwrap();
+		let blocks: Vec<_> = reader.iter_block().map(|b| b.unwrap()).collect();
+		assert_eq!(blocks.iter().map(|b| b.count).collect::<Vec<_>>(), vec![300, 300, 300, 100]);
+		for block in &blocks {
+			assert_eq!(block.codec, codec);
+			// The raw block data follows the record count and byte size of the block
+			let raw = &datafile[block.offset as usize..];
+			let data_pos = raw.windows(block.data.len()).position(|w| w == &block.data[..]).unwrap();
+			assert!(data_pos <= 20);
+			let decompressed = block.decompress().unwrap();
+			match codec {
+				Codec::Null => assert_eq!(decompressed, block.data),
+				_ => assert!(decompressed.len() > block.compressed_size())
+			}
+		}
+	}
+}
+
+#[test]
+fn blocks_and_records_agree() {
+	let datafile = blocked_datafile(Codec::Snappy);
+	let mut reader = AvroReader::new(Cursor::new(datafile.clone())).unwrap();
+	let mut offsets = vec![];
+	while let Some(record) = reader.next() {
+		record.unwrap();
+		if offsets.last() != Some(&reader.previous_sync()) {
+			offsets.push(reader.previous_sync());
+		}
+	}
+	let reader = AvroReader::new(Cursor::new(datafile)).unwrap();
+	let block_offsets: Vec<u64> = reader.iter_block().map(|b| b.unwrap().offset).collect();
+	assert_eq!(offsets, block_offsets);
+}
+
+#[test]
+fn block_with_bad_sync_marker_is_an_error() {
+	let mut datafile = blocked_datafile(Codec::Null);
+	let last = datafile.len() - 1;
+	datafile[last] ^= 0xFF;
+	let reader = AvroReader::new(Cursor::new(datafile)).unwrap();
+	let blocks: Vec<_> = reader.iter_block().collect();
+	assert_eq!(blocks.len(), 4);
+	assert!(blocks[..3].iter().all(|b| b.is_ok()));
+	assert!(blocks[3].is_err());
+}
@@ -0,0 +1,112 @@
+#![warn(unused_variables, unused_must_use)]
+
+extern crate ravro;
+
+mod common;
+
+use common::test_writer;
+use ravro::check::{check, check_file};
+use ravro::errors::AvroErr;
+use ravro::{AvroReader, Codec};
+use std::io::Cursor;
+
+// Writes 500 booleans in blocks of 100 records, followed by an empty block, returning the data
+// file and block offsets
+fn blocked_datafile(codec: Codec) -> (Vec<u8>, Vec<u64>) {
+	let mut data_writer = test_writer("tests/schemas/bool_schema.avsc", codec);
+	for i in 0..500 {
+		data_writer.write(i % 3 == 0).unwrap();
+		if i % 100 == 99 {
+			data_writer.commit_block().unwrap();
+		}
+	}
+	let datafile = data_writer.take_datafile().unwrap();
+	let offsets = AvroReader::new(Cursor::new(datafile.clone())).unwrap()
+		.iter_block().map(|b| b.unwrap().offset).collect();
+	(datafile, offsets)
+}
+
+#[test]
+fn intact_data_files_pass() {
+	for codec in vec![Codec::Null, Codec::Deflate, Codec::Snappy] {
+		let (datafile, offsets) = blocked_datafile(codec);
+		let report = check(&datafile[..]);
+		assert!(report.is_ok(), "{:?}", report);
+		assert_eq!(report.codec, Some(codec));
+		assert_eq!(report.records(), 500);
+		assert_eq!(report.blocks.iter().map(|block| block.offset).collect::<Vec<_>>(), offsets);
+		assert!(report.blocks.iter().all(|block| block.decoded == block.count));
+	}
+}
+
+#[test]
+fn corrupt_blocks_are_reported_and_skipped() {
+	let (mut datafile, offsets) = blocked_datafile(Codec::Snappy);
+	// Damage the compressed data of the third block, which fails its checksum
+	let pos = offsets[2] as usize + 10;
+	datafile[pos] ^= 0xff;
+	let report = check(&datafile[..]);
+	assert!(!report.is_ok());
+	assert!(report.fatal.is_none());
+	assert_eq!(report.blocks.len(), 6);
+	let failed = report.failed_blocks();
+	assert_eq!(failed.len(), 1);
+	assert_eq!(failed[0].index, 2);
+	match failed[0].error {
+		Some(AvroErr::Codec { ref position, .. }) => assert_eq!((position.block, position.offset), (Some(2), Some(offsets[2]))),
+		ref other => panic!("Expected a codec error, found {:?}", other)
+	}
+}
+
+#[test]
+fn records_must_decode_and_fill_their_block() {
+	let (datafile, offsets) = blocked_datafile(Codec::Null);
+	// The block starts with its count and size, of two bytes each, followed by the booleans
+	let mut invalid_record = datafile.clone();
+	invalid_record[offsets[1] as usize + 4 + 7] = 5;
+	let report = check(&invalid_record[..]);
+	let failed = report.failed_blocks();
+	assert_eq!(failed.len(), 1);
+	assert_eq!((failed[0].index, failed[0].decoded), (1, 7));
+	match failed[0].error {
+		Some(AvroErr::Decode { .. }) => (),
+		ref other => panic!("Expected a decoding error, found {:?}", other)
+	}
+
+	// A count of 99 instead of 100 leaves a byte over
+	let mut short_count = datafile.clone();
+	short_count[offsets[3] as usize] -= 2;
+	let report = check(&short_count[..]);
+	let failed = report.failed_blocks();
+	assert_eq!(failed.len(), 1);
+	assert_eq!((failed[0].index, failed[0].decoded), (3, 99));
+	let message = failed[0].error.as_ref().unwrap().to_string();
+	assert!(message.ends_with("1 bytes are left after the 99 records of the block"), "{}", message);
+	assert_eq!(report.records(), 499);
+}
+
+#[test]
+fn broken_structure_stops_the_check() {
+	let (datafile, offsets) = blocked_datafile(Codec::Deflate);
+	let report = check(&datafile[..datafile.len() - 3]);
+	assert_eq!(report.blocks.len(), 5);
+	assert!(report.blocks.iter().all(|block| block.error.is_none()));
+	match report.fatal {
+		Some(AvroErr::TruncatedBlock(offset)) => assert_eq!(offset, offsets[5]),
+		ref other => panic!("Expected a truncated block, found {:?}", other)
+	}
+
+	let mut bad_sync = datafile.clone();
+	bad_sync[offsets[2] as usize - 1] ^= 0xff;
+	let report = check(&bad_sync[..]);
+	assert_eq!(report.blocks.len(), 1);
+	match report.fatal {
+		Some(AvroErr::SyncMarkerMismatch(offset)) => assert_eq!(offset, offsets[1]),
+		ref other => panic!("Expected a sync marker mismatch, found {:?}", other)
+	}
+
+	let report = check(&b"Obj\x02"[..]);
+	assert!(report.codec.is_none() && report.blocks.is_empty());
+	assert!(report.fatal.is_some());
+	assert!(!check_file("tests/missing.avro").is_ok());
+}
@@ -0,0 +1,232 @@
+#![cfg(feature = "cli")]
+#![warn(unused_variables, unused_must_use)]
+
+extern crate ravro;
+
+use ravro::{AvroReader, Codec, Type};
+use std::env;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const RECORDS: &str = r#"{"name": "first", "foo": {"b": 1.5, "a": 2.0E10}, "inner_rec": {"id": 3}}
+{"name": "second", "foo": {}, "inner_rec": {"id": -9}}
+"#;
+
+fn ravro(args: &[&str], stdin: &[u8]) -> Output {
+	let mut child = Command::new(env!("CARGO_BIN_EXE_ravro")).args(args)
+		.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped())
+		.spawn().unwrap();
+	child.stdin.take().unwrap().write_all(stdin).unwrap();
+	child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+	assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+	String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn datafile_path(name: &str) -> String {
+	env::temp_dir().join(name).to_str().unwrap().to_string()
+}
+
+#[test]
+fn json_round_trips_through_data_files() {
+	let datafile = datafile_path("ravro_cli_nested.avro");
+	let output = ravro(&["fromjson", "--schema", "tests/schemas/nested_schema.avsc", "--codec", "snappy", "-o", &datafile], RECORDS.as_bytes());
+	assert_eq!(stdout(&output), "");
+
+	let reader = AvroReader::from_path(&datafile).unwrap();
+	assert_eq!(reader.get_codec(), Codec::Snappy);
+	let records: Vec<Type> = reader.map(|r| r.unwrap()).collect();
+	assert_eq!(records.len(), 2);
+
+	let json = stdout(&ravro(&["tojson", &datafile], b""));
+	assert_eq!(json, "{\"name\":\"first\",\"foo\":{\"a\":2.0E10,\"b\":1.5},\"inner_rec\":{\"id\":3}}\n\
+		{\"name\":\"second\",\"foo\":{},\"inner_rec\":{\"id\":-9}}\n");
+	let pretty = stdout(&ravro(&["tojson", "--pretty", &datafile], b""));
+	assert!(pretty.starts_with("{\n  \"name\" : \"first\",\n  \"foo\" : {\n    \"a\" : 2.0E10,"), "{}", pretty);
+
+	// Data files written to stdout and read from stdin are the same
+	let written = ravro(&["fromjson", "--schema", "tests/schemas/nested_schema.avsc"], json.as_bytes());
+	assert!(written.status.success());
+	assert_eq!(stdout(&ravro(&["tojson", "-"], &written.stdout)), json);
+	assert_eq!(stdout(&ravro(&["count"], &written.stdout)), "2\n");
+}
+
+#[test]
+fn header_is_printed() {
+	let datafile = datafile_path("ravro_cli_header.avro");
+	let output = ravro(&["fromjson", "--schema", "tests/schemas/nested_schema.avsc", "--codec", "deflate", "-o", &datafile], RECORDS.as_bytes());
+	assert!(output.status.success());
+
+	let reader = AvroReader::from_path(&datafile).unwrap();
+	let schema = stdout(&ravro(&["getschema", &datafile], b""));
+	assert_eq!(ravro::AvroSchema::from_str(&schema).unwrap().compile().unwrap(), reader.get_schema().compile().unwrap());
+	assert_eq!(stdout(&ravro(&["getmeta", "--key", "avro.codec", &datafile], b"")), "deflate\n");
+	let meta = stdout(&ravro(&["getmeta", &datafile], b""));
+	assert_eq!(meta, format!("avro.codec\tdeflate\navro.schema\t{}", schema));
+	assert_eq!(stdout(&ravro(&["count", &datafile], b"")), "2\n");
+}
+
+#[test]
+fn failures_exit_with_an_error() {
+	let cases: Vec<(Vec<&str>, &[u8], &str)> = vec![
+		(vec!["tojson"], b"not avro", "ravro: Failed to decode"),
+		(vec!["count", "tests/missing.avro"], b"", "ravro: Failed to open tests/missing.avro"),
+		(vec!["fromjson", "--schema", "tests/schemas/nested_schema.avsc"], b"{\"name\": 1}", "ravro: Invalid record 0: Expected string at $.name"),
+		(vec!["fromjson", "--schema", "tests/schemas/nested_schema.avsc"], b"{\"name\"", "ravro: Invalid JSON after 0 records")
+	];
+	for (args, stdin, message) in cases {
+		let output = ravro(&args, stdin);
+		assert_eq!(output.status.code(), Some(1));
+		let stderr = String::from_utf8_lossy(&output.stderr);
+		assert!(stderr.starts_with(message), "{:?}: {}", args, stderr);
+	}
+}
+
+/// Writes the JSON records to a data file with the given codec, returning its path
+fn write_datafile(name: &str, codec: &str, records: &str) -> String {
+	let datafile = datafile_path(name);
+	let output = ravro(&["fromjson", "--schema", "tests/schemas/nested_schema.avsc", "--codec", codec, "-o", &datafile], records.as_bytes());
+	assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+	datafile
+}
+
+fn block_counts(datafile: &str) -> Vec<i64> {
+	AvroReader::from_path(datafile).unwrap().iter_block().map(|b| b.unwrap().count).collect()
+}
+
+#[test]
+fn concat_copies_blocks() {
+	let first = write_datafile("ravro_cli_concat_first.avro", "snappy", RECORDS);
+	let second = write_datafile("ravro_cli_concat_second.avro", "deflate", r#"{"name": "third", "foo": {}, "inner_rec": {"id": 0}}"#);
+	let joined = datafile_path("ravro_cli_concat_joined.avro");
+	assert_eq!(stdout(&ravro(&["concat", &first, &second, "-o", &joined], b"")), "");
+
+	let reader = AvroReader::from_path(&joined).unwrap();
+	assert_eq!(reader.get_codec(), Codec::Snappy);
+	assert_eq!(block_counts(&joined), vec![2, 1]);
+	let first_blocks: Vec<_> = AvroReader::from_path(&first).unwrap().iter_block().map(|b| b.unwrap()).collect();
+	let joined_blocks: Vec<_> = AvroReader::from_path(&joined).unwrap().iter_block().map(|b| b.unwrap()).collect();
+	assert_eq!(joined_blocks[0].data, first_blocks[0].data);
+	let json = stdout(&ravro(&["tojson", &first], b"")) + &stdout(&ravro(&["tojson", &second], b""));
+	assert_eq!(stdout(&ravro(&["tojson", &joined], b"")), json);
+
+	let recompressed = ravro(&["concat", "--codec", "null", &second, &first], b"");
+	assert!(recompressed.status.success());
+	let reader = AvroReader::new(&recompressed.stdout[..]).unwrap();
+	assert_eq!(reader.get_codec(), Codec::Null);
+	assert_eq!(reader.count(), 3);
+
+	let output = ravro(&["concat", &first, "tests/conformance/strings.avsc"], b"");
+	assert_eq!(output.status.code(), Some(1));
+	let other_schema = datafile_path("ravro_cli_concat_other.avro");
+	let mut writer = ravro::AvroWriter::from_schema("tests/schemas/long_schema.avsc").unwrap().build().unwrap();
+	writer.write(1i64).unwrap();
+	std::fs::write(&other_schema, writer.take_datafile().unwrap()).unwrap();
+	let output = ravro(&["concat", &first, &other_schema], b"");
+	assert_eq!(output.status.code(), Some(1));
+	assert!(String::from_utf8_lossy(&output.stderr).starts_with(&format!("ravro: Schema of {} differs from the schema of {}", other_schema, first)));
+}
+
+/// Writes a data file of longs with the given metadata, returning its path
+fn write_with_metadata(name: &str, metadata: &[(&str, &str)]) -> String {
+	let datafile = datafile_path(name);
+	let mut builder = ravro::AvroWriter::from_schema("tests/schemas/long_schema.avsc").unwrap();
+	for &(key, value) in metadata {
+		builder.set_metadata(key, value.as_bytes().to_vec()).unwrap();
+	}
+	let mut writer = builder.build().unwrap();
+	writer.write(1i64).unwrap();
+	std::fs::write(&datafile, writer.take_datafile().unwrap()).unwrap();
+	datafile
+}
+
+fn metadata_of(datafile: &str, key: &str) -> Option<Type> {
+	AvroReader::from_path(datafile).unwrap().header().metadata.map_ref().get(key).cloned()
+}
+
+#[test]
+fn concat_keeps_metadata() {
+	let first = write_with_metadata("ravro_cli_concat_meta_first.avro", &[("origin", "sensors")]);
+	let second = write_with_metadata("ravro_cli_concat_meta_second.avro", &[("origin", "sensors")]);
+	let joined = datafile_path("ravro_cli_concat_meta_joined.avro");
+	assert_eq!(stdout(&ravro(&["concat", &first, &second, "-o", &joined], b"")), "");
+	assert_eq!(metadata_of(&joined, "origin"), Some(Type::Bytes(b"sensors".to_vec())));
+	assert_eq!(AvroReader::from_path(&joined).unwrap().count(), 2);
+
+	let other = write_with_metadata("ravro_cli_concat_meta_other.avro", &[("origin", "gateway")]);
+	let output = ravro(&["concat", &first, &other], b"");
+	assert_eq!(output.status.code(), Some(1));
+	assert!(String::from_utf8_lossy(&output.stderr).starts_with(&format!("ravro: Metadata of {} differs from the metadata of {}", other, first)));
+}
+
+#[test]
+fn outputs_may_not_be_inputs() {
+	let first = write_with_metadata("ravro_cli_output_input_first.avro", &[]);
+	let second = write_with_metadata("ravro_cli_output_input_second.avro", &[]);
+	let before = std::fs::read(&second).unwrap();
+	let output = ravro(&["concat", &first, &second, "-o", &second], b"");
+	assert_eq!(output.status.code(), Some(1));
+	assert!(String::from_utf8_lossy(&output.stderr).starts_with(&format!("ravro: Output {} is also an input", second)));
+	assert_eq!(std::fs::read(&second).unwrap(), before);
+
+	let output = ravro(&["recodec", "--codec", "snappy", &first, "-o", &first], b"");
+	assert_eq!(output.status.code(), Some(1));
+	assert_eq!(AvroReader::from_path(&first).unwrap().count(), 1);
+}
+
+#[test]
+fn recodec_recompresses_blocks() {
+	let datafile = write_datafile("ravro_cli_recodec.avro", "null", RECORDS);
+	let json = stdout(&ravro(&["tojson", &datafile], b""));
+	for codec in vec![Codec::Deflate, Codec::Snappy, Codec::Null] {
+		let recodeced = datafile_path(&format!("ravro_cli_recodeced_{}.avro", codec));
+		assert_eq!(stdout(&ravro(&["recodec", "--codec", &codec.to_string(), &datafile, "-o", &recodeced], b"")), "");
+		assert_eq!(AvroReader::from_path(&recodeced).unwrap().get_codec(), codec);
+		assert_eq!(block_counts(&recodeced), block_counts(&datafile));
+		assert_eq!(stdout(&ravro(&["tojson", &recodeced], b"")), json);
+	}
+	assert_eq!(ravro(&["recodec", &datafile], b"").status.code(), Some(1));
+
+	let datafile = write_with_metadata("ravro_cli_recodec_meta.avro", &[("origin", "sensors")]);
+	let recodeced = datafile_path("ravro_cli_recodeced_meta.avro");
+	assert_eq!(stdout(&ravro(&["recodec", "--codec", "deflate", &datafile, "-o", &recodeced], b"")), "");
+	assert_eq!(metadata_of(&recodeced, "origin"), Some(Type::Bytes(b"sensors".to_vec())));
+}
+
+#[test]
+fn random_writes_reproducible_records() {
+	let args = |seed: &'static str| vec!["random", "--schema", "tests/schemas/nested_schema.avsc", "--count", "5000", "--seed", seed, "--codec", "deflate"];
+	let written = ravro(&args("42"), b"");
+	let reader = AvroReader::new(&written.stdout[..]).unwrap();
+	assert_eq!(reader.get_codec(), Codec::Deflate);
+	assert_eq!(reader.count(), 5000);
+	let json = stdout(&ravro(&["tojson"], &written.stdout));
+	assert_eq!(stdout(&ravro(&["tojson"], &ravro(&args("42"), b"").stdout)), json);
+	assert!(stdout(&ravro(&["tojson"], &ravro(&args("43"), b"").stdout)) != json);
+
+	let output = ravro(&["random", "--schema", "tests/schemas/nested_schema.avsc", "--count", "many"], b"");
+	assert_eq!(output.status.code(), Some(1));
+	assert!(String::from_utf8_lossy(&output.stderr).starts_with("ravro: Invalid count: many"));
+}
+
+#[test]
+fn check_reports_corrupt_blocks() {
+	let datafile = write_datafile("ravro_cli_check.avro", "snappy", RECORDS);
+	assert_eq!(stdout(&ravro(&["check", &datafile], b"")), "block 0 at offset 346: 2 records in 36 bytes: ok\n\
+		1 blocks with 2 records checked\n");
+
+	let mut corrupt = std::fs::read(&datafile).unwrap();
+	let pos = corrupt.len() - 20;
+	corrupt[pos] ^= 0xff;
+	let output = ravro(&["check"], &corrupt);
+	assert_eq!(output.status.code(), Some(1));
+	let report = String::from_utf8_lossy(&output.stdout);
+	assert!(report.starts_with("block 0 at offset 346: 2 records in 36 bytes: failed: Codec snappy failed"), "{}", report);
+	assert_eq!(String::from_utf8_lossy(&output.stderr), "ravro: - is corrupt: 1 of 1 blocks failed the check\n");
+
+	let output = ravro(&["check", "README.md"], b"");
+	assert_eq!(output.status.code(), Some(1));
+	assert!(String::from_utf8_lossy(&output.stderr).starts_with("ravro: README.md is corrupt: "));
+}
@@ -0,0 +1,99 @@
+#![warn(unused_variables, unused_must_use)]
+
+extern crate ravro;
+
+use ravro::{AvroReader, AvroSchema, AvroWriter, EncoderOptions, SliceReader, Type, TypeRef};
+use ravro::errors::AvroErr;
+use std::collections::HashMap;
+use std::io::Cursor;
+
+fn compile(schema: &str) -> ravro::schema::CompiledSchema {
+	AvroSchema::from_str(schema).unwrap().compile().unwrap()
+}
+
+fn longs(values: &[i64]) -> Type {
+	Type::Array(values.iter().cloned().map(Type::Long).collect())
+}
+
+#[test]
+fn arrays_in_several_blocks_decode() {
+	let schema = compile(r#"{"type": "array", "items": "long"}"#);
+	// A block of two items, a block of one item with a negative count and its size, then the end
+	let data = vec![0x04, 0x02, 0x04, 0x01, 0x02, 0x06, 0x00];
+	assert_eq!(Type::decode_with(&schema, &mut Cursor::new(&data)).unwrap(), longs(&[1, 2, 3]));
+	let mut buf = &data[..];
+	assert_eq!(TypeRef::decode(&schema, &mut buf).unwrap().into_owned(), longs(&[1, 2, 3]));
+	assert!(buf.is_empty());
+}
+
+#[test]
+fn blocks_are_written_with_sizes() {
+	let schema = compile(r#"{"type": "array", "items": "long"}"#);
+	let options = EncoderOptions { block_items: Some(2) };
+	let value = longs(&[1, 2, 3, 64, -1]);
+	let mut encoded = vec![];
+	let len = value.encode_with_options(&schema, &options, &mut encoded).unwrap();
+	assert_eq!(len, encoded.len());
+	assert_eq!(encoded, vec![0x03, 0x04, 0x02, 0x04, 0x03, 0x06, 0x06, 0x80, 0x01, 0x01, 0x02, 0x01, 0x00]);
+	assert_eq!(Type::decode_with(&schema, &mut Cursor::new(&encoded)).unwrap(), value);
+
+	let mut encoded = vec![];
+	longs(&[]).encode_with_options(&schema, &options, &mut encoded).unwrap();
+	assert_eq!(encoded, vec![0x00]);
+}
+
+#[test]
+fn nested_maps_and_arrays_round_trip_in_blocks() {
+	let schema = compile(r#"{"type": "map", "values": {"type": "array", "items": "string"}}"#);
+	let mut map = HashMap::new();
+	for i in 0..7 {
+		map.insert(format!("key {}", i), Type::Array((0..i).map(|j| Type::Str(format!("item {}", j))).collect()));
+	}
+	let value = Type::Map(map);
+	for block_items in 1..9 {
+		let options = EncoderOptions { block_items: Some(block_items) };
+		let mut encoded = vec![];
+		value.encode_with_options(&schema, &options, &mut encoded).unwrap();
+		assert_eq!(Type::decode_with(&schema, &mut Cursor::new(&encoded)).unwrap(), value);
+		let mut buf = &encoded[..];
+		let borrowed = TypeRef::decode(&schema, &mut buf).unwrap();
+		assert!(buf.is_empty());
+		let mut reencoded = vec![];
+		borrowed.encode_with_options(&schema, &options, &mut reencoded).unwrap();
+		assert_eq!(reencoded, encoded);
+	}
+}
+
+#[test]
+fn data_files_hold_blocked_collections() {
+	let mut builder = AvroWriter::from_schema("tests/schemas/array_schema.avsc").unwrap();
+	builder.set_encoder_options(EncoderOptions { block_items: Some(3) });
+	let mut data_writer = builder.build().unwrap();
+	let values: Vec<Type> = (0..10)
+		.map(|i| Type::Array((0..i).map(|j| Type::Str(j.to_string())).collect()))
+		.collect();
+	for value in &values {
+		data_writer.write_type(value).unwrap();
+	}
+	let datafile = data_writer.take_datafile().unwrap();
+	let read: Vec<Type> = AvroReader::new(Cursor::new(datafile.clone())).unwrap().map(|v| v.unwrap()).collect();
+	assert_eq!(read, values);
+	let reader = SliceReader::new(&datafile).unwrap();
+	let read: Vec<Type> = reader.records().unwrap().map(|v| v.unwrap().into_owned()).collect();
+	assert_eq!(read, values);
+}
+
+#[test]
+fn invalid_block_headers_are_rejected() {
+	let schema = compile(r#"{"type": "array", "items": "long"}"#);
+	// A negative count followed by a negative size
+	let data = vec![0x01, 0x01, 0x02, 0x00];
+	match Type::decode_with(&schema, &mut Cursor::new(&data)) {
+		Err(AvroErr::Decode { ref reason, .. }) => assert_eq!(reason, "Block size -1 is negative"),
+		other => panic!("Expected a decode error, found {:?}", other)
+	}
+	// The smallest long as the count
+	let data = vec![0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01, 0x00];
+	assert!(Type::decode_with(&schema, &mut Cursor::new(&data)).is_err());
+	assert!(TypeRef::decode(&schema, &mut &data[..]).is_err());
+}
@@ -1,10 +1,10 @@
 
 extern crate ravro;
 
-use std::process::Command;
-use std::str;
+use self::ravro::AvroReader;
 use self::ravro::AvroWriter;
 use self::ravro::Codec;
+use self::ravro::json::to_json;
 
 pub fn test_writer(schema_file: &str, codec: Codec) -> AvroWriter {
 	let mut data_writer = AvroWriter::from_schema(schema_file).unwrap();
@@ -13,10 +13,15 @@ pub fn test_writer(schema_file: &str, codec: Codec) -> AvroWriter {
 	data_writer
 }
 
-pub fn get_java_tool_output(encoded: &str) -> Result<String, ()> {
-    let a = Command::new("java")
-            .args(&["-jar", "avro-tools-1.8.2.jar", "tojson", encoded])
-            .output()
-            .expect("failed to execute process");
-    str::from_utf8(&a.stdout).map_err(|_| ()).map(|s| s.to_string())
+/// Gives the records of a data file as JSON, one record per line, the same as `ravro tojson` prints them
+#[allow(dead_code)]
+pub fn tojson_output(encoded: &str) -> Result<String, ()> {
+	let reader = AvroReader::from_path(encoded).map_err(|_| ())?;
+	let schema = reader.get_schema().compile().map_err(|_| ())?;
+	let mut output = String::new();
+	for record in reader {
+		output.push_str(&to_json(&schema, &record.map_err(|_| ())?).map_err(|_| ())?);
+		output.push('\n');
+	}
+	Ok(output)
 }
@@ -0,0 +1,156 @@
+#![warn(unused_variables, unused_must_use)]
+
+extern crate rand;
+extern crate ravro;
+
+use rand::{Isaac64Rng, Rng, SeedableRng};
+use ravro::{AvroSchema, EncoderOptions, Type};
+use ravro::compare::{compare, compare_values};
+use ravro::complex::{Enum, Field, Record};
+use ravro::errors::AvroErr;
+use ravro::schema::CompiledSchema;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+const EVENT_SCHEMA: &str = r#"{"type": "record", "name": "event", "fields": [
+	{"name": "kind", "type": {"type": "enum", "name": "kind", "symbols": ["START", "STOP", "ERROR"]}},
+	{"name": "host", "type": "string", "order": "descending"},
+	{"name": "tags", "type": {"type": "map", "values": "string"}, "order": "ignore"},
+	{"name": "readings", "type": {"type": "array", "items": "double"}},
+	{"name": "code", "type": ["null", "int", "bytes"]},
+	{"name": "id", "type": {"type": "fixed", "name": "id", "size": 2}},
+	{"name": "seq", "type": "long"}
+]}"#;
+
+fn compile(schema: &str) -> CompiledSchema {
+	AvroSchema::from_str(schema).unwrap().compile().unwrap()
+}
+
+fn encode(value: &Type, schema: &CompiledSchema) -> Vec<u8> {
+	let mut encoded = vec![];
+	value.encode_with(schema, &mut encoded).unwrap();
+	encoded
+}
+
+/// Picks values from small sets, so that compared records often share some of their fields
+fn random_event(rng: &mut Isaac64Rng) -> Type {
+	let symbols = ["START", "STOP", "ERROR"];
+	let mut kind = Enum::new("kind", &symbols);
+	kind.set_value(symbols[rng.gen_range(0, 3)]);
+	let hosts = ["", "a", "ab", "b", "é", "日本"];
+	let mut tags = HashMap::new();
+	tags.insert("seed".to_string(), Type::Str(rng.gen::<u8>().to_string()));
+	let readings = [0.0, -0.0, 1.5, -1.5, std::f64::NAN, std::f64::INFINITY];
+	let readings = (0..rng.gen_range(0, 3)).map(|_| Type::Double(readings[rng.gen_range(0, readings.len())])).collect();
+	let code = match rng.gen_range(0, 3) {
+		0 => Type::Union(0, Box::new(Type::Null)),
+		1 => Type::Union(1, Box::new(Type::Int(rng.gen_range(-2, 3)))),
+		_ => Type::Union(2, Box::new(Type::Bytes((0..rng.gen_range(0, 3)).map(|_| rng.gen_range(0, 3) * 127).collect())))
+	};
+	Type::Record(Record::new("event", None, vec![
+		Field::new("kind", Type::Enum(kind)),
+		Field::new("host", Type::Str(hosts[rng.gen_range(0, hosts.len())].to_string())),
+		Field::new("tags", Type::Map(tags)),
+		Field::new("readings", Type::Array(readings)),
+		Field::new("code", code),
+		Field::new("id", Type::Fixed(vec![rng.gen_range(0, 2) * 255, rng.gen_range(0, 2)])),
+		Field::new("seq", Type::Long(rng.gen_range(-2, 3)))
+	]))
+}
+
+#[test]
+fn encodings_compare_as_their_values() {
+	let schema = compile(EVENT_SCHEMA);
+	let mut rng = Isaac64Rng::from_seed(&[45][..]);
+	let events: Vec<Type> = (0..60).map(|_| random_event(&mut rng)).collect();
+	let blocked = EncoderOptions { block_items: Some(1) };
+	for a in &events {
+		for b in &events {
+			let order = compare_values(&schema, a, b).unwrap();
+			assert_eq!(a.cmp_with(b, &schema).unwrap(), order);
+			assert_eq!(compare_values(&schema, b, a).unwrap(), order.reverse());
+			assert_eq!(compare(&schema, &encode(a, &schema), &encode(b, &schema)).unwrap(), order, "{:?} {:?}", a, b);
+			let mut a_blocked = vec![];
+			a.encode_with_options(&schema, &blocked, &mut a_blocked).unwrap();
+			assert_eq!(compare(&schema, &a_blocked, &encode(b, &schema)).unwrap(), order);
+		}
+	}
+}
+
+#[test]
+fn values_follow_the_spec_order() {
+	let cases: Vec<(&str, Type, Type)> = vec![
+		(r#""boolean""#, Type::Bool(false), Type::Bool(true)),
+		(r#""long""#, Type::Long(-5), Type::Long(3)),
+		(r#""double""#, Type::Double(-0.0), Type::Double(0.0)),
+		(r#""double""#, Type::Double(std::f64::INFINITY), Type::Double(std::f64::NAN)),
+		(r#""float""#, Type::Float(-1.0), Type::Float(std::f32::NAN)),
+		(r#""string""#, Type::Str("z".to_string()), Type::Str("é".to_string())),
+		(r#""string""#, Type::Str("ab".to_string()), Type::Str("abc".to_string())),
+		(r#""bytes""#, Type::Bytes(vec![0x7f]), Type::Bytes(vec![0x80])),
+		(r#"{"type": "array", "items": "int"}"#, Type::Array(vec![Type::Int(1)]), Type::Array(vec![Type::Int(1), Type::Int(0)])),
+		(r#"["string", "null"]"#, Type::Union(0, Box::new(Type::Str("z".to_string()))), Type::Union(1, Box::new(Type::Null)))
+	];
+	for (schema, smaller, larger) in cases {
+		let schema = compile(schema);
+		assert_eq!(compare_values(&schema, &smaller, &larger).unwrap(), Ordering::Less, "{:?} {:?}", smaller, larger);
+		assert_eq!(compare(&schema, &encode(&smaller, &schema), &encode(&larger, &schema)).unwrap(), Ordering::Less);
+		assert_eq!(compare(&schema, &encode(&larger, &schema), &encode(&larger, &schema)).unwrap(), Ordering::Equal);
+	}
+}
+
+#[test]
+fn field_orders_are_followed() {
+	let schema = compile(r#"{"type": "record", "name": "rec", "fields": [
+		{"name": "a", "type": "long", "order": "descending"},
+		{"name": "b", "type": "long", "order": "ignore"},
+		{"name": "c", "type": "long"}
+	]}"#);
+	let rec = |a, b, c| Type::Record(Record::new("rec", None, vec![
+		Field::new("a", Type::Long(a)), Field::new("b", Type::Long(b)), Field::new("c", Type::Long(c))
+	]));
+	let cases = vec![
+		(rec(2, 0, 0), rec(1, 0, 0), Ordering::Less),
+		(rec(1, 0, 5), rec(1, 9, 5), Ordering::Equal),
+		(rec(1, 9, 4), rec(1, 0, 5), Ordering::Less)
+	];
+	for (a, b, order) in cases {
+		assert_eq!(compare_values(&schema, &a, &b).unwrap(), order);
+		assert_eq!(compare(&schema, &encode(&a, &schema), &encode(&b, &schema)).unwrap(), order);
+	}
+	let invalid = r#"{"type": "record", "name": "rec", "fields": [{"name": "a", "type": "long", "order": "sideways"}]}"#;
+	match AvroSchema::from_str(invalid).unwrap().compile() {
+		Err(AvroErr::SchemaParse { ref path, ref reason }) => {
+			assert_eq!(path.to_string(), "$.a");
+			assert_eq!(reason, "Invalid order \"sideways\"");
+		}
+		other => panic!("Expected a schema error, found {:?}", other)
+	}
+}
+
+#[test]
+fn maps_can_not_be_compared() {
+	let schema = compile(r#"{"type": "record", "name": "rec", "fields": [{"name": "m", "type": {"type": "map", "values": "long"}}]}"#);
+	let value = Type::Record(Record::new("rec", None, vec![Field::new("m", Type::Map(HashMap::new()))]));
+	let encoded = encode(&value, &schema);
+	for result in vec![compare(&schema, &encoded, &encoded), compare_values(&schema, &value, &value)] {
+		match result {
+			Err(AvroErr::SchemaParse { ref path, ref reason }) => {
+				assert_eq!(path.to_string(), "$.m");
+				assert_eq!(reason, "Maps can not be compared");
+			}
+			other => panic!("Expected a schema error, found {:?}", other)
+		}
+	}
+}
+
+#[test]
+fn malformed_data_fails_to_compare() {
+	let schema = compile(EVENT_SCHEMA);
+	let mut rng = Isaac64Rng::from_seed(&[46][..]);
+	let encoded = encode(&random_event(&mut rng), &schema);
+	assert!(compare(&schema, &encoded, &encoded[..encoded.len() - 1]).is_err());
+	assert!(compare(&schema, &[0x06], &[0x06]).is_err());
+	let mismatch = compare_values(&schema, &Type::Long(1), &Type::Long(2));
+	assert!(match mismatch { Err(AvroErr::Validation { .. }) => true, _ => false });
+}
@@ -25,7 +25,7 @@ fn write_map() {
 		let datafile_buffer = data_writer.take_datafile().unwrap();
 		let mut open_options = OpenOptions::new().truncate(true).write(true).create(true).open(datafile_name).unwrap();
     	let _ = open_options.write_all(datafile_buffer.as_slice());
-		assert_eq!(Ok("{\"A\":234.455}\n".to_string()), common::get_java_tool_output(datafile_name));
+		assert_eq!(Ok("{\"A\":234.455}\n".to_string()), common::tojson_output(datafile_name));
 	}
 }
 
@@ -48,7 +48,7 @@ fn write_nested_record() {
 		let datafile_buffer = data_writer.take_datafile().unwrap();
 		let mut open_options = OpenOptions::new().truncate(true).write(true).create(true).open(datafile_name).unwrap();
     	let _ = open_options.write_all(datafile_buffer.as_slice());
-		assert_eq!(Ok("{\"name\":\"nested_record_example\",\"foo\":{\"SomeData\":234.455},\"inner_rec\":{\"id\":3}}\n".to_string()), common::get_java_tool_output(datafile_name));
+		assert_eq!(Ok("{\"name\":\"nested_record_example\",\"foo\":{\"SomeData\":234.455},\"inner_rec\":{\"id\":3}}\n".to_string()), common::tojson_output(datafile_name));
 	}
 }
 
@@ -69,7 +69,7 @@ fn write_record() {
 		let datafile_buffer = data_writer.take_datafile().unwrap();
 		let mut open_options = OpenOptions::new().truncate(true).write(true).create(true).open(datafile_name).unwrap();
     	let _ = open_options.write_all(datafile_buffer.as_slice());
-		assert_eq!(Ok("{\"name\":\"record_example\",\"canFrame\":34534,\"gps\":7673,\"lsmsensor\":2554,\"map\":\"junk\"}\n".to_string()), common::get_java_tool_output(datafile_name));
+		assert_eq!(Ok("{\"name\":\"record_example\",\"canFrame\":34534,\"gps\":7673,\"lsmsensor\":2554,\"map\":\"junk\"}\n".to_string()), common::tojson_output(datafile_name));
 	}
 }
 
@@ -88,7 +88,7 @@ fn write_array() {
 		let datafile_buffer = data_writer.take_datafile().unwrap();
 		let mut open_options = OpenOptions::new().truncate(true).write(true).create(true).open(datafile_name).unwrap();
     	let _ = open_options.write_all(datafile_buffer.as_slice());
-		assert_eq!(Ok("[\"a\",\"b\",\"c\",\"d\"]\n".to_string()), common::get_java_tool_output(datafile_name));
+		assert_eq!(Ok("[\"a\",\"b\",\"c\",\"d\"]\n".to_string()), common::tojson_output(datafile_name));
 	}
 }
 
@@ -105,6 +105,6 @@ fn write_enum() {
     	let datafile_buffer = data_writer.take_datafile().unwrap();
 		let mut open_options = OpenOptions::new().truncate(true).write(true).create(true).open(datafile_name).unwrap();
     	let _ = open_options.write_all(datafile_buffer.as_slice());
-		assert_eq!(Ok("\"DIAMOND\"\n".to_string()), common::get_java_tool_output(datafile_name));
+		assert_eq!(Ok("\"DIAMOND\"\n".to_string()), common::tojson_output(datafile_name));
 	}
 }
@@ -0,0 +1,196 @@
+#![warn(unused_variables, unused_must_use)]
+
+extern crate ravro;
+
+mod common;
+
+use common::test_writer;
+use ravro::{AvroReader, AvroSchema, Codec, DecoderLimits, Type, TypeRef};
+use ravro::codec::Encoder;
+use ravro::complex::{Field, Record};
+use ravro::schema::CompiledSchema;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Cursor;
+
+// The golden binaries in `tests/conformance` hold the binary encoding of these values as laid
+// down by the avro spec, produced independently of this crate.
+
+fn strings() -> Type {
+	let strings = vec!["", "é", "日本語", "🦀 crab", "a\u{0}b", "ünïcödé ✓"];
+	Type::Array(strings.into_iter().map(|s| Type::Str(s.to_string())).collect())
+}
+
+fn longs() -> Type {
+	let longs = vec![i64::min_value(), i64::max_value(), 0, -1, 1, -64, 64,
+		i64::from(i32::min_value()) - 1, i64::from(i32::max_value()) + 1];
+	Type::Array(longs.into_iter().map(Type::Long).collect())
+}
+
+fn ints() -> Type {
+	let ints = vec![i32::min_value(), i32::max_value(), 0, -1, 63, -65];
+	Type::Array(ints.into_iter().map(Type::Int).collect())
+}
+
+fn floats() -> Type {
+	let floats = vec![std::f32::NAN, std::f32::INFINITY, std::f32::NEG_INFINITY, -0.0, 1.5, std::f32::MAX, std::f32::MIN_POSITIVE];
+	let doubles = vec![std::f64::NAN, std::f64::INFINITY, std::f64::NEG_INFINITY, -0.0, std::f64::MAX, std::f64::MIN_POSITIVE, 5e-324];
+	Type::Record(Record::new("floats", None, vec![
+		Field::new("floats", Type::Array(floats.into_iter().map(Type::Float).collect())),
+		Field::new("doubles", Type::Array(doubles.into_iter().map(Type::Double).collect()))
+	]))
+}
+
+fn empty() -> Type {
+	Type::Record(Record::new("empty", None, vec![
+		Field::new("items", Type::Array(vec![])),
+		Field::new("entries", Type::Map(HashMap::new())),
+		Field::new("bytes", Type::Bytes(vec![])),
+		Field::new("string", Type::Str(String::new())),
+		Field::new("nothing", Type::Record(Record::new("nothing", None, vec![]))),
+		Field::new("optional", Type::Union(0, Box::new(Type::Null)))
+	]))
+}
+
+fn nested() -> Type {
+	let mut next = Type::Union(0, Box::new(Type::Null));
+	for depth in (0..40).rev() {
+		let node = Record::new("node", None, vec![
+			Field::new("depth", Type::Int(depth)),
+			Field::new("label", Type::Str(format!("level é{}", depth))),
+			Field::new("next", next)
+		]);
+		next = Type::Union(1, Box::new(Type::Record(node)));
+	}
+	match next {
+		Type::Union(_, node) => *node,
+		_ => unreachable!()
+	}
+}
+
+fn map_keys() -> Type {
+	let mut map = HashMap::new();
+	map.insert("ключ".to_string(), Type::Long(1));
+	map.insert("κλειδί".to_string(), Type::Long(-2));
+	map.insert("键".to_string(), Type::Long(i64::max_value()));
+	map.insert("key".to_string(), Type::Long(i64::min_value()));
+	map.insert(String::new(), Type::Long(0));
+	Type::Map(map)
+}
+
+/// Every level of the nested records adds a record and a union to the depth
+fn limits() -> DecoderLimits {
+	DecoderLimits { max_depth: 128, ..DecoderLimits::default() }
+}
+
+fn cases() -> Vec<(&'static str, Type)> {
+	vec![
+		("strings", strings()),
+		("longs", longs()),
+		("ints", ints()),
+		("floats", floats()),
+		("empty", empty()),
+		("nested", nested()),
+		("map_keys", map_keys())
+	]
+}
+
+fn load(name: &str) -> (CompiledSchema, Vec<u8>) {
+	let schema = AvroSchema::from_file(format!("tests/conformance/{}.avsc", name)).unwrap().compile().unwrap();
+	let golden = fs::read(format!("tests/conformance/{}.bin", name)).unwrap();
+	(schema, golden)
+}
+
+fn encode_with(value: &Type, schema: &CompiledSchema) -> Vec<u8> {
+	let mut encoded = vec![];
+	let len = value.encode_with(schema, &mut encoded).unwrap();
+	assert_eq!(len, encoded.len());
+	encoded
+}
+
+/// Compares values by their encoding, as NaN floats do not equal themselves
+fn assert_same(name: &str, left: &Type, right: &Type, schema: &CompiledSchema) {
+	if name == "floats" {
+		assert_eq!(encode_with(left, schema), encode_with(right, schema), "{}", name);
+	} else {
+		assert_eq!(left, right, "{}", name);
+	}
+}
+
+#[test]
+fn values_encode_to_golden_binaries() {
+	for (name, value) in cases() {
+		let (schema, golden) = load(name);
+		assert_eq!(encode_with(&value, &schema), golden, "{}", name);
+		let mut encoded = vec![];
+		let len = value.encode(&mut encoded).unwrap();
+		assert_eq!(len, encoded.len(), "{}", name);
+		assert_eq!(encoded, golden, "{}", name);
+	}
+}
+
+#[test]
+fn golden_binaries_decode_to_values() {
+	for (name, value) in cases() {
+		let (schema, golden) = load(name);
+		let mut reader = Cursor::new(&golden);
+		let decoded = Type::decode_with_limits(&schema, &limits(), &mut reader).unwrap();
+		assert_eq!(reader.position() as usize, golden.len(), "{}", name);
+		assert_same(name, &decoded, &value, &schema);
+
+		let mut buf = &golden[..];
+		let borrowed = TypeRef::decode_with_limits(&schema, &limits(), &mut buf).unwrap();
+		assert!(buf.is_empty(), "{}", name);
+		let mut encoded = vec![];
+		borrowed.encode_with(&schema, &mut encoded).unwrap();
+		assert_eq!(encoded, golden, "{}", name);
+		assert_same(name, &borrowed.into_owned(), &value, &schema);
+	}
+}
+
+#[test]
+fn special_floats_keep_their_bits() {
+	let (schema, golden) = load("floats");
+	let decoded = Type::decode_with(&schema, &mut Cursor::new(&golden)).unwrap();
+	let fields = match decoded {
+		Type::Record(rec) => rec.fields,
+		other => panic!("Expected a record, found {:?}", other)
+	};
+	match fields[0].ty {
+		Type::Array(ref floats) => {
+			assert!(floats[0].float_ref().is_nan());
+			assert_eq!(floats[1].float_ref(), std::f32::INFINITY);
+			assert_eq!(floats[2].float_ref(), std::f32::NEG_INFINITY);
+			assert!(floats[3].float_ref() == 0.0 && floats[3].float_ref().is_sign_negative());
+		}
+		ref other => panic!("Expected an array, found {:?}", other)
+	}
+	match fields[1].ty {
+		Type::Array(ref doubles) => {
+			assert!(doubles[0].double_ref().is_nan());
+			assert!(doubles[3].double_ref() == 0.0 && doubles[3].double_ref().is_sign_negative());
+			assert_eq!(doubles[6].double_ref(), 5e-324);
+		}
+		ref other => panic!("Expected an array, found {:?}", other)
+	}
+}
+
+#[test]
+fn values_round_trip_through_data_files() {
+	for (name, value) in cases() {
+		let (schema, _) = load(name);
+		for codec in vec![Codec::Null, Codec::Snappy, Codec::Deflate].into_iter() {
+			let mut data_writer = test_writer(&format!("tests/conformance/{}.avsc", name), codec);
+			data_writer.write(value.clone()).unwrap();
+			data_writer.write(value.clone()).unwrap();
+			let datafile = data_writer.take_datafile().unwrap();
+			let mut reader = AvroReader::new(Cursor::new(datafile)).unwrap();
+			reader.set_limits(limits());
+			let records: Vec<Type> = reader.map(|r| r.unwrap()).collect();
+			assert_eq!(records.len(), 2, "{} {:?}", name, codec);
+			for record in &records {
+				assert_same(name, record, &value, &schema);
+			}
+		}
+	}
+}
@@ -0,0 +1,59 @@
+#![warn(unused_variables, unused_must_use)]
+
+extern crate ravro;
+
+use ravro::AvroWriter;
+use ravro::Codec;
+use ravro::Type;
+use std::collections::HashMap;
+use ravro::writer::SyncMarker;
+
+fn seeded_datafile(codec: Codec, seed: u64) -> Vec<u8> {
+	let mut builder = AvroWriter::from_schema("tests/schemas/long_schema.avsc").unwrap();
+	builder.set_codec(codec);
+	builder.set_seed(seed);
+	let mut data_writer = builder.build().unwrap();
+	for i in 0..100i64 {
+		data_writer.write(i * 31).unwrap();
+	}
+	data_writer.take_datafile().unwrap()
+}
+
+#[test]
+fn same_seed_gives_identical_datafiles() {
+	for codec in vec![Codec::Null, Codec::Snappy, Codec::Deflate].into_iter() {
+		assert_eq!(seeded_datafile(codec, 42), seeded_datafile(codec, 42));
+		assert!(seeded_datafile(codec, 42) != seeded_datafile(codec, 43));
+	}
+}
+
+#[test]
+fn explicit_sync_marker_is_written() {
+	let marker = [7u8; 16];
+	let mut builder = AvroWriter::from_schema("tests/schemas/string_schema.avsc").unwrap();
+	builder.set_sync_marker(SyncMarker::from(marker));
+	let mut data_writer = builder.build().unwrap();
+	data_writer.write("abcd".to_string()).unwrap();
+	let datafile = data_writer.take_datafile().unwrap();
+	// One marker ends the header and one ends the data block
+	assert_eq!(&datafile[datafile.len() - 16..], &marker[..]);
+	let occurrences = datafile.windows(16).filter(|w| *w == &marker[..]).count();
+	assert_eq!(occurrences, 2);
+}
+
+#[test]
+fn map_entries_are_written_in_key_order() {
+	let encode_map = || {
+		let mut builder = AvroWriter::from_schema("tests/schemas/map_schema.avsc").unwrap();
+		builder.set_seed(7);
+		let mut data_writer = builder.build().unwrap();
+		// Every HashMap gets its own hasher state, so iteration order differs between the two calls
+		let mut map = HashMap::new();
+		for i in 0..64 {
+			map.insert(format!("key{}", i), Type::Double(i as f64));
+		}
+		data_writer.write(map).unwrap();
+		data_writer.take_datafile().unwrap()
+	};
+	assert_eq!(encode_map(), encode_map());
+}
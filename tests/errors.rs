@@ -0,0 +1,120 @@
+#![warn(unused_variables, unused_must_use)]
+
+extern crate failure;
+extern crate ravro;
+
+mod common;
+
+use common::test_writer;
+use failure::Fail;
+use ravro::{AvroReader, AvroSchema, Codec, Type};
+use ravro::complex::{Field, Record};
+use ravro::errors::AvroErr;
+use std::collections::HashMap;
+use std::io::{Cursor, ErrorKind};
+
+fn nested_record(id: Type, pi: Type) -> Record {
+	let mut map = HashMap::new();
+	map.insert("pi".to_string(), pi);
+	Record::new("dashboard_stats", None, vec![
+		Field::new("name", Type::Str("stats".to_string())),
+		Field::new("foo", Type::Map(map)),
+		Field::new("inner_rec", Type::Record(Record::new("id_rec", None, vec![Field::new("id", id)])))
+	])
+}
+
+#[test]
+fn validation_errors_point_at_the_offending_value() {
+	let mut data_writer = test_writer("tests/schemas/nested_schema.avsc", Codec::Null);
+	match data_writer.write(nested_record(Type::Str("3".to_string()), Type::Float(3.14))) {
+		Err(AvroErr::Validation { ref path, ref expected, ref found }) => {
+			assert_eq!(path.to_string(), "$.inner_rec.id");
+			assert_eq!(expected, "long");
+			assert_eq!(found, "string");
+		}
+		other => panic!("Expected a validation error, found {:?}", other)
+	}
+	let err = data_writer.write(nested_record(Type::Long(3), Type::Double(3.14))).unwrap_err();
+	assert_eq!(err.path().unwrap().to_string(), "$.foo[\"pi\"]");
+	assert_eq!(err.to_string(), "Expected float at $.foo[\"pi\"], found double 3.14");
+}
+
+#[test]
+fn validation_errors_name_mismatching_fields() {
+	let mut data_writer = test_writer("tests/schemas/nested_schema.avsc", Codec::Null);
+	let record = Record::new("dashboard_stats", None, vec![
+		Field::new("title", Type::Str("stats".to_string())),
+		Field::new("foo", Type::Map(HashMap::new())),
+		Field::new("inner_rec", Type::Record(Record::new("id_rec", None, vec![Field::new("id", Type::Long(3))])))
+	]);
+	match data_writer.write(record) {
+		Err(AvroErr::Validation { ref expected, ref found, .. }) => {
+			assert_eq!(expected, "field name");
+			assert_eq!(found, "field title");
+		}
+		other => panic!("Expected a validation error, found {:?}", other)
+	}
+}
+
+#[test]
+fn decode_errors_carry_block_and_offset() {
+	let mut data_writer = test_writer("tests/schemas/union_schema.avsc", Codec::Null);
+	for i in 0..6u8 {
+		data_writer.write(Record::new("reading", None, vec![
+			Field::new("id", Type::Fixed(vec![i, 0, 0, 0])),
+			Field::new("value", Type::Union(1, Box::new(Type::Double(1.5)))),
+			Field::new("next_id", Type::Union(0, Box::new(Type::Null)))
+		])).unwrap();
+		data_writer.commit_block().unwrap();
+	}
+	let mut datafile = data_writer.take_datafile().unwrap();
+	let blocks: Vec<_> = AvroReader::new(Cursor::new(datafile.clone())).unwrap().iter_block()
+		.map(|b| b.unwrap())
+		.collect();
+	// Point the value of the record in the fourth block to a union branch that does not exist
+	let block = &blocks[3];
+	let branch_offset = block.offset as usize + 2 + 4;
+	datafile[branch_offset] = 20;
+
+	let results: Vec<_> = AvroReader::new(Cursor::new(datafile)).unwrap().collect();
+	assert_eq!(results.len(), 4);
+	let err = results[3].as_ref().unwrap_err();
+	match *err {
+		AvroErr::Decode { ref path, position, ref reason } => {
+			assert_eq!(path.to_string(), "$.value");
+			assert_eq!(position.block, Some(3));
+			assert_eq!(position.offset, Some(block.offset));
+			assert_eq!(reason, "Union branch 10 does not exist");
+		}
+		ref other => panic!("Expected a decode error, found {:?}", other)
+	}
+}
+
+#[test]
+fn io_errors_keep_their_cause() {
+	let err = AvroSchema::from_file("tests/schemas/no_such_schema.avsc").unwrap_err();
+	match err {
+		AvroErr::Io(ref e) => assert_eq!(e.kind(), ErrorKind::NotFound),
+		ref other => panic!("Expected an io error, found {:?}", other)
+	}
+	assert!(err.cause().is_some());
+}
+
+#[test]
+fn schema_errors_point_at_the_invalid_declaration() {
+	let schema = AvroSchema::from_str(r#"{"type": "record", "name": "rec", "fields": [
+		{"name": "a", "type": "long"},
+		{"name": "b", "type": {"type": "array", "items": "widget"}}
+	]}"#).unwrap();
+	match schema.compile() {
+		Err(AvroErr::SchemaParse { ref path, ref reason }) => {
+			assert_eq!(path.to_string(), "$.b[*]");
+			assert_eq!(reason, "Unknown type widget");
+		}
+		other => panic!("Expected a schema error, found {:?}", other)
+	}
+	match AvroSchema::from_str("{\"type\": ") {
+		Err(ref err @ AvroErr::SchemaJson(_)) => assert!(err.cause().is_some()),
+		other => panic!("Expected a json error, found {:?}", other)
+	}
+}
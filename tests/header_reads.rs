@@ -1,26 +1,39 @@
+#![warn(unused_variables, unused_must_use)]
 
-// TODO enable when reading is fully implemented
+extern crate ravro;
 
-// #![warn(unused_variables, unused_must_use)]
+use ravro::{AvroReader, AvroWriter, Codec, Type};
+use std::collections::HashMap;
+use std::io::Cursor;
 
-// extern crate ravro;
+#[test]
+fn test_header_read() {
+	let mut builder = AvroWriter::from_schema("tests/schemas/mapmap_schema.avsc").unwrap();
+	builder.set_codec(Codec::Snappy);
+	let mut writer = builder.build().unwrap();
+	let mut map = HashMap::new();
+	let mut inner_map = HashMap::new();
+	inner_map.insert("one".to_string(), Type::Double(23.));
+	map.insert("hello".to_string(), Type::Map(inner_map));
+	let _ = writer.write(map);
+	let datafile = writer.take_datafile().unwrap();
 
-// use ravro::reader::{AvroReader, BlockReader};
-// use ravro::types::Schema;
-// use ravro::writer::AvroWriter;
-// use std::collections::BTreeMap;
+	let mut reader = AvroReader::new(Cursor::new(datafile)).unwrap();
+	assert_eq!(reader.get_codec(), Codec::Snappy);
+	assert_eq!(reader.header().get_codec().unwrap(), Codec::Snappy);
+	assert!(reader.header().metadata.map_ref().contains_key("avro.schema"));
+	assert!(reader.next().is_some());
+}
 
-// #[test]
-// fn test_header_read() {
-//     let mut writer = AvroWriter::from_schema("tests/schemas/mapmap_schema.avsc").unwrap();
-//     let mut map = BTreeMap::new();
-//     let mut inner_map = BTreeMap::new();
-//     inner_map.insert("one".to_string(), Schema::Double(23.));
-//     map.insert("hello".to_string(), Schema::Map(inner_map));
-//     let _ = writer.write(map);
-//     writer.flush_to_disk("tests/encoded/mapmap_encoded.avro");
-//     // Read the data
-//     let reader = AvroReader::from_path("tests/encoded/mapmap_encoded.avro").unwrap();
-//     let mut it: BlockReader<Schema> = reader.iter_block();
-//     assert!(it.next().is_some());
-// }
+#[test]
+fn metadata_is_written_to_the_header() {
+	let mut builder = AvroWriter::from_schema("tests/schemas/long_schema.avsc").unwrap();
+	builder.set_metadata("origin", b"hour_00".to_vec()).unwrap();
+	assert!(builder.set_metadata("avro.codec", b"snappy".to_vec()).is_err());
+	let mut writer = builder.build().unwrap();
+	writer.write(1i64).unwrap();
+	let reader = AvroReader::new(Cursor::new(writer.take_datafile().unwrap())).unwrap();
+	assert_eq!(reader.get_codec(), Codec::Null);
+	assert_eq!(reader.header().metadata.map_ref().get("origin"), Some(&Type::Bytes(b"hour_00".to_vec())));
+	assert_eq!(reader.count(), 1);
+}
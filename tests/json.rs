@@ -0,0 +1,134 @@
+#![warn(unused_variables, unused_must_use)]
+
+extern crate ravro;
+extern crate serde_json;
+
+use ravro::json::{from_json, to_json, to_json_pretty};
+use ravro::complex::{Enum, Field, Record};
+use ravro::errors::AvroErr;
+use ravro::schema::CompiledSchema;
+use ravro::{AvroSchema, Type};
+use std::collections::HashMap;
+
+fn compile(schema: &str) -> CompiledSchema {
+	AvroSchema::from_str(schema).unwrap().compile().unwrap()
+}
+
+fn json(schema: &CompiledSchema, value: Type) -> String {
+	to_json(schema, &value).unwrap()
+}
+
+#[test]
+fn floats_are_formatted_as_java_formats_them() {
+	let double = compile(r#""double""#);
+	let cases = vec![
+		(3.14, "3.14"), (100.0, "100.0"), (-0.0, "-0.0"), (0.001, "0.001"), (0.0001, "1.0E-4"),
+		(1e7, "1.0E7"), (3.675465665544325e12, "3.675465665544325E12"), (-1.5e-10, "-1.5E-10"),
+		(std::f64::NAN, "\"NaN\""), (std::f64::NEG_INFINITY, "\"-Infinity\"")
+	];
+	for (value, expected) in cases {
+		assert_eq!(json(&double, Type::Double(value)), expected);
+	}
+	let float = compile(r#""float""#);
+	assert_eq!(json(&float, Type::Float(7.325344)), "7.325344");
+	assert_eq!(json(&float, Type::Float(1e10)), "1.0E10");
+}
+
+#[test]
+fn values_round_trip_through_json() {
+	let schema = compile(r#"{"type": "record", "name": "event", "namespace": "ns", "fields": [
+		{"name": "kind", "type": {"type": "enum", "name": "kind", "symbols": ["START", "STOP"]}},
+		{"name": "payload", "type": ["null", "bytes", {"type": "fixed", "name": "md5", "namespace": "hash", "size": 2}, {"type": "array", "items": "int"}]},
+		{"name": "tags", "type": {"type": "map", "values": ["null", "string"]}},
+		{"name": "delta", "type": "float"}
+	]}"#);
+	let event = |kind: &str, payload: Type, tag: Type| {
+		let mut kind_val = Enum::new("kind", &["START", "STOP"]);
+		kind_val.set_value(kind);
+		let mut tags = HashMap::new();
+		tags.insert("b".to_string(), tag);
+		tags.insert("a".to_string(), Type::Union(0, Box::new(Type::Null)));
+		Type::Record(Record::new("event", None, vec![
+			Field::new("kind", Type::Enum(kind_val)),
+			Field::new("payload", payload),
+			Field::new("tags", Type::Map(tags)),
+			Field::new("delta", Type::Float(-2.5))
+		]))
+	};
+	let cases = vec![
+		(event("START", Type::Union(0, Box::new(Type::Null)), Type::Union(1, Box::new(Type::Str("\"é\n".to_string())))),
+			r#"{"kind":"START","payload":null,"tags":{"a":null,"b":{"string":"\"é\n"}},"delta":-2.5}"#),
+		(event("STOP", Type::Union(1, Box::new(Type::Bytes(vec![0, 0x41, 0xe9, 0xff]))), Type::Union(0, Box::new(Type::Null))),
+			r#"{"kind":"STOP","payload":{"bytes":"\u0000Aéÿ"},"tags":{"a":null,"b":null},"delta":-2.5}"#),
+		(event("STOP", Type::Union(2, Box::new(Type::Fixed(vec![1, 200]))), Type::Union(0, Box::new(Type::Null))),
+			r#"{"kind":"STOP","payload":{"hash.md5":"\u0001È"},"tags":{"a":null,"b":null},"delta":-2.5}"#),
+		(event("START", Type::Union(3, Box::new(Type::Array(vec![Type::Int(1), Type::Int(-2)]))), Type::Union(0, Box::new(Type::Null))),
+			r#"{"kind":"START","payload":{"array":[1,-2]},"tags":{"a":null,"b":null},"delta":-2.5}"#)
+	];
+	for (value, expected) in cases {
+		let encoded = to_json(&schema, &value).unwrap();
+		assert_eq!(encoded, expected);
+		let parsed = serde_json::from_str(&encoded).unwrap();
+		assert_eq!(from_json(&schema, &parsed).unwrap(), value);
+	}
+}
+
+#[test]
+fn pretty_json_is_indented() {
+	let schema = compile(r#"{"type": "record", "name": "rec", "fields": [
+		{"name": "inner", "type": {"type": "record", "name": "inner", "fields": [{"name": "id", "type": "long"}]}},
+		{"name": "items", "type": {"type": "array", "items": "string"}},
+		{"name": "empty", "type": {"type": "map", "values": "long"}}
+	]}"#);
+	let value = Type::Record(Record::new("rec", None, vec![
+		Field::new("inner", Type::Record(Record::new("inner", None, vec![Field::new("id", Type::Long(3))]))),
+		Field::new("items", Type::Array(vec![Type::Str("a".to_string()), Type::Str("b".to_string())])),
+		Field::new("empty", Type::Map(HashMap::new()))
+	]));
+	let expected = "{\n  \"inner\" : {\n    \"id\" : 3\n  },\n  \"items\" : [ \"a\", \"b\" ],\n  \"empty\" : { }\n}";
+	assert_eq!(to_json_pretty(&schema, &value).unwrap(), expected);
+}
+
+#[test]
+fn invalid_json_is_rejected_with_its_path() {
+	let schema = compile(r#"{"type": "record", "name": "rec", "fields": [
+		{"name": "count", "type": "int"},
+		{"name": "id", "type": {"type": "fixed", "name": "id", "size": 2}},
+		{"name": "choice", "type": ["null", "long"]},
+		{"name": "tags", "type": {"type": "map", "values": "int"}}
+	]}"#);
+	let cases = vec![
+		(r#"{"count": 2147483648, "id": "ab", "choice": null}"#, "$.count"),
+		(r#"{"count": 1, "id": "abc", "choice": null}"#, "$.id"),
+		(r#"{"count": 1, "id": "aĀ", "choice": null}"#, "$.id"),
+		(r#"{"count": 1, "id": "ab", "choice": {"int": 1}}"#, "$.choice"),
+		(r#"{"count": 1, "id": "ab", "choice": {"long": "1"}}"#, "$.choice[1]"),
+		(r#"{"count": 1, "id": "ab", "choice": null, "tags": {"a.b": "c"}}"#, "$.tags[\"a.b\"]"),
+		(r#"{"count": 1, "id": "ab"}"#, "$")
+	];
+	for (json, path) in cases {
+		match from_json(&schema, &serde_json::from_str(json).unwrap()) {
+			Err(AvroErr::Validation { path: ref error_path, .. }) => assert_eq!(error_path.to_string(), path, "{}", json),
+			other => panic!("Expected a validation error for {}, found {:?}", json, other)
+		}
+	}
+
+	let record = |choice: Type, tags: HashMap<String, Type>| Type::Record(Record::new("rec", None, vec![
+		Field::new("count", Type::Int(1)),
+		Field::new("id", Type::Fixed(b"ab".to_vec())),
+		Field::new("choice", choice),
+		Field::new("tags", Type::Map(tags))
+	]));
+	let mut tags = HashMap::new();
+	tags.insert("a.b".to_string(), Type::Str("c".to_string()));
+	let invalid = vec![
+		(record(Type::Union(1, Box::new(Type::Str("1".to_string()))), HashMap::new()), "$.choice[1]"),
+		(record(Type::Union(0, Box::new(Type::Null)), tags), "$.tags[\"a.b\"]")
+	];
+	for (value, path) in invalid {
+		match to_json(&schema, &value) {
+			Err(AvroErr::Validation { path: ref error_path, .. }) => assert_eq!(error_path.to_string(), path, "{:?}", value),
+			other => panic!("Expected a validation error for {:?}, found {:?}", value, other)
+		}
+	}
+}
@@ -0,0 +1,186 @@
+#![warn(unused_variables, unused_must_use)]
+
+extern crate ravro;
+
+mod common;
+
+use common::test_writer;
+use ravro::{AvroReader, AvroSchema, Codec, DecoderLimits, SliceReader, Type, TypeRef};
+use ravro::check::check;
+use ravro::codec::{Decoder, Encoder};
+use ravro::errors::AvroErr;
+use std::io::Cursor;
+
+fn compile(schema: &str) -> ravro::schema::CompiledSchema {
+	AvroSchema::from_str(schema).unwrap().compile().unwrap()
+}
+
+fn assert_limit(result: Result<impl std::fmt::Debug, AvroErr>, expected: &str) {
+	match result {
+		Err(AvroErr::LimitExceeded { limit, .. }) => assert_eq!(limit, expected),
+		other => panic!("Expected the {} limit to be exceeded, found {:?}", expected, other)
+	}
+}
+
+#[test]
+fn huge_length_prefixes_are_rejected() {
+	// A length of 2^62 followed by a few bytes
+	let data = vec![0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x01, 1, 2, 3];
+	assert_limit(Vec::<u8>::decode(&mut &data[..]), "max_length");
+	assert_limit(String::decode(&mut &data[..]), "max_length");
+	let schema = compile(r#""bytes""#);
+	assert_limit(Type::decode_with(&schema, &mut Cursor::new(&data)), "max_length");
+	assert_limit(TypeRef::decode(&schema, &mut &data[..]), "max_length");
+
+	let limits = DecoderLimits { max_length: 2, ..DecoderLimits::default() };
+	let mut data = vec![];
+	"abc".to_string().encode(&mut data).unwrap();
+	assert_eq!(String::decode(&mut &data[..]).unwrap(), "abc");
+	assert_limit(String::decode_with_limits(&mut &data[..], &limits), "max_length");
+	assert_limit(Vec::<u8>::decode_with_limits(&mut &data[..], &limits), "max_length");
+}
+
+#[test]
+fn lengths_and_item_counts_follow_the_limits() {
+	let limits = DecoderLimits { max_length: 4, max_items: 3, ..DecoderLimits::default() };
+	let schema = compile(r#"{"type": "array", "items": "string"}"#);
+	let mut value = vec![];
+	Type::Array(vec![Type::Str("abc".to_string()), Type::Str("abcd".to_string())]).encode_with(&schema, &mut value).unwrap();
+	assert!(Type::decode_with_limits(&schema, &limits, &mut Cursor::new(&value)).is_ok());
+
+	let mut value = vec![];
+	Type::Array(vec![Type::Str("abcde".to_string())]).encode_with(&schema, &mut value).unwrap();
+	match Type::decode_with_limits(&schema, &limits, &mut Cursor::new(&value)) {
+		Err(AvroErr::LimitExceeded { ref path, limit, value, max, .. }) => {
+			assert_eq!(path.to_string(), "$[0]");
+			assert_eq!((limit, value, max), ("max_length", 5, 4));
+		}
+		other => panic!("Expected the length limit to be exceeded, found {:?}", other)
+	}
+
+	let mut value = vec![];
+	let items = (0..4).map(|i| Type::Str(i.to_string())).collect();
+	Type::Array(items).encode_with(&schema, &mut value).unwrap();
+	assert_limit(Type::decode_with_limits(&schema, &limits, &mut Cursor::new(&value)), "max_items");
+	assert_limit(TypeRef::decode_with_limits(&schema, &limits, &mut &value[..]), "max_items");
+}
+
+#[test]
+fn nesting_depth_is_limited() {
+	// A record which can only be decoded by recursing forever, without consuming any data
+	let schema = compile(r#"{"type": "record", "name": "endless", "fields": [{"name": "next", "type": "endless"}]}"#);
+	assert_limit(Type::decode_with(&schema, &mut Cursor::new(vec![])), "max_depth");
+	assert_limit(TypeRef::decode(&schema, &mut &[][..]), "max_depth");
+
+	let schema = compile(r#"{"type": "array", "items": {"type": "array", "items": "long"}}"#);
+	let value = Type::Array(vec![Type::Array(vec![Type::Long(1)])]);
+	let mut encoded = vec![];
+	value.encode_with(&schema, &mut encoded).unwrap();
+	let limits = DecoderLimits { max_depth: 1, ..DecoderLimits::default() };
+	assert_limit(Type::decode_with_limits(&schema, &limits, &mut Cursor::new(&encoded)), "max_depth");
+	let limits = DecoderLimits { max_depth: 2, ..DecoderLimits::default() };
+	assert_eq!(Type::decode_with_limits(&schema, &limits, &mut Cursor::new(&encoded)).unwrap(), value);
+}
+
+fn string_datafile(codec: Codec) -> Vec<u8> {
+	let mut data_writer = test_writer("tests/schemas/string_schema.avsc", codec);
+	for _ in 0..4 {
+		data_writer.write(Type::Str("0".repeat(16 * 1024))).unwrap();
+	}
+	data_writer.take_datafile().unwrap()
+}
+
+#[test]
+fn block_size_is_limited_before_and_after_decompression() {
+	let limits = DecoderLimits { max_block_size: 32 * 1024, ..DecoderLimits::default() };
+	for codec in vec![Codec::Null, Codec::Snappy, Codec::Deflate].into_iter() {
+		let datafile = string_datafile(codec);
+		let mut reader = AvroReader::new(Cursor::new(datafile.clone())).unwrap();
+		reader.set_limits(limits);
+		let results: Vec<_> = reader.collect();
+		assert_eq!(results.len(), 1);
+		match results[0] {
+			Err(AvroErr::LimitExceeded { limit, position, .. }) => {
+				assert_eq!(limit, "max_block_size");
+				assert_eq!(position.block, Some(0));
+			}
+			ref other => panic!("Expected the block size limit to be exceeded, found {:?}", other)
+		}
+
+		let mut reader = AvroReader::new(Cursor::new(datafile.clone())).unwrap();
+		reader.set_limits(limits);
+		assert_limit(reader.parallel(2).next().unwrap(), "max_block_size");
+
+		let mut reader = AvroReader::new(Cursor::new(datafile)).unwrap();
+		reader.set_limits(DecoderLimits { max_block_size: 64 * 1024 + 1024, ..limits });
+		assert_eq!(reader.count(), 4);
+	}
+}
+
+#[test]
+fn slice_reader_follows_the_limits() {
+	let datafile = string_datafile(Codec::Null);
+	let mut reader = SliceReader::new(&datafile).unwrap();
+	reader.set_limits(DecoderLimits { max_length: 1024, ..DecoderLimits::default() });
+	assert_limit(reader.records().unwrap().next().unwrap(), "max_length");
+	reader.set_limits(DecoderLimits { max_block_size: 1024, ..DecoderLimits::default() });
+	assert_limit(reader.blocks().next().unwrap(), "max_block_size");
+}
+
+#[test]
+fn items_encoded_in_no_bytes_are_limited() {
+	// An array of 2^24 nulls in five bytes
+	let data = vec![0x80, 0x80, 0x80, 0x10, 0x00];
+	let schema = compile(r#"{"type": "array", "items": "null"}"#);
+	assert_limit(Type::decode_with(&schema, &mut Cursor::new(&data)), "max_zero_width_items");
+	assert_limit(TypeRef::decode(&schema, &mut &data[..]), "max_zero_width_items");
+
+	// Nesting arrays of empty records does not get around the limit
+	let schema = compile(r#"{"type": "array", "items": {"type": "array", "items": {"type": "record", "name": "empty", "fields": []}}}"#);
+	// Two arrays of 256 empty records
+	let data = vec![0x04, 0x80, 0x04, 0x00, 0x80, 0x04, 0x00, 0x00];
+	let limits = DecoderLimits { max_zero_width_items: 300, ..DecoderLimits::default() };
+	assert_limit(Type::decode_with_limits(&schema, &limits, &mut Cursor::new(&data)), "max_zero_width_items");
+	let limits = DecoderLimits { max_zero_width_items: 512, ..DecoderLimits::default() };
+	assert!(Type::decode_with_limits(&schema, &limits, &mut Cursor::new(&data)).is_ok());
+}
+
+/// Rewrites the only block of an empty data file of the given schema with the given record count and data
+fn forged_datafile(schema_file: &str, count: i64, data: &[u8]) -> Vec<u8> {
+	let datafile = test_writer(schema_file, Codec::Null).take_datafile().unwrap();
+	// The empty block ending the data file is made up of two zeros and the sync marker
+	let (header, sync_marker) = datafile.split_at(datafile.len() - 16);
+	let mut forged = header[..header.len() - 2].to_vec();
+	Type::Long(count).encode(&mut forged).unwrap();
+	Type::Long(data.len() as i64).encode(&mut forged).unwrap();
+	forged.extend_from_slice(data);
+	forged.extend_from_slice(sync_marker);
+	forged
+}
+
+#[test]
+fn block_record_counts_are_limited() {
+	// A block claiming 10^8 nulls, which take up no bytes
+	let datafile = forged_datafile("tests/schemas/null_schema.avsc", 100_000_000, &[]);
+	let reader = AvroReader::new(Cursor::new(datafile.clone())).unwrap();
+	assert_limit(reader.parallel(1).next().unwrap(), "max_block_records");
+	let mut reader = AvroReader::new(Cursor::new(datafile.clone())).unwrap();
+	assert_limit(reader.next().unwrap(), "max_block_records");
+	assert_limit(SliceReader::new(&datafile).unwrap().blocks().next().unwrap(), "max_block_records");
+	let report = check(Cursor::new(datafile.clone()));
+	assert!(!report.is_ok());
+	assert_limit(Err::<(), _>(report.fatal.unwrap()), "max_block_records");
+}
+
+#[test]
+fn blocks_too_small_for_their_records_are_rejected() {
+	let datafile = forged_datafile("tests/schemas/long_schema.avsc", 1000, &[0x02; 999]);
+	let reader = AvroReader::new(Cursor::new(datafile.clone())).unwrap();
+	match reader.parallel(1).next() {
+		Some(Err(AvroErr::Decode { .. })) => {}
+		other => panic!("Expected the block to be rejected, found {:?}", other)
+	}
+	let report = check(Cursor::new(datafile));
+	assert_eq!(report.failed_blocks().len(), 1);
+	assert_eq!(report.failed_blocks()[0].decoded, 0);
+}
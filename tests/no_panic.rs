@@ -0,0 +1,145 @@
+#![warn(unused_variables, unused_must_use)]
+
+extern crate rand;
+extern crate ravro;
+
+mod common;
+
+use common::test_writer;
+use rand::{Isaac64Rng, Rng, SeedableRng};
+use ravro::{AvroReader, AvroSchema, Codec, SliceReader, Type, TypeRef};
+use ravro::complex::{Enum, Field, Record};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::panic::{self, AssertUnwindSafe};
+
+const MUTATIONS: usize = 500;
+
+fn datafiles() -> Vec<(String, Vec<u8>)> {
+	let mut datafiles = vec![];
+	for codec in vec![Codec::Null, Codec::Snappy, Codec::Deflate].into_iter() {
+		let mut nested = test_writer("tests/schemas/nested_schema.avsc", codec);
+		let mut union = test_writer("tests/schemas/union_schema.avsc", codec);
+		let mut array = test_writer("tests/schemas/array_schema.avsc", codec);
+		let mut enums = test_writer("tests/schemas/enum_schema.avsc", codec);
+		for i in 0..20 {
+			let mut map = HashMap::new();
+			map.insert(format!("key {}", i), Type::Float(i as f32));
+			nested.write(Record::new("dashboard_stats", None, vec![
+				Field::new("name", Type::Str(format!("record {}", i))),
+				Field::new("foo", Type::Map(map)),
+				Field::new("inner_rec", Type::Record(Record::new("id_rec", None, vec![Field::new("id", Type::Long(i))])))
+			])).unwrap();
+			union.write(Record::new("reading", None, vec![
+				Field::new("id", Type::Fixed(vec![i as u8; 4])),
+				Field::new("value", Type::Union(1, Box::new(Type::Double(i as f64)))),
+				Field::new("next_id", Type::Union(1, Box::new(Type::Fixed(vec![0, 1, 2, 3]))))
+			])).unwrap();
+			array.write(Type::Array(vec![Type::Str("a".to_string()), Type::Str(format!("item {}", i))])).unwrap();
+			let mut e = Enum::new("Foo", &["CLUBS", "SPADE", "DIAMOND"]);
+			e.set_value(["CLUBS", "SPADE", "DIAMOND"][i as usize % 3]);
+			enums.write(Type::Enum(e)).unwrap();
+			if i % 7 == 6 {
+				nested.commit_block().unwrap();
+				union.commit_block().unwrap();
+				array.commit_block().unwrap();
+				enums.commit_block().unwrap();
+			}
+		}
+		datafiles.push((format!("nested {:?}", codec), nested.take_datafile().unwrap()));
+		datafiles.push((format!("union {:?}", codec), union.take_datafile().unwrap()));
+		datafiles.push((format!("array {:?}", codec), array.take_datafile().unwrap()));
+		datafiles.push((format!("enum {:?}", codec), enums.take_datafile().unwrap()));
+	}
+	datafiles
+}
+
+/// Flips, drops or inserts a few random bytes
+fn mutate(rng: &mut Isaac64Rng, data: &[u8]) -> Vec<u8> {
+	let mut data = data.to_vec();
+	for _ in 0..rng.gen_range(1, 4) {
+		let idx = rng.gen_range(0, data.len());
+		match rng.gen_range(0, 4) {
+			0 => data[idx] ^= 1 << rng.gen_range(0, 8),
+			1 => data[idx] = rng.gen(),
+			2 => { data.remove(idx); }
+			_ => data.insert(idx, rng.gen())
+		}
+	}
+	if rng.gen_weighted_bool(8) {
+		let len = rng.gen_range(0, data.len());
+		data.truncate(len);
+	}
+	data
+}
+
+/// Reads the data file in every way the crate offers, ignoring the errors
+fn read_all(data: &[u8]) {
+	if let Ok(reader) = AvroReader::new(Cursor::new(data)) {
+		for _ in reader {}
+	}
+	if let Ok(reader) = AvroReader::new(Cursor::new(data)) {
+		for _ in reader.recover() {}
+	}
+	if let Ok(reader) = AvroReader::new(Cursor::new(data)) {
+		for _ in reader.parallel(2) {}
+	}
+	if let Ok(reader) = SliceReader::new(data) {
+		if let Ok(records) = reader.records() {
+			for _ in records {}
+		}
+		for block in reader.blocks() {
+			if let Ok(block) = block {
+				if let Ok(data) = block.decompress() {
+					for _ in reader.block_records(&data, block.count) {}
+				}
+			}
+		}
+	}
+}
+
+#[test]
+fn corrupt_datafiles_do_not_panic() {
+	let mut rng = Isaac64Rng::from_seed(&[39][..]);
+	for (name, datafile) in datafiles() {
+		for i in 0..MUTATIONS {
+			let mutated = mutate(&mut rng, &datafile);
+			let result = panic::catch_unwind(AssertUnwindSafe(|| read_all(&mutated)));
+			assert!(result.is_ok(), "Reading mutation {} of the {} data file panicked: {:?}", i, name, mutated);
+		}
+	}
+}
+
+#[test]
+fn corrupt_values_do_not_panic() {
+	let mut rng = Isaac64Rng::from_seed(&[40][..]);
+	let schema = AvroSchema::from_file("tests/schemas/union_schema.avsc").unwrap().compile().unwrap();
+	for _ in 0..MUTATIONS * 4 {
+		let len = rng.gen_range(0, 40);
+		let data: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+		let result = panic::catch_unwind(AssertUnwindSafe(|| {
+			let _ = Type::decode_with(&schema, &mut Cursor::new(&data));
+			let _ = TypeRef::decode(&schema, &mut &data[..]);
+		}));
+		assert!(result.is_ok(), "Decoding {:?} panicked", data);
+	}
+}
+
+#[test]
+fn invalid_schemas_do_not_panic() {
+	let mut rng = Isaac64Rng::from_seed(&[41][..]);
+	let schemas = vec!["nested_schema", "union_schema", "enum_schema", "map_schema", "mapmap_schema", "journal_schema"];
+	for name in schemas {
+		let declaration = std::fs::read(format!("tests/schemas/{}.avsc", name)).unwrap();
+		for _ in 0..MUTATIONS {
+			let mutated = mutate(&mut rng, &declaration);
+			let result = panic::catch_unwind(AssertUnwindSafe(|| {
+				if let Ok(schema) = AvroSchema::from_str(&String::from_utf8_lossy(&mutated)) {
+					let _ = schema.compile();
+					let _ = schema.record_field_pairs();
+				}
+			}));
+			assert!(result.is_ok(), "Parsing mutation of {} panicked: {}", name, String::from_utf8_lossy(&mutated));
+		}
+	}
+}
@@ -0,0 +1,104 @@
+#![warn(unused_variables, unused_must_use)]
+
+extern crate ravro;
+
+use ravro::{AvroReader, AvroWriter, Codec, Type};
+use ravro::errors::AvroErr;
+use std::io::Cursor;
+
+fn long_datafile(codec: Codec, workers: usize, records: i64) -> Vec<u8> {
+	let mut builder = AvroWriter::from_schema("tests/schemas/long_schema.avsc").unwrap();
+	builder.set_codec(codec);
+	builder.set_seed(7);
+	builder.set_workers(workers);
+	let mut data_writer = builder.build().unwrap();
+	for i in 0..records {
+		data_writer.write(i * 31).unwrap();
+		if i % 10 == 9 {
+			data_writer.commit_block().unwrap();
+		}
+	}
+	data_writer.take_datafile().unwrap()
+}
+
+#[test]
+fn parallel_writer_gives_identical_datafiles() {
+	for codec in vec![Codec::Null, Codec::Snappy, Codec::Deflate].into_iter() {
+		let sequential = long_datafile(codec, 0, 1005);
+		assert_eq!(long_datafile(codec, 1, 1005), sequential);
+		assert_eq!(long_datafile(codec, 4, 1005), sequential);
+	}
+}
+
+#[test]
+fn parallel_writer_can_be_reused() {
+	let mut builder = AvroWriter::from_schema("tests/schemas/long_schema.avsc").unwrap();
+	builder.set_codec(Codec::Snappy);
+	builder.set_workers(2);
+	let mut data_writer = builder.build().unwrap();
+	for round in 0..3 {
+		for i in 0..50i64 {
+			data_writer.write(i).unwrap();
+			data_writer.commit_block().unwrap();
+		}
+		let datafile = data_writer.take_datafile().unwrap();
+		let reader = AvroReader::new(Cursor::new(datafile)).unwrap();
+		assert_eq!(reader.count(), 50, "round {}", round);
+	}
+}
+
+#[test]
+fn parallel_reader_yields_records_in_order() {
+	for codec in vec![Codec::Null, Codec::Snappy, Codec::Deflate].into_iter() {
+		let datafile = long_datafile(codec, 0, 1005);
+		let reader = AvroReader::new(Cursor::new(datafile)).unwrap();
+		let records: Vec<Type> = reader.parallel(3).map(|r| r.unwrap()).collect();
+		let expected: Vec<Type> = (0..1005).map(|i| Type::Long(i * 31)).collect();
+		assert_eq!(records, expected);
+	}
+}
+
+#[test]
+fn parallel_reader_stops_at_corrupt_block() {
+	let mut datafile = long_datafile(Codec::Snappy, 0, 100);
+	let blocks: Vec<_> = AvroReader::new(Cursor::new(datafile.clone())).unwrap().iter_block()
+		.map(|b| b.unwrap())
+		.collect();
+	// Flip a byte of the checksum of the sixth block
+	let block = &blocks[5];
+	let checksum_offset = block.offset as usize + 2 + block.data.len() - 1;
+	datafile[checksum_offset] ^= 0xff;
+
+	let reader = AvroReader::new(Cursor::new(datafile)).unwrap();
+	let results: Vec<_> = reader.parallel(4).collect();
+	assert_eq!(results.len(), 51);
+	for (i, result) in results[..50].iter().enumerate() {
+		assert_eq!(*result.as_ref().unwrap(), Type::Long(i as i64 * 31));
+	}
+	match results[50] {
+		Err(AvroErr::Codec { ref codec, position, .. }) => {
+			assert_eq!(codec, "snappy");
+			assert_eq!(position.block, Some(5));
+			assert_eq!(position.offset, Some(block.offset));
+		}
+		ref other => panic!("Expected a codec error, found {:?}", other)
+	}
+}
+
+#[test]
+fn parallel_reader_reports_truncated_data_file() {
+	let mut datafile = long_datafile(Codec::Deflate, 0, 100);
+	let blocks: Vec<_> = AvroReader::new(Cursor::new(datafile.clone())).unwrap().iter_block()
+		.map(|b| b.unwrap())
+		.collect();
+	// Cut the data file in the middle of the last block of records
+	datafile.truncate(blocks[9].offset as usize + 5);
+	let reader = AvroReader::new(Cursor::new(datafile)).unwrap();
+	let results: Vec<_> = reader.parallel(2).collect();
+	assert_eq!(results.len(), 91);
+	assert!(results[..90].iter().all(|r| r.is_ok()));
+	match results[90] {
+		Err(AvroErr::TruncatedBlock(_)) => (),
+		ref other => panic!("Expected a truncated block, found {:?}", other)
+	}
+}
@@ -22,7 +22,7 @@ fn write_null() {
 		let datafile_buffer = data_writer.take_datafile().unwrap();
 		let mut open_options = OpenOptions::new().truncate(true).write(true).create(true).open(datafile_name).unwrap();
     	let _ = open_options.write_all(datafile_buffer.as_slice());
-		assert_eq!(Ok("null\nnull\n".to_string()), common::get_java_tool_output(datafile_name));
+		assert_eq!(Ok("null\nnull\n".to_string()), common::tojson_output(datafile_name));
 	}
 }
 
@@ -39,7 +39,7 @@ fn write_bool() {
 		let datafile_buffer = data_writer.take_datafile().unwrap();
 		let mut open_options = OpenOptions::new().truncate(true).write(true).create(true).open(datafile_name).unwrap();
     	let _ = open_options.write_all(datafile_buffer.as_slice());
-		assert_eq!(Ok("true\nfalse\n".to_string()), common::get_java_tool_output(datafile_name));
+		assert_eq!(Ok("true\nfalse\n".to_string()), common::tojson_output(datafile_name));
 	}
 }
 
@@ -56,7 +56,7 @@ fn write_int() {
 		let datafile_buffer = data_writer.take_datafile().unwrap();
 		let mut open_options = OpenOptions::new().truncate(true).write(true).create(true).open(datafile_name).unwrap();
     	let _ = open_options.write_all(datafile_buffer.as_slice());
-		assert_eq!(Ok("3454\n567561\n".to_string()), common::get_java_tool_output(datafile_name));
+		assert_eq!(Ok("3454\n567561\n".to_string()), common::tojson_output(datafile_name));
 	}
 }
 
@@ -73,7 +73,7 @@ fn write_string() {
 		let datafile_buffer = data_writer.take_datafile().unwrap();
 		let mut open_options = OpenOptions::new().truncate(true).write(true).create(true).open(datafile_name).unwrap();
     	let _ = open_options.write_all(datafile_buffer.as_slice());
-		assert_eq!(Ok("\"abcd\"\n\"efgh\"\n".to_string()), common::get_java_tool_output(datafile_name));
+		assert_eq!(Ok("\"abcd\"\n\"efgh\"\n".to_string()), common::tojson_output(datafile_name));
 	}
 }
 
@@ -89,7 +89,7 @@ fn write_bytes() {
 		let datafile_buffer = data_writer.take_datafile().unwrap();
 		let mut open_options = OpenOptions::new().truncate(true).write(true).create(true).open(datafile_name).unwrap();
     	let _ = open_options.write_all(datafile_buffer.as_slice());
-		assert_eq!(Ok("\"ravro\"\n".to_string()), common::get_java_tool_output(datafile_name));
+		assert_eq!(Ok("\"ravro\"\n".to_string()), common::tojson_output(datafile_name));
 	}
 }
 
@@ -109,7 +109,7 @@ fn write_long() {
 		let datafile_buffer = data_writer.take_datafile().unwrap();
 		let mut open_options = OpenOptions::new().truncate(true).write(true).create(true).open(datafile_name).unwrap();
     	let _ = open_options.write_all(datafile_buffer.as_slice());
-		assert_eq!(Ok("4354645765756754\n24564564534\n34543645\n424543543\n534543543\n".to_string()), common::get_java_tool_output(datafile_name));
+		assert_eq!(Ok("4354645765756754\n24564564534\n34543645\n424543543\n534543543\n".to_string()), common::tojson_output(datafile_name));
 	}
 }
 
@@ -126,7 +126,7 @@ fn write_float() {
 		let datafile_buffer = data_writer.take_datafile().unwrap();
 		let mut open_options = OpenOptions::new().truncate(true).write(true).create(true).open(datafile_name).unwrap();
     	let _ = open_options.write_all(datafile_buffer.as_slice());
-		assert_eq!(Ok("54.254\n7.325344\n".to_string()), common::get_java_tool_output(datafile_name));
+		assert_eq!(Ok("54.254\n7.325344\n".to_string()), common::tojson_output(datafile_name));
 	}
 }
 
@@ -143,6 +143,6 @@ fn write_double() {
 		let datafile_buffer = data_writer.take_datafile().unwrap();
 		let mut open_options = OpenOptions::new().truncate(true).write(true).create(true).open(datafile_name).unwrap();
     	let _ = open_options.write_all(datafile_buffer.as_slice());
-		assert_eq!(Ok("3.14\n3.675465665544325E12\n".to_string()), common::get_java_tool_output(datafile_name));
+		assert_eq!(Ok("3.14\n3.675465665544325E12\n".to_string()), common::tojson_output(datafile_name));
 	}
 }
@@ -0,0 +1,321 @@
+#![warn(unused_variables, unused_must_use)]
+
+extern crate ravro;
+
+mod common;
+
+use common::test_writer;
+use ravro::{AvroReader, AvroSchema, Codec, DecoderLimits, EncoderOptions, Type};
+use ravro::complex::{Field, Record};
+use ravro::errors::AvroErr;
+use ravro::projection::Projection;
+use ravro::schema::CompiledSchema;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Cursor;
+
+const JOURNAL_SUBSET: &str = r#"{"type": "record", "name": "dashboard_stats", "fields": [
+	{"name": "__REALTIME_TIMESTAMP", "type": "string"},
+	{"name": "_HOSTNAME", "type": "string"},
+	{"name": "MESSAGE", "type": "string"}
+]}"#;
+
+fn compile(schema: &str) -> CompiledSchema {
+	AvroSchema::from_str(schema).unwrap().compile().unwrap()
+}
+
+fn journal_fields() -> Vec<String> {
+	let schema = AvroSchema::from_file("tests/schemas/journal.avsc").unwrap();
+	schema.record_field_pairs().unwrap().into_iter().map(|(name, _)| name).collect()
+}
+
+fn journal_datafile(codec: Codec) -> Vec<u8> {
+	let mut data_writer = test_writer("tests/schemas/journal.avsc", codec);
+	for i in 0..100 {
+		let fields = journal_fields().iter().map(|name| Field::new(name, Type::Str(format!("{} {}", name, i)))).collect();
+		data_writer.write(Record::new("dashboard_stats", None, fields)).unwrap();
+	}
+	data_writer.take_datafile().unwrap()
+}
+
+fn journal_subset(i: usize) -> Type {
+	Type::Record(Record::new("dashboard_stats", None, vec![
+		Field::new("__REALTIME_TIMESTAMP", Type::Str(format!("__REALTIME_TIMESTAMP {}", i))),
+		Field::new("_HOSTNAME", Type::Str(format!("_HOSTNAME {}", i))),
+		Field::new("MESSAGE", Type::Str(format!("MESSAGE {}", i)))
+	]))
+}
+
+#[test]
+fn values_are_skipped_whole() {
+	for entry in fs::read_dir("tests/conformance").unwrap() {
+		let path = entry.unwrap().path();
+		if path.extension().map_or(true, |ext| ext != "bin") {
+			continue;
+		}
+		let schema = AvroSchema::from_file(path.with_extension("avsc")).unwrap().compile().unwrap();
+		let golden = fs::read(&path).unwrap();
+		let mut reader = Cursor::new(&golden);
+		let limits = DecoderLimits { max_depth: 128, ..DecoderLimits::default() };
+		Type::skip_with_limits(&schema, &limits, &mut reader).unwrap();
+		assert_eq!(reader.position() as usize, golden.len(), "{:?}", path);
+	}
+}
+
+#[test]
+fn sized_blocks_are_skipped_whole() {
+	let schema = compile(r#"{"type": "record", "name": "rec", "fields": [
+		{"name": "items", "type": {"type": "array", "items": "string"}},
+		{"name": "last", "type": "long"}
+	]}"#);
+	let items = (0..100).map(|i| Type::Str(i.to_string())).collect();
+	let value = Type::Record(Record::new("rec", None, vec![Field::new("items", Type::Array(items)), Field::new("last", Type::Long(7))]));
+	for options in vec![EncoderOptions::default(), EncoderOptions { block_items: Some(16) }] {
+		let mut encoded = vec![];
+		value.encode_with_options(&schema, &options, &mut encoded).unwrap();
+		let mut reader = Cursor::new(&encoded);
+		Type::skip(&schema, &mut reader).unwrap();
+		assert_eq!(reader.position() as usize, encoded.len());
+	}
+	// A truncated value can not be skipped
+	let mut encoded = vec![];
+	value.encode_with_options(&schema, &EncoderOptions { block_items: Some(16) }, &mut encoded).unwrap();
+	encoded.truncate(encoded.len() - 20);
+	assert!(Type::skip(&schema, &mut Cursor::new(&encoded)).is_err());
+}
+
+#[test]
+fn readers_decode_the_fields_of_the_reader_schema() {
+	for codec in vec![Codec::Null, Codec::Deflate].into_iter() {
+		let datafile = journal_datafile(codec);
+		let mut reader = AvroReader::new(Cursor::new(datafile.clone())).unwrap();
+		reader.set_reader_schema(&AvroSchema::from_str(JOURNAL_SUBSET).unwrap()).unwrap();
+		let records: Vec<Type> = reader.map(|r| r.unwrap()).collect();
+		assert_eq!(records, (0..100).map(journal_subset).collect::<Vec<_>>());
+
+		let mut reader = AvroReader::new(Cursor::new(datafile)).unwrap();
+		reader.set_reader_schema(&AvroSchema::from_str(JOURNAL_SUBSET).unwrap()).unwrap();
+		let records: Vec<Type> = reader.parallel(2).map(|r| r.unwrap()).collect();
+		assert_eq!(records, (0..100).map(journal_subset).collect::<Vec<_>>());
+	}
+}
+
+#[test]
+fn nested_records_are_projected() {
+	let writer = compile(r#"{"type": "record", "name": "outer", "fields": [
+		{"name": "tags", "type": {"type": "map", "values": "string"}},
+		{"name": "inner", "type": {"type": "array", "items": {"type": "record", "name": "inner", "fields": [
+			{"name": "id", "type": "long"},
+			{"name": "note", "type": ["null", "string"]}
+		]}}},
+		{"name": "id", "type": "long"}
+	]}"#);
+	let reader = compile(r#"{"type": "record", "name": "outer", "fields": [
+		{"name": "inner", "type": {"type": "array", "items": {"type": "record", "name": "inner", "fields": [
+			{"name": "id", "type": "long"}
+		]}}}
+	]}"#);
+	let inner = |id, note: Type| Type::Record(Record::new("inner", None, vec![Field::new("id", Type::Long(id)), Field::new("note", note)]));
+	let mut tags = HashMap::new();
+	tags.insert("a".to_string(), Type::Str("b".to_string()));
+	let value = Type::Record(Record::new("outer", None, vec![
+		Field::new("tags", Type::Map(tags)),
+		Field::new("inner", Type::Array(vec![inner(1, Type::Union(0, Box::new(Type::Null))), inner(2, Type::Union(1, Box::new(Type::Str("x".to_string()))))])),
+		Field::new("id", Type::Long(3))
+	]));
+	let mut encoded = vec![];
+	value.encode_with(&writer, &mut encoded).unwrap();
+	let projection = Projection::new(&writer, &reader).unwrap();
+	let mut data = Cursor::new(&encoded);
+	let projected = projection.decode(&DecoderLimits::default(), &mut data).unwrap();
+	assert_eq!(data.position() as usize, encoded.len());
+	let inner = |id| Type::Record(Record::new("inner", None, vec![Field::new("id", Type::Long(id))]));
+	assert_eq!(projected, Type::Record(Record::new("outer", None, vec![Field::new("inner", Type::Array(vec![inner(1), inner(2)]))])));
+}
+
+#[test]
+fn projected_fields_follow_the_reader_schema() {
+	let writer = compile(r#"{"type": "record", "name": "outer", "fields": [
+		{"name": "id", "type": "long"},
+		{"name": "skipped", "type": "string"},
+		{"name": "inner", "type": {"type": "record", "name": "inner", "fields": [
+			{"name": "a", "type": "int"},
+			{"name": "b", "type": "int"}
+		]}}
+	]}"#);
+	let reader = compile(r#"{"type": "record", "name": "outer", "fields": [
+		{"name": "inner", "type": {"type": "record", "name": "inner", "fields": [
+			{"name": "b", "type": "int"},
+			{"name": "a", "type": "int"}
+		]}},
+		{"name": "id", "type": "long"}
+	]}"#);
+	let value = Type::Record(Record::new("outer", None, vec![
+		Field::new("id", Type::Long(1)),
+		Field::new("skipped", Type::Str("x".to_string())),
+		Field::new("inner", Type::Record(Record::new("inner", None, vec![Field::new("a", Type::Int(2)), Field::new("b", Type::Int(3))])))
+	]));
+	let mut encoded = vec![];
+	value.encode_with(&writer, &mut encoded).unwrap();
+	let projection = Projection::new(&writer, &reader).unwrap();
+	let projected = projection.decode(&DecoderLimits::default(), &mut Cursor::new(&encoded)).unwrap();
+	assert_eq!(projected, Type::Record(Record::new("outer", None, vec![
+		Field::new("inner", Type::Record(Record::new("inner", None, vec![Field::new("b", Type::Int(3)), Field::new("a", Type::Int(2))]))),
+		Field::new("id", Type::Long(1))
+	])));
+}
+
+#[test]
+fn recursive_records_are_projected() {
+	let writer = AvroSchema::from_file("tests/conformance/nested.avsc").unwrap().compile().unwrap();
+	let reader = compile(r#"{"type": "record", "name": "node", "fields": [
+		{"name": "depth", "type": "int"},
+		{"name": "next", "type": ["null", "node"]}
+	]}"#);
+	let projection = Projection::new(&writer, &reader).unwrap();
+	let golden = fs::read("tests/conformance/nested.bin").unwrap();
+	let limits = DecoderLimits { max_depth: 128, ..DecoderLimits::default() };
+	let mut value = projection.decode(&limits, &mut Cursor::new(&golden)).unwrap();
+	for depth in 0..40 {
+		let mut fields = match value {
+			Type::Record(rec) => rec.fields,
+			other => panic!("Expected a record, found {:?}", other)
+		};
+		assert_eq!(fields.len(), 2);
+		assert_eq!(fields[0].ty, Type::Int(depth));
+		value = match fields.pop().unwrap().ty {
+			Type::Union(1, next) => *next,
+			Type::Union(0, _) if depth == 39 => break,
+			other => panic!("Expected a union, found {:?}", other)
+		};
+	}
+}
+
+#[test]
+fn mismatching_reader_schemas_are_rejected() {
+	let writer = AvroSchema::from_file("tests/schemas/journal.avsc").unwrap().compile().unwrap();
+	let reader = compile(r#"{"type": "record", "name": "dashboard_stats", "fields": [{"name": "SEVERITY", "type": "string"}]}"#);
+	match Projection::new(&writer, &reader) {
+		Err(AvroErr::SchemaParse { ref path, ref reason }) => {
+			assert_eq!(path.to_string(), "$.SEVERITY");
+			assert_eq!(reason, "Field SEVERITY is not in the writer's schema");
+		}
+		other => panic!("Expected a schema error, found {:?}", other)
+	}
+	let reader = compile(r#"{"type": "record", "name": "dashboard_stats", "fields": [{"name": "MESSAGE", "type": "bytes"}]}"#);
+	match Projection::new(&writer, &reader) {
+		Err(AvroErr::SchemaParse { ref path, ref reason }) => {
+			assert_eq!(path.to_string(), "$.MESSAGE");
+			assert_eq!(reason, "Expected string as in the writer's schema, found bytes");
+		}
+		other => panic!("Expected a schema error, found {:?}", other)
+	}
+}
+
+#[test]
+fn readers_decode_the_projected_fields() {
+	let datafile = journal_datafile(Codec::Snappy);
+	let mut reader = AvroReader::new(Cursor::new(datafile.clone())).unwrap();
+	reader.project(&["MESSAGE", "__REALTIME_TIMESTAMP", "_HOSTNAME"]).unwrap();
+	let records: Vec<Type> = reader.map(|r| r.unwrap()).collect();
+	assert_eq!(records, (0..100).map(journal_subset).collect::<Vec<_>>());
+
+	let mut reader = AvroReader::new(Cursor::new(datafile)).unwrap();
+	reader.project(&["MESSAGE"]).unwrap();
+	let first = reader.parallel(2).next().unwrap().unwrap();
+	let message = Field::new("MESSAGE", Type::Str("MESSAGE 0".to_string()));
+	assert_eq!(first, Type::Record(Record::new("dashboard_stats", None, vec![message])));
+}
+
+#[test]
+fn nested_fields_are_projected() {
+	let mut data_writer = test_writer("tests/schemas/nested_schema.avsc", Codec::Null);
+	for i in 0..3 {
+		let mut map = HashMap::new();
+		map.insert("half".to_string(), Type::Float(0.5));
+		data_writer.write(Record::new("dashboard_stats", None, vec![
+			Field::new("name", Type::Str(format!("stats {}", i))),
+			Field::new("foo", Type::Map(map)),
+			Field::new("inner_rec", Type::Record(Record::new("id_rec", None, vec![Field::new("id", Type::Long(i))])))
+		])).unwrap();
+	}
+	let datafile = data_writer.take_datafile().unwrap();
+	let mut reader = AvroReader::new(Cursor::new(datafile)).unwrap();
+	reader.project(&["inner_rec.id", "name"]).unwrap();
+	let records: Vec<Type> = reader.map(|r| r.unwrap()).collect();
+	let expected: Vec<Type> = (0..3).map(|i| Type::Record(Record::new("dashboard_stats", None, vec![
+		Field::new("name", Type::Str(format!("stats {}", i))),
+		Field::new("inner_rec", Type::Record(Record::new("id_rec", None, vec![Field::new("id", Type::Long(i))])))
+	]))).collect();
+	assert_eq!(records, expected);
+}
+
+#[test]
+fn paths_reach_through_collections_and_unions() {
+	let schema = compile(r#"{"type": "record", "name": "outer", "fields": [
+		{"name": "inner", "type": {"type": "array", "items": ["null", {"type": "record", "name": "inner", "fields": [
+			{"name": "id", "type": "long"},
+			{"name": "note", "type": "string"}
+		]}]}},
+		{"name": "id", "type": "long"}
+	]}"#);
+	let inner = |id, note: &str| Type::Union(1, Box::new(Type::Record(Record::new("inner", None, vec![
+		Field::new("id", Type::Long(id)),
+		Field::new("note", Type::Str(note.to_string()))
+	]))));
+	let value = Type::Record(Record::new("outer", None, vec![
+		Field::new("inner", Type::Array(vec![inner(1, "a"), Type::Union(0, Box::new(Type::Null)), inner(2, "b")])),
+		Field::new("id", Type::Long(3))
+	]));
+	let mut encoded = vec![];
+	value.encode_with(&schema, &mut encoded).unwrap();
+	let projection = Projection::from_paths(&schema, &["inner.note"]).unwrap();
+	let projected = projection.decode(&DecoderLimits::default(), &mut Cursor::new(&encoded)).unwrap();
+	let inner = |note: &str| Type::Union(1, Box::new(Type::Record(Record::new("inner", None, vec![Field::new("note", Type::Str(note.to_string()))]))));
+	assert_eq!(projected, Type::Record(Record::new("outer", None, vec![
+		Field::new("inner", Type::Array(vec![inner("a"), Type::Union(0, Box::new(Type::Null)), inner("b")]))
+	])));
+}
+
+#[test]
+fn union_branches_without_records_are_decoded_whole() {
+	let schema = compile(r#"{"type": "record", "name": "outer", "fields": [
+		{"name": "u", "type": ["null", {"type": "array", "items": "string"}, {"type": "record", "name": "inner", "fields": [
+			{"name": "id", "type": "long"},
+			{"name": "note", "type": "string"}
+		]}]}
+	]}"#);
+	let projection = Projection::from_paths(&schema, &["u.id"]).unwrap();
+	let strings = Type::Union(1, Box::new(Type::Array(vec![Type::Str("a".to_string()), Type::Str("b".to_string())])));
+	let inner = Type::Union(2, Box::new(Type::Record(Record::new("inner", None, vec![
+		Field::new("id", Type::Long(7)),
+		Field::new("note", Type::Str("c".to_string()))
+	]))));
+	let projected_inner = Type::Union(2, Box::new(Type::Record(Record::new("inner", None, vec![Field::new("id", Type::Long(7))]))));
+	for (value, expected) in vec![(strings.clone(), strings), (inner, projected_inner)] {
+		let mut encoded = vec![];
+		Type::Record(Record::new("outer", None, vec![Field::new("u", value)])).encode_with(&schema, &mut encoded).unwrap();
+		let projected = projection.decode(&DecoderLimits::default(), &mut Cursor::new(&encoded)).unwrap();
+		assert_eq!(projected, Type::Record(Record::new("outer", None, vec![Field::new("u", expected)])));
+	}
+}
+
+#[test]
+fn invalid_paths_are_rejected() {
+	let schema = AvroSchema::from_file("tests/schemas/nested_schema.avsc").unwrap().compile().unwrap();
+	match Projection::from_paths(&schema, &["inner_rec.key"]) {
+		Err(AvroErr::SchemaParse { ref path, ref reason }) => {
+			assert_eq!(path.to_string(), "$.inner_rec.key");
+			assert_eq!(reason, "Field key is not in the writer's schema");
+		}
+		other => panic!("Expected a schema error, found {:?}", other)
+	}
+	match Projection::from_paths(&schema, &["name.first"]) {
+		Err(AvroErr::SchemaParse { ref path, ref reason }) => {
+			assert_eq!(path.to_string(), "$.name");
+			assert_eq!(reason, "Expected a record to select fields first of, found string");
+		}
+		other => panic!("Expected a schema error, found {:?}", other)
+	}
+	assert!(Projection::from_paths(&schema, &["inner_rec."]).is_err());
+}
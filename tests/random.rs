@@ -0,0 +1,127 @@
+#![warn(unused_variables, unused_must_use)]
+
+extern crate ravro;
+
+use ravro::random::RandomGenerator;
+use ravro::schema::CompiledSchema;
+use ravro::{AvroReader, AvroSchema, AvroWriter, Codec, Type};
+
+const SCHEMA: &str = r#"{"type": "record", "name": "event", "fields": [
+	{"name": "id", "type": {"type": "string", "logicalType": "uuid"}},
+	{"name": "day", "type": {"type": "int", "logicalType": "date"}},
+	{"name": "at", "type": {"type": "long", "logicalType": "timestamp-micros"}},
+	{"name": "price", "type": {"type": "bytes", "logicalType": "decimal", "precision": 4, "scale": 2}},
+	{"name": "total", "type": {"type": "fixed", "name": "total", "size": 16, "logicalType": "decimal", "precision": 30}},
+	{"name": "kind", "type": {"type": "enum", "name": "kind", "symbols": ["START", "STOP"]}},
+	{"name": "digest", "type": {"type": "fixed", "name": "md5", "size": 16}},
+	{"name": "payload", "type": ["null", "bytes", "double", {"type": "array", "items": "float"}]},
+	{"name": "tags", "type": {"type": "map", "values": {"type": "array", "items": ["null", "string"]}}},
+	{"name": "ok", "type": "boolean"}
+]}"#;
+
+fn compile(schema: &str) -> CompiledSchema {
+	AvroSchema::from_str(schema).unwrap().compile().unwrap()
+}
+
+fn field<'a>(value: &'a Type, name: &str) -> &'a Type {
+	match *value {
+		Type::Record(ref rec) => &rec.fields.iter().find(|f| f.name == name).unwrap().ty,
+		_ => panic!("Expected a record, found {:?}", value)
+	}
+}
+
+/// Gives the nesting depth of the `next` records of a linked list
+fn list_len(value: &Type) -> usize {
+	match *field(value, "next") {
+		Type::Union(0, ref next) => 1 + list_len(next),
+		_ => 1
+	}
+}
+
+#[test]
+fn same_seeds_generate_the_same_values() {
+	let values = |seed| RandomGenerator::new(compile(SCHEMA), seed).unwrap().take(20).collect::<Vec<_>>();
+	assert_eq!(values(7), values(7));
+	assert!(values(7) != values(8));
+}
+
+#[test]
+fn generated_values_round_trip_through_data_files() {
+	let values: Vec<Type> = RandomGenerator::new(compile(SCHEMA), 1).unwrap().take(500).collect();
+	let mut builder = AvroWriter::with_schema(AvroSchema::from_str(SCHEMA).unwrap());
+	builder.set_codec(Codec::Snappy);
+	let mut writer = builder.build().unwrap();
+	for value in &values {
+		writer.write(value.clone()).unwrap();
+	}
+	let datafile = writer.take_datafile().unwrap();
+	let reader = AvroReader::new(&datafile[..]).unwrap();
+	let read: Vec<Type> = reader.map(|r| r.unwrap()).collect();
+	assert_eq!(read, values);
+
+	for value in &values {
+		match *field(value, "id") {
+			Type::Str(ref id) => {
+				assert_eq!(id.len(), 36);
+				assert_eq!(&id[14..15], "4");
+			}
+			ref other => panic!("Expected a uuid, found {:?}", other)
+		}
+		match *field(value, "day") {
+			Type::Int(day) => assert!(day >= 10_957 && day < 21_915, "{}", day),
+			ref other => panic!("Expected a date, found {:?}", other)
+		}
+		match *field(value, "price") {
+			Type::Bytes(ref unscaled) => {
+				assert!(!unscaled.is_empty() && unscaled.len() <= 2);
+				let mut price = if unscaled[0] >= 0x80 { -1i64 } else { 0 };
+				for byte in unscaled {
+					price = price << 8 | i64::from(*byte);
+				}
+				assert!(price.abs() < 10_000, "{}", price);
+			}
+			ref other => panic!("Expected a decimal, found {:?}", other)
+		}
+	}
+}
+
+#[test]
+fn sizes_are_bounded() {
+	let schema = compile(r#"{"type": "map", "values": {"type": "array", "items": "string"}}"#);
+	let mut generator = RandomGenerator::new(schema, 3).unwrap();
+	generator.set_max_string_len(4);
+	generator.set_max_items(3);
+	for value in generator.take(100) {
+		let map = match value {
+			Type::Map(map) => map,
+			other => panic!("Expected a map, found {:?}", other)
+		};
+		assert!(map.len() <= 3);
+		for (key, items) in map {
+			assert!(!key.is_empty() && key.len() <= 4);
+			match items {
+				Type::Array(ref items) => {
+					assert!(items.len() <= 3);
+					assert!(items.iter().all(|item| match *item { Type::Str(ref s) => s.len() <= 4, _ => false }));
+				}
+				other => panic!("Expected an array, found {:?}", other)
+			}
+		}
+	}
+}
+
+#[test]
+fn recursive_values_end_within_the_max_depth() {
+	let list = compile(r#"{"type": "record", "name": "node", "fields": [
+		{"name": "value", "type": "long"},
+		{"name": "next", "type": ["node", "null"]}
+	]}"#);
+	let mut generator = RandomGenerator::new(list, 5).unwrap();
+	generator.set_max_depth(3);
+	let lens: Vec<usize> = generator.take(100).map(|value| list_len(&value)).collect();
+	assert!(lens.iter().all(|&len| len <= 3), "{:?}", lens);
+	assert!(lens.contains(&3));
+
+	let endless = compile(r#"{"type": "record", "name": "node", "fields": [{"name": "next", "type": "node"}]}"#);
+	assert!(RandomGenerator::new(endless, 5).is_err());
+}
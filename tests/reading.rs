@@ -1,110 +1,162 @@
-// #![warn(unused_variables, unused_must_use)]
-
-// extern crate ravro;
-
-// use ravro::schema::AvroSchema;
-// use std::fs::OpenOptions;
-// use std::io::Cursor;
-// use ravro::datafile::{DataWriter, Codecs};
-
-// use std::io::Write;
-// use ravro::reader::{AvroReader, BlockReader};
-// use ravro::types::Schema;
-// use std::fs::File;
-
-// fn create_writer(src_schema: &str, encoded_file: &str, codec: Codecs) -> (DataWriter, File) {
-//     let schema_file = &format!("tests/schemas/{}", src_schema);
-//     let string_schema = AvroSchema::from_file(schema_file).unwrap();
-//     let datafile_name = &format!("tests/encoded/{}", encoded_file);
-//     let mut writer_file = OpenOptions::new().write(true)
-//                                    .create(true)
-//                                    .open(datafile_name).unwrap();
-//     let mut data_writer = DataWriter::new(string_schema, codec).unwrap();
-//     (data_writer, writer_file)
-// }
-
-// #[test]
-// fn reading_string_uncompressed() {
-//     // Write some data
-//     let (mut data_writer,
-//          mut writer_file) = create_writer("string_schema.avsc", "string_for_read.avro", Codecs::Null);
-//     let _ = data_writer.write("Reading".to_string());
-//     let _ = data_writer.write("avro".to_string());
-//     let _ = data_writer.write("string".to_string());
-//     let _ = data_writer.commit_block();
-//     let _ = writer_file.write_all(&data_writer.swap_buffer().into_inner());
-//     // Read that data
-//     let reader = AvroReader::from_path("tests/encoded/string_for_read.avro").unwrap();
-//     let mut a: BlockReader<Schema> = reader.iter_block();
-//     assert_eq!(a.next().unwrap().string_ref(), "Reading".to_string());
-//     assert_eq!(a.next().unwrap().string_ref(), "avro".to_string());
-//     assert_eq!(a.next().unwrap().string_ref(), "string".to_string());
-//     assert_eq!(a.next(), None);
-// }
-
-// #[test]
-// fn reading_record_uncompressed() {
-//     let reader = AvroReader::from_path("tests/encoded/record_encoded.avro").unwrap();
-//     let mut a: BlockReader<Schema> = reader.iter_block();
-//     assert!(a.next().is_some());
-// }
-
-// #[test]
-// fn reading_map_uncompressed() {
-//     let reader = AvroReader::from_path("tests/encoded/map_encoded.avro").unwrap();
-//     let mut a: BlockReader<Schema> = reader.iter_block();
-//     assert!(a.next().is_some());
-// }
-
-// #[test]
-// fn reading_bool_uncompressed() {
-//     let (mut data_writer,
-//          mut writer_file) = create_writer("bool_schema.avsc", "bool_for_read.avro", Codecs::Null);
-//     let _ = data_writer.write(true);
-//     let _ = data_writer.write(false);
-//     let _ = data_writer.write(false);
-//     let _ = data_writer.commit_block();
-//     data_writer.flush_to_disk("tests/encoded/bool_for_read.avro");
-//     let reader = AvroReader::from_path("tests/encoded/bool_for_read.avro").unwrap();
-//     let mut a: BlockReader<Schema> = reader.iter_block();
-//     assert_eq!(a.next().unwrap().bool_ref(), true);
-//     assert_eq!(a.next().unwrap().bool_ref(), false);
-//     assert_eq!(a.next().unwrap().bool_ref(), false);
-//     assert_eq!(a.next(), None);
-// }
-
-// #[test]
-// fn reading_bool_compressed() {
-//     let (mut data_writer,
-//              mut writer_file) = create_writer("bool_schema.avsc", "bool_for_read_comp.avro", Codecs::Snappy);
-//     let _ = data_writer.write(true);
-//     let _ = data_writer.write(false);
-//     let _ = data_writer.write(false);
-//     let _ = data_writer.commit_block();
-//     let _ = writer_file.write_all(&data_writer.swap_buffer().into_inner());
-//     // Read that data
-//     let reader = AvroReader::from_path("tests/encoded/bool_for_read_comp.avro").unwrap();
-//     let mut a: BlockReader<Schema> = reader.iter_block();
-//     assert_eq!(a.next().unwrap().bool_ref(), true);
-//     assert_eq!(a.next().unwrap().bool_ref(), false);
-//     assert_eq!(a.next().unwrap().bool_ref(), false);
-//     assert_eq!(a.next(), None);
-// }
-
-// #[test]
-// fn reading_string_compressed() {
-//     let (mut data_writer,
-//              mut writer_file) = create_writer("string_schema.avsc", "string_for_read_comp.avro", Codecs::Snappy);
-//     let _ = data_writer.write("Reading".to_string());
-//     let _ = data_writer.write("avro".to_string());
-//     let _ = data_writer.write("string".to_string());
-//     let _ = data_writer.commit_block();
-//     data_writer.flush_to_disk("string_for_read_comp.avro");
-//     // Read data
-//     let reader = AvroReader::from_path("tests/encoded/string_for_read_comp.avro").unwrap();
-//     let mut a: BlockReader<Schema> = reader.iter_block();
-//     assert_eq!(a.next().unwrap().string_ref(), "Reading".to_string());
-//     assert_eq!(a.next().unwrap().string_ref(), "avro".to_string());
-//     assert_eq!(a.next().unwrap().string_ref(), "string".to_string());
-//     assert_eq!(a.next(), None);
-// }
+#![warn(unused_variables, unused_must_use)]
+
+extern crate ravro;
+
+mod common;
+
+use common::test_writer;
+use ravro::{AvroReader, Codec, Type};
+use ravro::complex::{Enum, Field, Record};
+use std::collections::HashMap;
+use std::io::Cursor;
+
+fn codecs() -> Vec<Codec> {
+	vec![Codec::Null, Codec::Snappy, Codec::Deflate]
+}
+
+fn read_all(datafile: Vec<u8>) -> Vec<Type> {
+	let reader = AvroReader::new(Cursor::new(datafile)).unwrap();
+	reader.map(|r| r.unwrap()).collect()
+}
+
+#[test]
+fn reading_primitives() {
+	for codec in codecs() {
+		let mut data_writer = test_writer("tests/schemas/string_schema.avsc", codec);
+		data_writer.write("Reading".to_string()).unwrap();
+		data_writer.write("avro".to_string()).unwrap();
+		data_writer.write("string".to_string()).unwrap();
+		let strings: Vec<String> = read_all(data_writer.take_datafile().unwrap()).iter().map(|t| t.string_ref()).collect();
+		assert_eq!(strings, vec!["Reading", "avro", "string"]);
+
+		let mut data_writer = test_writer("tests/schemas/bool_schema.avsc", codec);
+		data_writer.write(true).unwrap();
+		data_writer.write(false).unwrap();
+		assert_eq!(read_all(data_writer.take_datafile().unwrap()), vec![Type::Bool(true), Type::Bool(false)]);
+
+		let mut data_writer = test_writer("tests/schemas/int_schema.avsc", codec);
+		data_writer.write(3454).unwrap();
+		data_writer.write(-567561).unwrap();
+		assert_eq!(read_all(data_writer.take_datafile().unwrap()), vec![Type::Int(3454), Type::Int(-567561)]);
+
+		let mut data_writer = test_writer("tests/schemas/double_schema.avsc", codec);
+		data_writer.write(3.14).unwrap();
+		assert_eq!(read_all(data_writer.take_datafile().unwrap()), vec![Type::Double(3.14)]);
+
+		let mut data_writer = test_writer("tests/schemas/bytes_schema.avsc", codec);
+		data_writer.write(b"ravro".to_vec()).unwrap();
+		assert_eq!(read_all(data_writer.take_datafile().unwrap()), vec![Type::Bytes(b"ravro".to_vec())]);
+
+		let mut data_writer = test_writer("tests/schemas/null_schema.avsc", codec);
+		data_writer.write(()).unwrap();
+		assert_eq!(read_all(data_writer.take_datafile().unwrap()), vec![Type::Null]);
+	}
+}
+
+#[test]
+fn reading_nested_record() {
+	for codec in codecs() {
+		let mut data_writer = test_writer("tests/schemas/nested_schema.avsc", codec);
+		let mut map = HashMap::new();
+		map.insert("SomeData".to_owned(), Type::Float(234.455));
+		let inner_rec = Record::new("id_rec", None, vec![Field::new("id", Type::Long(3i64))]);
+		let outer_rec = Record::new("dashboard_stats", None, vec![
+			Field::new("name", Type::Str("nested_record_example".to_string())),
+			Field::new("foo", Type::Map(map)),
+			Field::new("inner_rec", Type::Record(inner_rec))
+		]);
+		data_writer.write(outer_rec.clone()).unwrap();
+		assert_eq!(read_all(data_writer.take_datafile().unwrap()), vec![Type::Record(outer_rec)]);
+	}
+}
+
+#[test]
+fn reading_collections_and_enums() {
+	for codec in codecs() {
+		let mut data_writer = test_writer("tests/schemas/mapmap_schema.avsc", codec);
+		let mut inner_map = HashMap::new();
+		inner_map.insert("one".to_string(), Type::Double(23.));
+		let mut map = HashMap::new();
+		map.insert("hello".to_string(), Type::Map(inner_map));
+		data_writer.write(map.clone()).unwrap();
+		assert_eq!(read_all(data_writer.take_datafile().unwrap()), vec![Type::Map(map)]);
+
+		let mut data_writer = test_writer("tests/schemas/array_schema.avsc", codec);
+		let arr: Vec<Type> = vec!["a".to_string().into(), "b".to_string().into()];
+		data_writer.write(arr.clone()).unwrap();
+		assert_eq!(read_all(data_writer.take_datafile().unwrap()), vec![Type::Array(arr)]);
+
+		let mut data_writer = test_writer("tests/schemas/enum_schema.avsc", codec);
+		let mut enum_scm = Enum::new("Foo", &["CLUBS", "SPADE", "DIAMOND"]);
+		enum_scm.set_value("DIAMOND");
+		data_writer.write(Type::Enum(enum_scm.clone())).unwrap();
+		assert_eq!(read_all(data_writer.take_datafile().unwrap()), vec![Type::Enum(enum_scm)]);
+	}
+}
+
+#[test]
+fn reading_unions_and_fixed() {
+	for codec in codecs() {
+		let mut data_writer = test_writer("tests/schemas/union_schema.avsc", codec);
+		let present = Record::new("reading", None, vec![
+			Field::new("id", Type::Fixed(vec![1, 2, 3, 4])),
+			Field::new("value", Type::Union(1, Box::new(Type::Double(0.5)))),
+			Field::new("next_id", Type::Union(1, Box::new(Type::Fixed(vec![5, 6, 7, 8]))))
+		]);
+		let absent = Record::new("reading", None, vec![
+			Field::new("id", Type::Fixed(vec![9, 9, 9, 9])),
+			Field::new("value", Type::Union(0, Box::new(Type::Null))),
+			Field::new("next_id", Type::Union(0, Box::new(Type::Null)))
+		]);
+		data_writer.write(present.clone()).unwrap();
+		data_writer.write(absent.clone()).unwrap();
+		assert_eq!(read_all(data_writer.take_datafile().unwrap()), vec![Type::Record(present), Type::Record(absent)]);
+	}
+}
+
+#[test]
+fn reading_multiple_blocks() {
+	for codec in codecs() {
+		let mut data_writer = test_writer("tests/schemas/long_schema.avsc", codec);
+		for i in 0..10000i64 {
+			data_writer.write(i).unwrap();
+			if i % 1000 == 999 {
+				data_writer.commit_block().unwrap();
+			}
+		}
+		let longs: Vec<i64> = read_all(data_writer.take_datafile().unwrap()).iter().map(|t| t.long_ref()).collect();
+		assert_eq!(longs, (0..10000).collect::<Vec<i64>>());
+	}
+}
+
+#[test]
+fn reading_rejects_bad_sync_marker() {
+	let mut data_writer = test_writer("tests/schemas/long_schema.avsc", Codec::Null);
+	data_writer.write(1i64).unwrap();
+	let mut datafile = data_writer.take_datafile().unwrap();
+	let last = datafile.len() - 1;
+	datafile[last] ^= 0xFF;
+	let mut reader = AvroReader::new(Cursor::new(datafile)).unwrap();
+	assert!(reader.next().unwrap().is_err());
+	assert!(reader.next().is_none());
+}
+
+#[test]
+fn reading_blocks_larger_than_the_compressor_buffer() {
+	// Strings of pseudo random bytes, which deflate cannot shrink much
+	let mut state = 0x2545_f491_4f6c_dd1du64;
+	let strings: Vec<String> = (0..64).map(|_| (0..16 * 1024).map(|_| {
+		state ^= state << 13;
+		state ^= state >> 7;
+		state ^= state << 17;
+		(b'!' + (state % 94) as u8) as char
+	}).collect()).collect();
+	for codec in codecs() {
+		let mut data_writer = test_writer("tests/schemas/string_schema.avsc", codec);
+		for string in &strings {
+			data_writer.write(string.clone()).unwrap();
+		}
+		let records = read_all(data_writer.take_datafile().unwrap());
+		assert_eq!(records, strings.iter().map(|s| Type::Str(s.clone())).collect::<Vec<_>>());
+	}
+}
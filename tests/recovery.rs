@@ -0,0 +1,93 @@
+#![warn(unused_variables, unused_must_use)]
+
+extern crate ravro;
+
+mod common;
+
+use common::test_writer;
+use ravro::{AvroReader, Codec, Type};
+use std::io::Cursor;
+
+// Writes 500 booleans in blocks of 100 records, returning the data file and block offsets
+fn blocked_datafile(codec: Codec) -> (Vec<u8>, Vec<u64>) {
+	let mut data_writer = test_writer("tests/schemas/bool_schema.avsc", codec);
+	for i in 0..500 {
+		data_writer.write(i % 3 == 0).unwrap();
+		if i % 100 == 99 {
+			data_writer.commit_block().unwrap();
+		}
+	}
+	let datafile = data_writer.take_datafile().unwrap();
+	let offsets = AvroReader::new(Cursor::new(datafile.clone())).unwrap()
+		.iter_block().map(|b| b.unwrap().offset).collect();
+	(datafile, offsets)
+}
+
+#[test]
+fn recover_from_corrupt_compressed_block() {
+	let (mut datafile, offsets) = blocked_datafile(Codec::Snappy);
+	// Damage the compressed data of the third block
+	let pos = offsets[2] as usize + 10;
+	datafile[pos] ^= 0xFF;
+	let mut reader = AvroReader::new(Cursor::new(datafile)).unwrap().recover();
+	let records: Vec<Type> = reader.by_ref().collect();
+	assert_eq!(records.len(), 400);
+	let skipped = reader.skipped();
+	assert_eq!(skipped.len(), 1);
+	assert_eq!(skipped[0].offset, offsets[2]);
+	assert_eq!(skipped[0].length, offsets[3] - offsets[2]);
+	assert_eq!(skipped[0].estimated_records, 100);
+}
+
+#[test]
+fn recover_from_undecodable_record() {
+	let (mut datafile, offsets) = blocked_datafile(Codec::Null);
+	// The 51st boolean of the second block gets an invalid value. Each block here starts
+	// with its count and size, both of which are 100 and take up two bytes as varints.
+	let pos = offsets[1] as usize + 4 + 50;
+	datafile[pos] = 0x05;
+	let mut reader = AvroReader::new(Cursor::new(datafile)).unwrap().recover();
+	let records: Vec<Type> = reader.by_ref().collect();
+	assert_eq!(records.len(), 450);
+	let skipped = reader.skipped();
+	assert_eq!(skipped.len(), 1);
+	assert_eq!(skipped[0].offset, offsets[1]);
+	assert_eq!(skipped[0].estimated_records, 50);
+}
+
+#[test]
+fn recover_from_corrupt_sync_marker() {
+	let (mut datafile, offsets) = blocked_datafile(Codec::Deflate);
+	// The sync marker ending the second block is the 16 bytes before the third block
+	let pos = offsets[2] as usize - 4;
+	datafile[pos] ^= 0xFF;
+	let mut reader = AvroReader::new(Cursor::new(datafile)).unwrap().recover();
+	let records: Vec<Type> = reader.by_ref().collect();
+	// The damaged marker makes the reader resynchronize at the marker ending the third block
+	assert_eq!(records.len(), 300);
+	let skipped = reader.skipped();
+	assert_eq!(skipped.len(), 1);
+	assert_eq!(skipped[0].offset, offsets[1]);
+	assert_eq!(skipped[0].length, offsets[3] - offsets[1]);
+}
+
+#[test]
+fn recover_from_truncated_datafile() {
+	let (mut datafile, offsets) = blocked_datafile(Codec::Null);
+	let truncated_len = offsets[4] as usize + 30;
+	datafile.truncate(truncated_len);
+	let mut reader = AvroReader::new(Cursor::new(datafile)).unwrap().recover();
+	assert_eq!(reader.by_ref().count(), 400);
+	let skipped = reader.skipped();
+	assert_eq!(skipped.len(), 1);
+	assert_eq!(skipped[0].offset, offsets[4]);
+	assert_eq!(skipped[0].length, 30);
+}
+
+#[test]
+fn intact_datafile_skips_nothing() {
+	let (datafile, _) = blocked_datafile(Codec::Snappy);
+	let mut reader = AvroReader::new(Cursor::new(datafile)).unwrap().recover();
+	assert_eq!(reader.by_ref().count(), 500);
+	assert!(reader.skipped().is_empty());
+}
@@ -2,6 +2,7 @@
 extern crate ravro;
 
 use ravro::AvroSchema;
+use ravro::schema::{LogicalType, SchemaNode};
 
 #[test]
 fn test_parse_schema() {
@@ -14,3 +15,50 @@ fn test_parse_schema() {
 	assert!(fields[3] == ("lsmsensor".to_string(), "long".to_string()));
 	assert!(fields[4] == ("map".to_string(), "string".to_string()));
 }
+
+#[test]
+fn test_compile_recursive_schema() {
+	let schema = AvroSchema::from_str(r#"{"type": "record", "name": "node", "namespace": "example.list", "fields": [
+		{"name": "value", "type": "string"},
+		{"name": "next", "type": ["null", "example.list.node"]}
+	]}"#).unwrap();
+	let compiled = schema.compile().unwrap();
+	let root = compiled.root();
+	let fields = match *compiled.node(root) {
+		SchemaNode::Record { ref fields, .. } => fields.clone(),
+		ref other => panic!("Expected a record node, found {:?}", other)
+	};
+	assert_eq!(fields[0].name, "value");
+	assert_eq!(*compiled.node(fields[0].node), SchemaNode::String);
+	match *compiled.node(fields[1].node) {
+		SchemaNode::Union(ref branches) => {
+			assert_eq!(*compiled.node(branches[0]), SchemaNode::Null);
+			assert_eq!(branches[1], root);
+		}
+		ref other => panic!("Expected a union node, found {:?}", other)
+	}
+}
+
+#[test]
+fn test_compile_unknown_reference() {
+	let schema = AvroSchema::from_str(r#"{"type": "array", "items": "missing"}"#).unwrap();
+	assert!(schema.compile().is_err());
+}
+
+#[test]
+fn test_compile_logical_types() {
+	let logical_type = |schema: &str| AvroSchema::from_str(schema).unwrap().compile().map(|compiled| compiled.logical_type(compiled.root())).unwrap();
+	assert_eq!(logical_type(r#"{"type": "int", "logicalType": "date"}"#), Some(LogicalType::Date));
+	assert_eq!(logical_type(r#"{"type": "string", "logicalType": "uuid"}"#), Some(LogicalType::Uuid));
+	assert_eq!(logical_type(r#"{"type": "bytes", "logicalType": "decimal", "precision": 9, "scale": 2}"#),
+		Some(LogicalType::Decimal { precision: 9, scale: 2 }));
+	assert_eq!(logical_type(r#"{"type": "fixed", "name": "d", "size": 4, "logicalType": "decimal", "precision": 9}"#),
+		Some(LogicalType::Decimal { precision: 9, scale: 0 }));
+	assert_eq!(logical_type(r#"{"type": "fixed", "name": "d", "size": 12, "logicalType": "duration"}"#), Some(LogicalType::Duration));
+	// Unknown logical types and ones invalid for their type are ignored
+	assert_eq!(logical_type(r#""int""#), None);
+	assert_eq!(logical_type(r#"{"type": "long", "logicalType": "date"}"#), None);
+	assert_eq!(logical_type(r#"{"type": "int", "logicalType": "day-of-week"}"#), None);
+	assert_eq!(logical_type(r#"{"type": "bytes", "logicalType": "decimal", "precision": 2, "scale": 3}"#), None);
+	assert_eq!(logical_type(r#"{"type": "fixed", "name": "d", "size": 4, "logicalType": "decimal", "precision": 10}"#), None);
+}
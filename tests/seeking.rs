@@ -0,0 +1,74 @@
+#![warn(unused_variables, unused_must_use)]
+
+extern crate ravro;
+
+mod common;
+
+use common::test_writer;
+use ravro::{AvroReader, Codec};
+use std::io::Cursor;
+
+// Writes 0..1000 as longs, in blocks of 100 records
+fn blocked_datafile(codec: Codec) -> Vec<u8> {
+	let mut data_writer = test_writer("tests/schemas/long_schema.avsc", codec);
+	for i in 0..1000i64 {
+		data_writer.write(i).unwrap();
+		if i % 100 == 99 {
+			data_writer.commit_block().unwrap();
+		}
+	}
+	data_writer.take_datafile().unwrap()
+}
+
+#[test]
+fn seek_to_sync_resumes_at_next_block() {
+	for codec in vec![Codec::Null, Codec::Snappy, Codec::Deflate].into_iter() {
+		let datafile = blocked_datafile(codec);
+		let mut reader = AvroReader::new(Cursor::new(datafile.clone())).unwrap();
+		let data_start = reader.tell();
+		// Read a record of the third block to learn where it starts
+		let third_block = reader.by_ref().nth(200).unwrap().unwrap();
+		assert_eq!(third_block.long_ref(), 200);
+		let third_block_start = reader.previous_sync();
+		assert!(reader.tell() > third_block_start);
+
+		// Seeking to a block boundary resumes right at that block
+		reader.seek_to_sync(third_block_start).unwrap();
+		assert_eq!(reader.next().unwrap().unwrap().long_ref(), 200);
+
+		// Seeking into the middle of a block resumes at the following one
+		reader.seek_to_sync(third_block_start + 1).unwrap();
+		assert_eq!(reader.next().unwrap().unwrap().long_ref(), 300);
+
+		// Seeking before the data blocks resumes at the first block
+		reader.seek_to_sync(0).unwrap();
+		assert_eq!(reader.previous_sync(), data_start);
+		assert_eq!(reader.next().unwrap().unwrap().long_ref(), 0);
+
+		// Seeking past the last block ends the iteration
+		reader.seek_to_sync(datafile.len() as u64 - 3).unwrap();
+		assert!(reader.next().is_none());
+	}
+}
+
+#[test]
+fn split_reading_by_byte_range() {
+	for codec in vec![Codec::Null, Codec::Snappy, Codec::Deflate].into_iter() {
+		let datafile = blocked_datafile(codec);
+		let len = datafile.len() as u64;
+		let splits = [0, len / 3, len / 2, 2 * len / 3, len];
+		let mut all = vec![];
+		for range in splits.windows(2) {
+			let mut reader = AvroReader::new(Cursor::new(datafile.clone())).unwrap();
+			reader.seek_to_sync(range[0]).unwrap();
+			while let Some(record) = reader.next() {
+				let record = record.unwrap();
+				if reader.previous_sync() >= range[1] {
+					break;
+				}
+				all.push(record.long_ref());
+			}
+		}
+		assert_eq!(all, (0..1000).collect::<Vec<i64>>());
+	}
+}
@@ -0,0 +1,183 @@
+#![warn(unused_variables, unused_must_use)]
+
+extern crate ravro;
+
+mod common;
+
+use common::test_writer;
+use ravro::{AvroReader, Codec, SliceReader, TypeRef};
+use ravro::complex::{Field, Record};
+use ravro::errors::AvroErr;
+use ravro::Type;
+use std::collections::HashMap;
+use std::io::Cursor;
+
+fn nested_datafile(codec: Codec, records: usize) -> Vec<u8> {
+	let mut data_writer = test_writer("tests/schemas/nested_schema.avsc", codec);
+	for i in 0..records {
+		let mut map = HashMap::new();
+		map.insert(format!("key {}", i), Type::Float(i as f32));
+		let inner_rec = Record::new("id_rec", None, vec![Field::new("id", Type::Long(i as i64))]);
+		data_writer.write(Record::new("dashboard_stats", None, vec![
+			Field::new("name", Type::Str(format!("record {}", i))),
+			Field::new("foo", Type::Map(map)),
+			Field::new("inner_rec", Type::Record(inner_rec))
+		])).unwrap();
+		if i % 3 == 2 {
+			data_writer.commit_block().unwrap();
+		}
+	}
+	data_writer.take_datafile().unwrap()
+}
+
+fn borrows_from(s: &str, buf: &[u8]) -> bool {
+	let start = buf.as_ptr() as usize;
+	let ptr = s.as_ptr() as usize;
+	ptr >= start && ptr + s.len() <= start + buf.len()
+}
+
+#[test]
+fn reading_borrowed_records() {
+	let datafile = nested_datafile(Codec::Null, 10);
+	let reader = SliceReader::new(&datafile).unwrap();
+	let records: Vec<TypeRef> = reader.records().unwrap().map(|r| r.unwrap()).collect();
+	assert_eq!(records.len(), 10);
+	for (i, record) in records.iter().enumerate() {
+		let name = format!("record {}", i);
+		let key = format!("key {}", i);
+		let expected = TypeRef::Record {
+			name: "dashboard_stats",
+			fields: vec![
+				("name", TypeRef::Str(&name)),
+				("foo", TypeRef::Map(vec![(&key, TypeRef::Float(i as f32))])),
+				("inner_rec", TypeRef::Record { name: "id_rec", fields: vec![("id", TypeRef::Long(i as i64))] })
+			]
+		};
+		assert_eq!(*record, expected);
+		match *record {
+			TypeRef::Record { ref fields, .. } => match fields[0].1 {
+				TypeRef::Str(s) => assert!(borrows_from(s, &datafile)),
+				ref other => panic!("Expected a string, found {:?}", other)
+			},
+			ref other => panic!("Expected a record, found {:?}", other)
+		}
+	}
+}
+
+#[test]
+fn reading_unions_and_fixed() {
+	let mut data_writer = test_writer("tests/schemas/union_schema.avsc", Codec::Null);
+	data_writer.write(Record::new("reading", None, vec![
+		Field::new("id", Type::Fixed(vec![1, 2, 3, 4])),
+		Field::new("value", Type::Union(1, Box::new(Type::Double(2.5)))),
+		Field::new("next_id", Type::Union(0, Box::new(Type::Null)))
+	])).unwrap();
+	let datafile = data_writer.take_datafile().unwrap();
+	let reader = SliceReader::new(&datafile).unwrap();
+	let records: Vec<TypeRef> = reader.records().unwrap().map(|r| r.unwrap()).collect();
+	assert_eq!(records, vec![TypeRef::Record {
+		name: "reading",
+		fields: vec![
+			("id", TypeRef::Fixed(&[1, 2, 3, 4])),
+			("value", TypeRef::Union(1, Box::new(TypeRef::Double(2.5)))),
+			("next_id", TypeRef::Union(0, Box::new(TypeRef::Null)))
+		]
+	}]);
+}
+
+#[test]
+fn reading_compressed_blocks() {
+	for codec in vec![Codec::Snappy, Codec::Deflate].into_iter() {
+		let datafile = nested_datafile(codec, 10);
+		let reader = SliceReader::new(&datafile).unwrap();
+		match reader.records() {
+			Err(AvroErr::Codec { .. }) => {}
+			other => panic!("Expected a codec error, found {:?}", other)
+		}
+		let mut names = vec![];
+		for block in reader.blocks() {
+			let block = block.unwrap();
+			let data = block.decompress().unwrap();
+			for record in reader.block_records(&data, block.count) {
+				match record.unwrap() {
+					TypeRef::Record { ref fields, .. } => match fields[0].1 {
+						TypeRef::Str(s) => names.push(s.to_string()),
+						ref other => panic!("Expected a string, found {:?}", other)
+					},
+					other => panic!("Expected a record, found {:?}", other)
+				}
+			}
+		}
+		let expected: Vec<String> = (0..10).map(|i| format!("record {}", i)).collect();
+		assert_eq!(names, expected);
+	}
+}
+
+#[test]
+fn reading_truncated_datafile() {
+	let datafile = nested_datafile(Codec::Null, 5);
+	let reader = SliceReader::new(&datafile[..datafile.len() - 1]).unwrap();
+	let blocks: Vec<_> = reader.blocks().collect();
+	assert_eq!(blocks.len(), 2);
+	let last_block = match blocks[1] {
+		Err(AvroErr::TruncatedBlock(offset)) => offset,
+		ref other => panic!("Expected a truncated block, found {:?}", other)
+	};
+	assert!(last_block > blocks[0].as_ref().unwrap().offset);
+	let records: Vec<_> = reader.records().unwrap().collect();
+	assert_eq!(records.len(), 4);
+	assert!(records[..3].iter().all(|r| r.is_ok()));
+	assert!(records[3].is_err());
+}
+
+#[cfg(feature = "memmap")]
+#[test]
+fn reading_memory_mapped_datafile() {
+	use std::env;
+	use std::fs::File;
+	use std::io::Write;
+	use ravro::slice_reader::map_file;
+
+	let path = env::temp_dir().join("ravro_slice_reading_mmap.avro");
+	File::create(&path).unwrap().write_all(&nested_datafile(Codec::Null, 5)).unwrap();
+	let mmap = map_file(&path).unwrap();
+	let reader = SliceReader::new(&mmap).unwrap();
+	assert_eq!(reader.records().unwrap().filter(|r| r.is_ok()).count(), 5);
+}
+
+#[test]
+fn filtering_and_reencoding_borrowed_records() {
+	let datafile = nested_datafile(Codec::Null, 10);
+	let reader = SliceReader::new(&datafile).unwrap();
+	let mut data_writer = test_writer("tests/schemas/nested_schema.avsc", Codec::Deflate);
+	let mut kept = vec![];
+	for record in reader.records().unwrap() {
+		let record = record.unwrap();
+		let keep = match record {
+			TypeRef::Record { ref fields, .. } => match fields[2].1 {
+				TypeRef::Record { ref fields, .. } => fields[0].1 == TypeRef::Long(0) || fields[0].1 == TypeRef::Long(7),
+				_ => false
+			},
+			_ => false
+		};
+		if keep {
+			data_writer.write_ref(&record).unwrap();
+			kept.push(record.into_owned());
+		}
+	}
+	assert_eq!(kept.len(), 2);
+	let reencoded = data_writer.take_datafile().unwrap();
+	let read_back: Vec<Type> = AvroReader::new(Cursor::new(reencoded)).unwrap().map(|r| r.unwrap()).collect();
+	assert_eq!(read_back, kept);
+}
+
+#[test]
+fn writing_borrowed_value_of_wrong_type() {
+	let mut data_writer = test_writer("tests/schemas/string_schema.avsc", Codec::Null);
+	assert!(data_writer.write_ref(&TypeRef::Long(3)).is_err());
+	data_writer.write_ref(&TypeRef::Str("borrowed")).unwrap();
+	let datafile = data_writer.take_datafile().unwrap();
+	let reader = SliceReader::new(&datafile).unwrap();
+	let records: Vec<TypeRef> = reader.records().unwrap().map(|r| r.unwrap()).collect();
+	assert_eq!(records, vec![TypeRef::Str("borrowed")]);
+}
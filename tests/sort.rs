@@ -0,0 +1,162 @@
+#![warn(unused_variables, unused_must_use)]
+
+extern crate rand;
+extern crate ravro;
+
+use rand::{Isaac64Rng, Rng, SeedableRng};
+use ravro::{AvroReader, AvroSchema, AvroWriter, Codec, Type};
+use ravro::compare::compare_values;
+use ravro::complex::{Field, Record};
+use ravro::errors::AvroErr;
+use ravro::sort::Sorter;
+use std::cmp::Ordering;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+const READING_SCHEMA: &str = r#"{"type": "record", "name": "reading", "fields": [
+	{"name": "sensor", "type": "string"},
+	{"name": "value", "type": "long", "order": "descending"},
+	{"name": "location", "type": {"type": "record", "name": "location", "fields": [
+		{"name": "zone", "type": "int"},
+		{"name": "tags", "type": {"type": "map", "values": "string"}}
+	]}, "order": "ignore"},
+	{"name": "seq", "type": "long", "order": "ignore"}
+]}"#;
+
+/// A fresh directory for the data files and temporary runs of a test
+fn test_dir(name: &str) -> PathBuf {
+	let dir = env::temp_dir().join(format!("ravro_sort_{}", name));
+	let _ = fs::remove_dir_all(&dir);
+	fs::create_dir_all(&dir).unwrap();
+	dir
+}
+
+fn reading(rng: &mut Isaac64Rng, seq: i64) -> Type {
+	let sensors = ["a", "b", "c", "é"];
+	Type::Record(Record::new("reading", None, vec![
+		Field::new("sensor", Type::Str(sensors[rng.gen_range(0, sensors.len())].to_string())),
+		Field::new("value", Type::Long(rng.gen_range(-3, 4))),
+		Field::new("location", Type::Record(Record::new("location", None, vec![
+			Field::new("zone", Type::Int(rng.gen_range(0, 3))),
+			Field::new("tags", Type::Map(Default::default()))
+		]))),
+		Field::new("seq", Type::Long(seq))
+	]))
+}
+
+fn write_readings(path: &PathBuf, codec: Codec, count: i64) -> Vec<Type> {
+	let mut rng = Isaac64Rng::from_seed(&[46][..]);
+	let mut builder = AvroWriter::from_str(READING_SCHEMA).unwrap();
+	builder.set_codec(codec);
+	let mut writer = builder.build().unwrap();
+	let readings: Vec<Type> = (0..count).map(|seq| reading(&mut rng, seq)).collect();
+	for value in &readings {
+		writer.write_type(value).unwrap();
+	}
+	let mut datafile = OpenOptions::new().truncate(true).write(true).create(true).open(path).unwrap();
+	datafile.write_all(&writer.take_datafile().unwrap()).unwrap();
+	readings
+}
+
+fn field<'a>(value: &'a Type, name: &str) -> &'a Type {
+	match *value {
+		Type::Record(ref rec) => &rec.fields.iter().find(|f| f.name == name).unwrap().ty,
+		_ => panic!("Expected a record, found {:?}", value)
+	}
+}
+
+fn long(value: &Type) -> i64 {
+	match *value {
+		Type::Long(l) => l,
+		Type::Int(i) => i64::from(i),
+		_ => panic!("Expected a long, found {:?}", value)
+	}
+}
+
+#[test]
+fn sorts_in_schema_order_across_runs() {
+	let dir = test_dir("schema_order");
+	for codec in vec![Codec::Null, Codec::Deflate, Codec::Snappy] {
+		let (input, output) = (dir.join("input.avro"), dir.join("output.avro"));
+		let readings = write_readings(&input, codec, 5000);
+		let runs_dir = dir.join("runs");
+		fs::create_dir_all(&runs_dir).unwrap();
+		let mut sorter = Sorter::new();
+		// Small runs and merges of few runs at a time make the records go through several merge passes
+		sorter.set_max_run_bytes(4096);
+		sorter.set_max_merge_runs(4);
+		sorter.set_temp_dir(&runs_dir);
+		assert_eq!(sorter.sort(&input, &output).unwrap(), 5000);
+		assert_eq!(fs::read_dir(&runs_dir).unwrap().count(), 0);
+
+		let reader = AvroReader::from_path(&output).unwrap();
+		assert_eq!(reader.get_codec(), codec);
+		let schema = reader.get_schema().compile().unwrap();
+		let sorted: Vec<Type> = reader.map(|r| r.unwrap()).collect();
+		let mut expected = readings.clone();
+		expected.sort_by(|a, b| compare_values(&schema, a, b).unwrap());
+		assert_eq!(sorted, expected);
+		for pair in sorted.windows(2) {
+			assert_ne!(compare_values(&schema, &pair[0], &pair[1]).unwrap(), Ordering::Greater);
+		}
+	}
+}
+
+#[test]
+fn sorts_by_key_fields_keeping_equal_records_in_order() {
+	let dir = test_dir("key_fields");
+	let (input, output) = (dir.join("input.avro"), dir.join("output.avro"));
+	let readings = write_readings(&input, Codec::Snappy, 3000);
+	let mut sorter = Sorter::new();
+	sorter.set_keys(&["location.zone", "value"]);
+	sorter.set_max_run_bytes(2048);
+	sorter.set_temp_dir(&dir);
+	assert_eq!(sorter.sort(&input, &output).unwrap(), 3000);
+
+	let sorted: Vec<Type> = AvroReader::from_path(&output).unwrap().map(|r| r.unwrap()).collect();
+	let key = |value: &Type| (long(field(field(value, "location"), "zone")), long(field(value, "value")));
+	let mut expected = readings.clone();
+	expected.sort_by_key(|value| key(value));
+	assert_eq!(sorted, expected);
+	// Ascending by the key fields, whatever the order attribute of the schema, and by seq among equal keys
+	for pair in sorted.windows(2) {
+		assert!((key(&pair[0]), long(field(&pair[0], "seq"))) < (key(&pair[1]), long(field(&pair[1], "seq"))));
+	}
+}
+
+#[test]
+fn sorts_data_fitting_in_a_single_run() {
+	let dir = test_dir("single_run");
+	let (input, output) = (dir.join("input.avro"), dir.join("output.avro"));
+	fs::write(&output, b"stale output").unwrap();
+	let readings = write_readings(&input, Codec::Deflate, 10);
+	let mut sorter = Sorter::new();
+	sorter.set_keys(&["seq"]);
+	sorter.set_temp_dir(&dir);
+	assert_eq!(sorter.sort(&input, &output).unwrap(), 10);
+	let reader = AvroReader::from_path(&output).unwrap();
+	assert_eq!(reader.get_schema().compile().unwrap(), AvroSchema::from_str(READING_SCHEMA).unwrap().compile().unwrap());
+	assert_eq!(reader.map(|r| r.unwrap()).collect::<Vec<_>>(), readings);
+	assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+	write_readings(&input, Codec::Null, 0);
+	assert_eq!(sorter.sort(&input, &output).unwrap(), 0);
+	assert_eq!(AvroReader::from_path(&output).unwrap().count(), 0);
+}
+
+#[test]
+fn unknown_key_fields_are_rejected() {
+	let dir = test_dir("unknown_keys");
+	let (input, output) = (dir.join("input.avro"), dir.join("output.avro"));
+	write_readings(&input, Codec::Null, 10);
+	for key in vec!["missing", "sensor.zone", "location.missing", ""] {
+		let mut sorter = Sorter::new();
+		sorter.set_keys(&[key]);
+		match sorter.sort(&input, &output) {
+			Err(AvroErr::SchemaParse { ref reason, .. }) => assert_eq!(reason, &format!("Field {} is not in the schema", key)),
+			other => panic!("Expected a schema error, found {:?}", other)
+		}
+	}
+}
@@ -0,0 +1,103 @@
+#![warn(unused_variables, unused_must_use)]
+
+extern crate ravro;
+
+mod common;
+
+use common::test_writer;
+use ravro::{AvroReader, Codec, Type};
+use ravro::errors::AvroErr;
+use std::io::Cursor;
+
+// Writes 20 strings in blocks of 10 records, returning the data file and the offset of the last block
+fn two_block_datafile(codec: Codec) -> (Vec<u8>, u64) {
+	let mut data_writer = test_writer("tests/schemas/string_schema.avsc", codec);
+	for i in 0..20 {
+		data_writer.write(format!("record {:02}", i)).unwrap();
+		if i == 9 {
+			data_writer.commit_block().unwrap();
+		}
+	}
+	let datafile = data_writer.take_datafile().unwrap();
+	let last_block = AvroReader::new(Cursor::new(datafile.clone())).unwrap()
+		.iter_block().last().unwrap().unwrap().offset;
+	(datafile, last_block)
+}
+
+fn read_until_error(datafile: Vec<u8>, read_partial: bool) -> (Vec<Type>, Option<AvroErr>) {
+	let mut reader = AvroReader::new(Cursor::new(datafile)).unwrap();
+	reader.set_read_partial_block(read_partial);
+	let mut records = vec![];
+	for record in reader {
+		match record {
+			Ok(record) => records.push(record),
+			Err(e) => return (records, Some(e))
+		}
+	}
+	(records, None)
+}
+
+#[test]
+fn clean_end_of_datafile() {
+	let (datafile, _) = two_block_datafile(Codec::Null);
+	let (records, err) = read_until_error(datafile, false);
+	assert_eq!(records.len(), 20);
+	assert!(err.is_none());
+}
+
+#[test]
+fn truncated_last_block() {
+	for codec in vec![Codec::Null, Codec::Snappy, Codec::Deflate].into_iter() {
+		let (datafile, last_block) = two_block_datafile(codec);
+		// Cut within the block count, the block data and the trailing sync marker
+		for cut in vec![last_block as usize + 1, last_block as usize + 20, datafile.len() - 1].into_iter() {
+			let (records, err) = read_until_error(datafile[..cut].to_vec(), false);
+			assert_eq!(records.len(), 10);
+			match err {
+				Some(AvroErr::TruncatedBlock(offset)) => assert_eq!(offset, last_block),
+				other => panic!("Expected a truncated block, found {:?}", other)
+			}
+		}
+	}
+}
+
+#[test]
+fn mismatching_sync_marker() {
+	let (mut datafile, last_block) = two_block_datafile(Codec::Null);
+	let last = datafile.len() - 1;
+	datafile[last] ^= 0xFF;
+	let (records, err) = read_until_error(datafile, false);
+	assert_eq!(records.len(), 10);
+	match err {
+		Some(AvroErr::SyncMarkerMismatch(offset)) => assert_eq!(offset, last_block),
+		other => panic!("Expected a sync marker mismatch, found {:?}", other)
+	}
+}
+
+#[test]
+fn partial_block_of_uncompressed_datafile() {
+	let (datafile, last_block) = two_block_datafile(Codec::Null);
+	// Each record takes 10 bytes, and the block data starts after a 1 byte count and a 2 byte size.
+	// Cutting in the middle of the fifth record leaves four complete records.
+	let cut = last_block as usize + 3 + 45;
+	let (records, err) = read_until_error(datafile[..cut].to_vec(), true);
+	assert_eq!(records.len(), 14);
+	assert_eq!(records[13], Type::Str("record 13".to_string()));
+	match err {
+		Some(AvroErr::TruncatedBlock(offset)) => assert_eq!(offset, last_block),
+		other => panic!("Expected a truncated block, found {:?}", other)
+	}
+
+	// All records are complete when only the sync marker is cut
+	let (records, err) = read_until_error(datafile[..datafile.len() - 5].to_vec(), true);
+	assert_eq!(records.len(), 20);
+	assert!(err.is_some());
+}
+
+#[test]
+fn partial_block_of_compressed_datafile() {
+	let (datafile, _) = two_block_datafile(Codec::Snappy);
+	let (records, err) = read_until_error(datafile[..datafile.len() - 5].to_vec(), true);
+	assert_eq!(records.len(), 10);
+	assert!(err.is_some());
+}
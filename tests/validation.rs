@@ -0,0 +1,101 @@
+#![warn(unused_variables, unused_must_use)]
+
+extern crate ravro;
+
+mod common;
+
+use common::test_writer;
+use ravro::{AvroReader, Codec, Type};
+use ravro::complex::{Enum, Field, Record};
+use std::collections::HashMap;
+use std::io::Cursor;
+
+fn read_all(datafile: Vec<u8>) -> Vec<Type> {
+	let reader = AvroReader::new(Cursor::new(datafile)).unwrap();
+	reader.map(|r| r.unwrap()).collect()
+}
+
+fn union_record(id: Vec<u8>, value: Type, next_id: Type) -> Record {
+	Record::new("reading", None, vec![
+		Field::new("id", Type::Fixed(id)),
+		Field::new("value", value),
+		Field::new("next_id", next_id)
+	])
+}
+
+#[test]
+fn rejected_values_are_not_written() {
+	let mut data_writer = test_writer("tests/schemas/union_schema.avsc", Codec::Null);
+	let valid = union_record(vec![1, 2, 3, 4], Type::Union(1, Box::new(Type::Double(1.5))), Type::Union(0, Box::new(Type::Null)));
+	// Fixed value of the wrong size
+	assert!(data_writer.write(union_record(vec![1, 2, 3], Type::Union(0, Box::new(Type::Null)), Type::Union(0, Box::new(Type::Null)))).is_err());
+	// Union branch that does not exist
+	assert!(data_writer.write(union_record(vec![1, 2, 3, 4], Type::Union(2, Box::new(Type::Null)), Type::Union(0, Box::new(Type::Null)))).is_err());
+	// Value not matching the union branch, rejected after the first fields were encoded
+	assert!(data_writer.write(union_record(vec![1, 2, 3, 4], Type::Union(1, Box::new(Type::Double(1.5))), Type::Union(1, Box::new(Type::Null)))).is_err());
+	// Union value not wrapped in a branch
+	assert!(data_writer.write(union_record(vec![1, 2, 3, 4], Type::Double(1.5), Type::Union(0, Box::new(Type::Null)))).is_err());
+	data_writer.write(valid.clone()).unwrap();
+	assert_eq!(read_all(data_writer.take_datafile().unwrap()), vec![Type::Record(valid)]);
+}
+
+#[test]
+fn record_fields_must_match_schema() {
+	let mut data_writer = test_writer("tests/schemas/nested_schema.avsc", Codec::Null);
+	let inner_rec = Record::new("id_rec", None, vec![Field::new("id", Type::Long(3))]);
+	let renamed = Record::new("dashboard_stats", None, vec![
+		Field::new("title", Type::Str("renamed".to_string())),
+		Field::new("foo", Type::Map(HashMap::new())),
+		Field::new("inner_rec", Type::Record(inner_rec.clone()))
+	]);
+	assert!(data_writer.write(renamed).is_err());
+	let missing = Record::new("dashboard_stats", None, vec![
+		Field::new("name", Type::Str("missing".to_string())),
+		Field::new("foo", Type::Map(HashMap::new()))
+	]);
+	assert!(data_writer.write(missing).is_err());
+	let mut map = HashMap::new();
+	map.insert("pi".to_string(), Type::Double(3.14));
+	let wrong_value = Record::new("dashboard_stats", None, vec![
+		Field::new("name", Type::Str("wrong value".to_string())),
+		Field::new("foo", Type::Map(map)),
+		Field::new("inner_rec", Type::Record(inner_rec))
+	]);
+	assert!(data_writer.write(wrong_value).is_err());
+	assert!(read_all(data_writer.take_datafile().unwrap()).is_empty());
+}
+
+#[test]
+fn ints_and_enums_are_checked() {
+	let mut data_writer = test_writer("tests/schemas/int_schema.avsc", Codec::Null);
+	data_writer.write(Type::Long(42)).unwrap();
+	assert!(data_writer.write(Type::Long(1 << 40)).is_err());
+	assert_eq!(read_all(data_writer.take_datafile().unwrap()), vec![Type::Int(42)]);
+
+	let mut data_writer = test_writer("tests/schemas/enum_schema.avsc", Codec::Null);
+	let mut unknown = Enum::new("Foo", &["CLUBS", "HEART"]);
+	unknown.set_value("HEART");
+	assert!(data_writer.write(Type::Enum(unknown)).is_err());
+	// Symbols are resolved against the schema, not the symbols of the value
+	let mut reordered = Enum::new("Foo", &["DIAMOND", "CLUBS"]);
+	reordered.set_value("DIAMOND");
+	data_writer.write(Type::Enum(reordered)).unwrap();
+	let mut expected = Enum::new("Foo", &["CLUBS", "SPADE", "DIAMOND"]);
+	expected.set_value("DIAMOND");
+	assert_eq!(read_all(data_writer.take_datafile().unwrap()), vec![Type::Enum(expected)]);
+}
+
+#[test]
+fn empty_collections_round_trip() {
+	let mut data_writer = test_writer("tests/schemas/array_schema.avsc", Codec::Null);
+	data_writer.write(Type::Array(vec![])).unwrap();
+	data_writer.write(Type::Array(vec![Type::Str("a".to_string())])).unwrap();
+	assert_eq!(read_all(data_writer.take_datafile().unwrap()),
+		vec![Type::Array(vec![]), Type::Array(vec![Type::Str("a".to_string())])]);
+
+	let mut data_writer = test_writer("tests/schemas/map_schema.avsc", Codec::Null);
+	data_writer.write(Type::Map(HashMap::new())).unwrap();
+	data_writer.write_type(&Type::Map(HashMap::new())).unwrap();
+	assert_eq!(read_all(data_writer.take_datafile().unwrap()),
+		vec![Type::Map(HashMap::new()), Type::Map(HashMap::new())]);
+}